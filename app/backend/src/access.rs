@@ -0,0 +1,313 @@
+use std::borrow::Cow;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use percent_encoding::percent_decode_str;
+use url::form_urlencoded;
+
+use crate::errors::AppError;
+use crate::settings::ServerSettings;
+
+const MIN_TOKEN_LEN: usize = 16;
+pub const TOKEN_COOKIE: &str = "audiodl_token";
+
+pub struct AccessPolicy {
+    token: Option<String>,
+    // User token to user name.
+    users: Vec<(String, String)>,
+    allowed: Vec<IpRange>,
+}
+
+// Which user a request acts for; `None` is the admin token (or local mode) and sees everything.
+#[derive(Clone, Default)]
+pub struct Workspace(pub Option<String>);
+
+impl Workspace {
+    pub fn owns(&self, owner: Option<&str>) -> bool {
+        self.0.is_none() || self.0.as_deref() == owner
+    }
+
+    // Settings, cookies and other machine-wide state belong to the admin token alone.
+    pub fn require_admin(&self) -> Result<(), AppError> {
+        match self.0 {
+            None => Ok(()),
+            Some(_) => Err(AppError::forbidden("only the admin token can do this")),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct IpRange {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+// Refuses configurations that would expose the service without a token, so binding beyond
+// loopback has to be asked for explicitly.
+pub fn validate_server(settings: &ServerSettings) -> Result<(), String> {
+    let address: SocketAddr = settings
+        .address
+        .parse()
+        .map_err(|_| format!("invalid server address {}", settings.address))?;
+    if let Some(url) = &settings.public_url {
+        let parsed = url::Url::parse(url).map_err(|_| format!("invalid public url {url}"))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("public url {url} must use http or https"));
+        }
+    }
+    if !address.ip().is_loopback() && !settings.remote {
+        return Err(format!(
+            "binding {address} exposes the service; enable server.remote to allow it"
+        ));
+    }
+    if !settings.remote {
+        return Ok(());
+    }
+    let token = settings.auth_token.as_deref().unwrap_or("").trim();
+    if token.len() < MIN_TOKEN_LEN {
+        return Err(format!(
+            "remote mode needs server.auth_token of at least {MIN_TOKEN_LEN} characters"
+        ));
+    }
+    if let Some(tls) = &settings.tls {
+        for path in [&tls.cert_path, &tls.key_path] {
+            if !path.is_file() {
+                return Err(format!("tls file {} not found", path.display()));
+            }
+        }
+    }
+    for entry in &settings.allowed_ips {
+        parse_range(entry).ok_or_else(|| format!("invalid allowed ip {entry}"))?;
+    }
+    for (index, user) in settings.users.iter().enumerate() {
+        let name = user.name.trim();
+        let valid = |ch: char| ch.is_alphanumeric() || ch == '-' || ch == '_';
+        if name.is_empty() || !name.chars().all(valid) {
+            return Err(format!("invalid user name {}", user.name));
+        }
+        if user.token.trim().len() < MIN_TOKEN_LEN {
+            return Err(format!("token for {name} must be at least {MIN_TOKEN_LEN} characters"));
+        }
+        if user.token.trim() == token {
+            return Err(format!("token for {name} must differ from server.auth_token"));
+        }
+        let clash = settings.users[..index]
+            .iter()
+            .any(|other| other.name.trim() == name || other.token.trim() == user.token.trim());
+        if clash {
+            return Err(format!("user {name} repeats another user's name or token"));
+        }
+    }
+    Ok(())
+}
+
+impl AccessPolicy {
+    pub fn from_settings(settings: &ServerSettings) -> Self {
+        Self {
+            token: settings
+                .auth_token
+                .as_deref()
+                .map(str::trim)
+                .filter(|token| settings.remote && !token.is_empty())
+                .map(str::to_string),
+            users: settings
+                .users
+                .iter()
+                .filter(|_| settings.remote)
+                .map(|user| (user.token.trim().to_string(), user.name.trim().to_string()))
+                .collect(),
+            allowed: settings
+                .allowed_ips
+                .iter()
+                .filter(|_| settings.remote)
+                .filter_map(|entry| parse_range(entry))
+                .collect(),
+        }
+    }
+
+    // Loopback gets no pass of its own: behind a local reverse proxy every client looks like it.
+    fn allows_ip(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            IpAddr::V4(_) => ip,
+        };
+        self.allowed.is_empty() || self.allowed.iter().any(|range| range.contains(ip))
+    }
+
+    fn workspace(&self, request: &Request) -> Option<Workspace> {
+        let Some(expected) = &self.token else {
+            return Some(Workspace::default());
+        };
+        request_tokens(request).find_map(|token| {
+            if constant_time_eq(token.as_bytes(), expected.as_bytes()) {
+                return Some(Workspace::default());
+            }
+            self.users
+                .iter()
+                .find(|(user_token, _)| constant_time_eq(token.as_bytes(), user_token.as_bytes()))
+                .map(|(_, name)| Workspace(Some(name.clone())))
+        })
+    }
+}
+
+impl IpRange {
+    fn contains(self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                prefix_matches(&range.octets(), &ip.octets(), self.prefix)
+            }
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                prefix_matches(&range.octets(), &ip.octets(), self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+// The bundled frontend is public; everything that reads or changes state needs the token.
+pub async fn require_access(
+    State(policy): State<Arc<AccessPolicy>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !policy.allows_ip(peer.ip()) {
+        return AppError::forbidden("address not allowed").into_response();
+    }
+    let path = request.uri().path();
+    let protected = path.starts_with("/api/") || path.starts_with("/preview/");
+    let workspace = match policy.workspace(&request) {
+        Some(workspace) => workspace,
+        None if protected => {
+            return AppError::unauthorized("missing or invalid access token").into_response();
+        }
+        None => Workspace::default(),
+    };
+    request.extensions_mut().insert(workspace);
+    next.run(request).await
+}
+
+// Media elements and podcast apps cannot send headers, so `?token=` and the cookie also work.
+// Both arrive percent-encoded; the frontend writes the cookie with `encodeURIComponent`.
+fn request_tokens(request: &Request) -> impl Iterator<Item = Cow<'_, str>> {
+    let headers = request.headers();
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(Cow::Borrowed);
+    let cookies = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().strip_prefix(TOKEN_COOKIE)?.strip_prefix('='))
+        .map(|value| percent_decode_str(value).decode_utf8_lossy());
+    let query = request
+        .uri()
+        .query()
+        .into_iter()
+        .flat_map(|query| form_urlencoded::parse(query.as_bytes()))
+        .filter(|(key, _)| key == "token")
+        .map(|(_, value)| value);
+    bearer.into_iter().chain(cookies).chain(query)
+}
+
+fn parse_range(entry: &str) -> Option<IpRange> {
+    let entry = entry.trim();
+    let (addr, prefix) = match entry.split_once('/') {
+        Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse::<u8>().ok()?)),
+        None => (entry.parse::<IpAddr>().ok()?, None),
+    };
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = prefix.unwrap_or(max);
+    (prefix <= max).then_some(IpRange { addr, prefix })
+}
+
+fn prefix_matches(range: &[u8], ip: &[u8], prefix: u8) -> bool {
+    let full = usize::from(prefix / 8);
+    let rest = prefix % 8;
+    if range[..full] != ip[..full] {
+        return false;
+    }
+    if rest == 0 {
+        return true;
+    }
+    let mask = 0xffu8 << (8 - rest);
+    range[full] & mask == ip[full] & mask
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+
+    use super::*;
+
+    fn contains(range: &str, ip: &str) -> bool {
+        parse_range(range).unwrap().contains(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn parses_addresses_and_cidr_ranges() {
+        assert_eq!(parse_range("10.0.0.1").map(|range| range.prefix), Some(32));
+        assert_eq!(parse_range(" 192.168.0.0/16 ").map(|range| range.prefix), Some(16));
+        assert_eq!(parse_range("::1").map(|range| range.prefix), Some(128));
+        assert!(parse_range("10.0.0.0/33").is_none());
+        assert!(parse_range("fd00::/129").is_none());
+        assert!(parse_range("10.0.0.0/x").is_none());
+        assert!(parse_range("example.com").is_none());
+    }
+
+    #[test]
+    fn matches_whole_and_partial_prefix_bytes() {
+        assert!(prefix_matches(&[10, 1, 2, 3], &[10, 1, 9, 9], 16));
+        assert!(!prefix_matches(&[10, 1, 2, 3], &[10, 2, 2, 3], 16));
+        assert!(prefix_matches(&[192, 168, 0, 0], &[192, 168, 15, 1], 20));
+        assert!(!prefix_matches(&[192, 168, 0, 0], &[192, 168, 16, 1], 20));
+        assert!(prefix_matches(&[1, 2, 3, 4], &[5, 6, 7, 8], 0));
+    }
+
+    #[test]
+    fn decodes_cookie_and_query_tokens() {
+        let request = Request::builder()
+            .uri("/api/files/x?token=a%2Bb%2Fc%3D+d&other=1")
+            .header(header::AUTHORIZATION, "Bearer a+b")
+            .header(header::COOKIE, format!("theme=dark; {TOKEN_COOKIE}=a%2Bb%2Fc%3D%20d"))
+            .body(Body::empty())
+            .unwrap();
+        let tokens: Vec<String> = request_tokens(&request).map(Cow::into_owned).collect();
+        assert_eq!(tokens, ["a+b", "a+b/c= d", "a+b/c= d"]);
+    }
+
+    #[test]
+    fn allowed_ips_also_apply_to_loopback() {
+        let mut settings = ServerSettings {
+            allowed_ips: vec!["192.168.1.0/24".to_string()],
+            ..ServerSettings::default()
+        };
+        let local = AccessPolicy::from_settings(&settings);
+        assert!(local.allows_ip("127.0.0.1".parse().unwrap()));
+        settings.remote = true;
+        let remote = AccessPolicy::from_settings(&settings);
+        assert!(remote.allows_ip("192.168.1.20".parse().unwrap()));
+        assert!(remote.allows_ip("::ffff:192.168.1.20".parse().unwrap()));
+        assert!(!remote.allows_ip("127.0.0.1".parse().unwrap()));
+        assert!(!remote.allows_ip("::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn ranges_only_match_their_address_family() {
+        assert!(contains("192.168.1.0/24", "192.168.1.200"));
+        assert!(!contains("192.168.1.0/24", "192.168.2.1"));
+        assert!(contains("fd00::/8", "fd12::1"));
+        assert!(!contains("0.0.0.0/0", "::1"));
+    }
+}
@@ -0,0 +1,210 @@
+use std::path::Path;
+use std::process::Stdio;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+
+// Decoding to low-rate mono keeps the PCM small while still resolving a few thousand peaks.
+const WAVEFORM_SAMPLE_RATE: u32 = 8_000;
+// Logarithmic intensity makes the lowpass shelf of a lossy transcode show up as a hard edge.
+const SPECTROGRAM_FILTER: &str = "showspectrumpic=s=1024x512:mode=combined:scale=log:legend=1";
+
+// Decoded lossy audio overshoots full scale, so anything within a hair of it counts as clipped.
+const CLIP_THRESHOLD: f32 = 0.9999;
+
+pub struct Waveform {
+    pub duration: f64,
+    pub peaks: Vec<f32>,
+}
+
+#[derive(Default, Serialize)]
+pub struct LoudnessAnalysis {
+    pub integrated_lufs: Option<f64>,
+    pub loudness_range: Option<f64>,
+    pub true_peak_db: Option<f64>,
+    pub sample_peak_db: Option<f64>,
+    pub total_samples: u64,
+    pub clipped_samples: u64,
+    pub clip_events: u64,
+    pub clipped_percent: f64,
+}
+
+pub async fn waveform_peaks(path: &Path, points: usize) -> Result<Waveform> {
+    let output = Command::new("ffmpeg")
+        .arg("-hide_banner")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-i")
+        .arg(path)
+        .arg("-vn")
+        .arg("-ac")
+        .arg("1")
+        .arg("-ar")
+        .arg(WAVEFORM_SAMPLE_RATE.to_string())
+        .arg("-f")
+        .arg("s16le")
+        .arg("pipe:1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("ffmpeg execution failed")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("ffmpeg decoding failed: {stderr}"));
+    }
+
+    let samples: Vec<i16> = output
+        .stdout
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    let duration = samples.len() as f64 / f64::from(WAVEFORM_SAMPLE_RATE);
+    let bucket = samples.len().div_ceil(points.max(1)).max(1);
+    let peaks = samples
+        .chunks(bucket)
+        .map(|chunk| {
+            let peak = chunk
+                .iter()
+                .map(|sample| sample.unsigned_abs())
+                .max()
+                .unwrap_or(0);
+            f32::from(peak) / 32_768.0
+        })
+        .collect();
+    Ok(Waveform { duration, peaks })
+}
+
+pub async fn render_spectrogram(path: &Path) -> Result<Vec<u8>> {
+    let output = Command::new("ffmpeg")
+        .arg("-hide_banner")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-i")
+        .arg(path)
+        .arg("-lavfi")
+        .arg(SPECTROGRAM_FILTER)
+        .arg("-frames:v")
+        .arg("1")
+        .arg("-f")
+        .arg("image2pipe")
+        .arg("-c:v")
+        .arg("png")
+        .arg("pipe:1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("ffmpeg execution failed")?;
+    if !output.status.success() || output.stdout.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("ffmpeg spectrogram failed: {stderr}"));
+    }
+    Ok(output.stdout)
+}
+
+pub async fn analyze_loudness(path: &Path) -> Result<LoudnessAnalysis> {
+    // One decode feeds both loudnorm's measurement pass and a raw stream for clip counting.
+    let mut child = Command::new("ffmpeg")
+        .arg("-hide_banner")
+        .arg("-nostats")
+        .arg("-i")
+        .arg(path)
+        .arg("-map")
+        .arg("0:a:0")
+        .arg("-af")
+        .arg("loudnorm=print_format=json")
+        .arg("-f")
+        .arg("null")
+        .arg("-")
+        .arg("-map")
+        .arg("0:a:0")
+        .arg("-ac")
+        .arg("2")
+        .arg("-f")
+        .arg("f32le")
+        .arg("pipe:1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("ffmpeg execution failed")?;
+
+    let mut stderr = child
+        .stderr
+        .take()
+        .ok_or_else(|| anyhow!("ffmpeg stderr unavailable"))?;
+    let stderr_task = tokio::spawn(async move {
+        let mut output = String::new();
+        let _ = stderr.read_to_string(&mut output).await;
+        output
+    });
+
+    let mut analysis = LoudnessAnalysis::default();
+    let mut peak = 0f32;
+    let mut clipping = [false; 2];
+    if let Some(mut stdout) = child.stdout.take() {
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut pending = Vec::with_capacity(4);
+        loop {
+            let read = stdout
+                .read(&mut buffer)
+                .await
+                .context("failed to read ffmpeg output")?;
+            if read == 0 {
+                break;
+            }
+            pending.extend_from_slice(&buffer[..read]);
+            let whole = pending.len() - pending.len() % 4;
+            for bytes in pending[..whole].chunks_exact(4) {
+                let sample = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).abs();
+                let channel = (analysis.total_samples % 2) as usize;
+                analysis.total_samples += 1;
+                peak = peak.max(sample);
+                let clipped = sample >= CLIP_THRESHOLD;
+                if clipped {
+                    analysis.clipped_samples += 1;
+                    if !clipping[channel] {
+                        analysis.clip_events += 1;
+                    }
+                }
+                clipping[channel] = clipped;
+            }
+            pending.drain(..whole);
+        }
+    }
+
+    let status = child.wait().await.context("ffmpeg execution failed")?;
+    let stderr = stderr_task.await.unwrap_or_default();
+    if !status.success() {
+        return Err(anyhow!("ffmpeg analysis failed: {stderr}"));
+    }
+
+    let summary = stderr
+        .rfind('{')
+        .and_then(|start| serde_json::from_str::<serde_json::Value>(&stderr[start..]).ok())
+        .ok_or_else(|| anyhow!("ffmpeg did not report loudness"))?;
+    let measure = |key: &str| {
+        summary
+            .get(key)
+            .and_then(|value| value.as_str())
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .filter(|value| value.is_finite())
+    };
+    analysis.integrated_lufs = measure("input_i");
+    analysis.loudness_range = measure("input_lra");
+    analysis.true_peak_db = measure("input_tp");
+    analysis.sample_peak_db = (peak > 0.0).then(|| 20.0 * f64::from(peak).log10());
+    if analysis.total_samples > 0 {
+        analysis.clipped_percent =
+            analysis.clipped_samples as f64 / analysis.total_samples as f64 * 100.0;
+    }
+    Ok(analysis)
+}
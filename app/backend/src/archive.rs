@@ -0,0 +1,194 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{DownloadState, QueueItem};
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct DownloadArchive {
+    pub entries: Vec<ArchiveEntry>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub id: String,
+    pub youtube_url: String,
+    pub title: String,
+    pub artist: String,
+    pub path: PathBuf,
+    pub downloaded_at: String,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub workspace: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ArchiveVerification {
+    pub id: String,
+    pub path: PathBuf,
+    pub status: VerificationStatus,
+}
+
+#[derive(Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationStatus {
+    Ok,
+    Mismatch,
+    Missing,
+    // Recorded before checksums were introduced.
+    Unknown,
+}
+
+impl ArchiveEntry {
+    // Each workspace keeps its own record of a video, so one user's download never stands in
+    // for another's.
+    pub fn matches_key(&self, workspace: Option<&str>, id: &str) -> bool {
+        self.workspace.as_deref() == workspace && self.id == id
+    }
+}
+
+impl DownloadArchive {
+    pub fn contains(&self, workspace: Option<&str>, id: &str) -> bool {
+        self.entries.iter().any(|entry| entry.matches_key(workspace, id))
+    }
+
+    // Shows an archived download in place instead of queueing it again.
+    pub fn mark_downloaded(&self, workspace: Option<&str>, item: &mut QueueItem) -> bool {
+        let Some(entry) = self
+            .entries
+            .iter()
+            .find(|entry| entry.matches_key(workspace, &item.id))
+        else {
+            return false;
+        };
+        let Ok(meta) = fs::metadata(&entry.path) else {
+            return false;
+        };
+        item.state = DownloadState::Complete;
+        item.progress = Some(100.0);
+        item.file_path = Some(entry.path.clone());
+        item.file_size = Some(meta.len());
+        item.sha256 = entry.sha256.clone();
+        item.warning = Some(format!("already downloaded on {}", entry.downloaded_at));
+        true
+    }
+
+    pub fn record(
+        &mut self,
+        item: &QueueItem,
+        path: &Path,
+        sha256: Option<String>,
+        workspace: Option<String>,
+    ) {
+        self.entries.retain(|entry| !entry.matches_key(workspace.as_deref(), &item.id));
+        self.entries.push(ArchiveEntry {
+            id: item.id.clone(),
+            youtube_url: item.youtube_url.clone(),
+            title: item.title.clone(),
+            artist: item.artist.clone(),
+            path: path.to_path_buf(),
+            downloaded_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            sha256,
+            workspace,
+        });
+    }
+
+    pub fn remove(&mut self, workspace: Option<&str>, id: &str) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|entry| !entry.matches_key(workspace, id));
+        self.entries.len() != before
+    }
+
+    pub fn prune_missing(&mut self, owned: impl Fn(&ArchiveEntry) -> bool) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|entry| !owned(entry) || entry.path.exists());
+        before - self.entries.len()
+    }
+}
+
+pub fn verify_entry(entry: &ArchiveEntry) -> VerificationStatus {
+    if !entry.path.exists() {
+        return VerificationStatus::Missing;
+    }
+    let Some(expected) = entry.sha256.as_deref() else {
+        return VerificationStatus::Unknown;
+    };
+    match file_sha256(&entry.path) {
+        Ok(actual) if actual.eq_ignore_ascii_case(expected) => VerificationStatus::Ok,
+        Ok(_) => VerificationStatus::Mismatch,
+        Err(_) => VerificationStatus::Missing,
+    }
+}
+
+pub fn file_sha256(path: &Path) -> Result<String> {
+    let mut file =
+        fs::File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut context = digest::Context::new(&digest::SHA256);
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        context.update(&buffer[..read]);
+    }
+    Ok(context
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+pub fn load_archive(path: &Path) -> Result<DownloadArchive> {
+    if !path.exists() {
+        return Ok(DownloadArchive::default());
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+pub fn save_archive(path: &Path, archive: &DownloadArchive) -> Result<()> {
+    let content = serde_json::to_string_pretty(archive)?;
+    fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(workspace: Option<&str>, id: &str) -> ArchiveEntry {
+        ArchiveEntry {
+            id: id.to_string(),
+            youtube_url: format!("https://youtu.be/{id}"),
+            title: "Song".to_string(),
+            artist: "Band".to_string(),
+            path: PathBuf::from(format!("/music/{id}.mp3")),
+            downloaded_at: "2026-01-01T00:00:00Z".to_string(),
+            sha256: None,
+            workspace: workspace.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn entries_are_keyed_by_workspace_and_id() {
+        let mut archive = DownloadArchive {
+            entries: vec![entry(Some("alice"), "vid"), entry(None, "other")],
+        };
+        assert!(archive.contains(Some("alice"), "vid"));
+        assert!(!archive.contains(Some("bob"), "vid"));
+        assert!(!archive.contains(None, "vid"));
+        assert!(!archive.remove(Some("bob"), "vid"));
+        assert!(archive.remove(Some("alice"), "vid"));
+        assert_eq!(archive.entries.len(), 1);
+    }
+}
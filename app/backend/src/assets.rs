@@ -0,0 +1,53 @@
+use axum::http::{header, HeaderValue, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use mime_guess::MimeGuess;
+use rust_embed::RustEmbed;
+
+// Built by `pnpm run build`; debug builds read the folder from disk, release builds embed it.
+#[derive(RustEmbed)]
+#[folder = "../frontend/dist"]
+#[allow_missing = true]
+struct FrontendAssets;
+
+pub async fn serve_frontend(uri: Uri) -> Response {
+    let path = uri.path().trim_start_matches('/');
+    if path.starts_with("api/") {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let path = if path.is_empty() { "index.html" } else { path };
+    match FrontendAssets::get(path) {
+        Some(file) => asset_response(path, file),
+        // Unknown paths without an extension are client-side routes.
+        None if !path.contains('.') => match FrontendAssets::get("index.html") {
+            Some(file) => asset_response("index.html", file),
+            None => missing_bundle(),
+        },
+        None if path == "index.html" => missing_bundle(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+fn asset_response(path: &str, file: rust_embed::EmbeddedFile) -> Response {
+    let mime = MimeGuess::from_path(path).first_or_octet_stream();
+    // Vite fingerprints everything under assets/, so only index.html must be revalidated.
+    let cache = if path.starts_with("assets/") {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+    let mut response = file.data.into_owned().into_response();
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(mime.as_ref()) {
+        headers.insert(header::CONTENT_TYPE, value);
+    }
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(cache));
+    response
+}
+
+fn missing_bundle() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        "frontend bundle not found; run `pnpm run build` in app/frontend and rebuild the backend",
+    )
+        .into_response()
+}
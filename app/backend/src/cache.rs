@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::Utc;
+use sanitize_filename::sanitize;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::settings::PreviewCacheSettings;
+use crate::types::VideoInfo;
+
+#[derive(Default, Serialize)]
+pub struct EvictionReport {
+    pub removed: usize,
+    pub freed_bytes: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedVideoInfo {
+    fetched_at: i64,
+    info: VideoInfo,
+}
+
+pub fn video_id_from_url(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    let host = url
+        .host_str()?
+        .trim_start_matches("www.")
+        .trim_start_matches("m.");
+    let id = match host {
+        "youtu.be" => url.path_segments()?.next()?.to_string(),
+        "youtube.com" | "music.youtube.com" => {
+            let mut segments = url.path_segments()?;
+            match segments.next()? {
+                "watch" => url
+                    .query_pairs()
+                    .find(|(key, _)| key == "v")
+                    .map(|(_, value)| value.into_owned())?,
+                "shorts" | "embed" | "live" | "v" => segments.next()?.to_string(),
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_');
+    valid.then_some(id)
+}
+
+pub async fn load_cached_info(dir: &Path, id: &str, ttl_secs: u64) -> Option<VideoInfo> {
+    let content = tokio::fs::read(cache_path(dir, id)).await.ok()?;
+    let cached: CachedVideoInfo = serde_json::from_slice(&content).ok()?;
+    let age = Utc::now().timestamp() - cached.fetched_at;
+    (age >= 0 && (age as u64) < ttl_secs).then_some(cached.info)
+}
+
+pub async fn store_cached_info(dir: &Path, info: &VideoInfo) {
+    let cached = CachedVideoInfo {
+        fetched_at: Utc::now().timestamp(),
+        info: info.clone(),
+    };
+    let result = match serde_json::to_vec(&cached) {
+        Ok(content) => tokio::fs::write(cache_path(dir, &info.id), content)
+            .await
+            .map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+    if let Err(err) = result {
+        error!("failed to cache metadata for {}: {err}", info.id);
+    }
+}
+
+fn cache_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.json", sanitize(id)))
+}
+
+// Previews are touched whenever they are served, so modification time doubles as last use.
+pub fn touch_preview(path: &Path) {
+    if let Err(err) = fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()))
+    {
+        error!("failed to touch preview {}: {err}", path.display());
+    }
+}
+
+pub fn evict_preview_cache(dir: &Path, settings: &PreviewCacheSettings) -> EvictionReport {
+    let mut files = preview_cache_files(dir);
+    let mut report = EvictionReport::default();
+    let now = SystemTime::now();
+    let max_age = settings.max_age_secs.map(Duration::from_secs);
+    files.retain(|(path, size, modified)| {
+        let expired = max_age.is_some_and(|max_age| {
+            now.duration_since(*modified).unwrap_or_default() > max_age
+        });
+        if expired && remove_cached(path, *size, &mut report) {
+            return false;
+        }
+        true
+    });
+    if let Some(max_bytes) = settings.max_bytes {
+        files.sort_by_key(|(_, _, modified)| *modified);
+        let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+        for (path, size, _) in &files {
+            if total <= max_bytes {
+                break;
+            }
+            if remove_cached(path, *size, &mut report) {
+                total -= size;
+            }
+        }
+    }
+    report
+}
+
+pub fn clear_preview_cache(dir: &Path) -> EvictionReport {
+    let mut report = EvictionReport::default();
+    for (path, size, _) in preview_cache_files(dir) {
+        remove_cached(&path, size, &mut report);
+    }
+    report
+}
+
+fn preview_cache_files(dir: &Path) -> Vec<(PathBuf, u64, SystemTime)> {
+    [dir.to_path_buf(), dir.join("thumbnails")]
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok().filter(|meta| meta.is_file())?;
+            Some((entry.path(), meta.len(), meta.modified().ok()?))
+        })
+        .collect()
+}
+
+fn remove_cached(path: &Path, size: u64, report: &mut EvictionReport) -> bool {
+    match fs::remove_file(path) {
+        Ok(()) => {
+            report.removed += 1;
+            report.freed_bytes += size;
+            true
+        }
+        Err(err) => {
+            error!("failed to evict {}: {err}", path.display());
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_ids_from_youtube_links() {
+        let id = |url| video_id_from_url(url).unwrap_or_default();
+        assert_eq!(id("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=1s"), "dQw4w9WgXcQ");
+        assert_eq!(id("https://youtu.be/dQw4w9WgXcQ?si=x"), "dQw4w9WgXcQ");
+        assert_eq!(id("https://m.youtube.com/shorts/abc_-123"), "abc_-123");
+        assert_eq!(id("https://music.youtube.com/watch?v=abc"), "abc");
+        assert_eq!(id("https://www.youtube.com/embed/abc"), "abc");
+    }
+
+    #[test]
+    fn rejects_other_links() {
+        assert_eq!(video_id_from_url("https://www.youtube.com/playlist?list=PL1"), None);
+        assert_eq!(video_id_from_url("https://www.youtube.com/watch?v=a%2Fb"), None);
+        assert_eq!(video_id_from_url("https://vimeo.com/123"), None);
+        assert_eq!(video_id_from_url("not a url"), None);
+    }
+}
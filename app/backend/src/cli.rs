@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::handlers::{batch_report, run_import, start_download};
+use crate::jobs::JobKind;
+use crate::queues::NamedQueue;
+use crate::types::{AppState, DownloadRequest, DownloadState, ImportMode, ImportQuery};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub const USAGE: &str = "\
+usage: audio_downloader_app [--headless]
+       audio_downloader_app download --input <list> [--format <format>] [--quality <n>]
+                                     [--out <dir>] [--limit-rate <rate>] [--force]
+                                     [--allow-duplicates] [--number-tracks]
+                                     [--preset <name>]";
+
+pub enum Command {
+    Serve { headless: bool },
+    Download(DownloadArgs),
+}
+
+pub struct DownloadArgs {
+    pub input: PathBuf,
+    pub format: String,
+    pub quality: Option<u32>,
+    pub out: Option<PathBuf>,
+    pub limit_rate: Option<String>,
+    pub force: bool,
+    pub allow_duplicates: bool,
+    pub number_tracks: bool,
+    pub preset: Option<String>,
+}
+
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Command> {
+    let mut headless = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--headless" => headless = true,
+            "download" => return parse_download_args(args).map(Command::Download),
+            other => bail!("unknown argument {other}"),
+        }
+    }
+    Ok(Command::Serve { headless })
+}
+
+fn parse_download_args(mut args: impl Iterator<Item = String>) -> Result<DownloadArgs> {
+    let mut input = None;
+    let mut parsed = DownloadArgs {
+        input: PathBuf::new(),
+        format: String::new(),
+        quality: None,
+        out: None,
+        limit_rate: None,
+        force: false,
+        allow_duplicates: false,
+        number_tracks: false,
+        preset: None,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("{arg} needs a value"));
+        match arg.as_str() {
+            "--input" | "-i" => input = Some(PathBuf::from(value()?)),
+            "--format" | "-f" => parsed.format = value()?,
+            "--quality" | "-q" => {
+                let quality = value()?;
+                parsed.quality =
+                    Some(quality.parse().with_context(|| format!("invalid quality {quality}"))?);
+            }
+            "--out" | "-o" => parsed.out = Some(expand_home(value()?)),
+            "--limit-rate" => parsed.limit_rate = Some(value()?),
+            "--force" => parsed.force = true,
+            "--allow-duplicates" => parsed.allow_duplicates = true,
+            "--number-tracks" => parsed.number_tracks = true,
+            "--preset" | "-p" => parsed.preset = Some(value()?),
+            other => bail!("unknown download argument {other}"),
+        }
+    }
+    parsed.input = input.ok_or_else(|| anyhow!("download needs --input <list>"))?;
+    Ok(parsed)
+}
+
+// Quoted paths such as "~/Music" reach us unexpanded.
+fn expand_home(path: String) -> PathBuf {
+    match path.strip_prefix("~/").zip(dirs::home_dir()) {
+        Some((rest, home)) => home.join(rest),
+        None if path == "~" => dirs::home_dir().unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    }
+}
+
+// Exits with 0 when everything downloaded (or was already archived), 1 when any row failed.
+pub async fn run_download(state: AppState, args: DownloadArgs) -> Result<ExitCode> {
+    if !args.input.is_file() {
+        bail!("input list {} not found", args.input.display());
+    }
+    let queue = NamedQueue {
+        id: format!("cli-{}", uuid::Uuid::new_v4()),
+        name: "Command line".to_string(),
+        output_dir: args.out.clone(),
+        // Used when neither --format nor --preset picks one.
+        format: Some("mp3".to_string()),
+        quality: None,
+        workspace: None,
+    };
+
+    println!("importing {}", args.input.display());
+    let target = args.input.display().to_string();
+    let (job_id, _) = state
+        .jobs
+        .start(JobKind::Import, Some(target), None, None)
+        .await;
+    let query = ImportQuery {
+        refresh: false,
+        mode: ImportMode::Append,
+        queue: Some(queue.id.clone()),
+        allow_duplicates: args.allow_duplicates,
+        force: args.force,
+    };
+    let result = run_import(&state, &job_id, args.input.clone(), query).await;
+    state.jobs.finish(&job_id, result.map_err(|err| err.to_string())).await;
+    let import = state
+        .jobs
+        .get(&job_id)
+        .await
+        .ok_or_else(|| anyhow!("import job disappeared"))?;
+    if let Some(error) = &import.error {
+        bail!("import failed: {error}");
+    }
+    for warning in &import.warnings {
+        println!("skipped {warning}");
+    }
+    println!(
+        "queued {} of {} rows ({} skipped, {} failed)",
+        import.succeeded, import.total, import.skipped, import.failed
+    );
+
+    let request = DownloadRequest {
+        format: args.format,
+        quality: args.quality,
+        limit_rate: args.limit_rate,
+        number_tracks: args.number_tracks,
+        preset: args.preset,
+    };
+    let response = start_download(&state, &queue, request, None)
+        .await
+        .map_err(|err| anyhow!("{err}"))?;
+
+    let mut printed = HashMap::new();
+    let report = loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        print_progress(&state, &queue.id, &mut printed).await;
+        let batch = state.batches.lock().await.get(&response.batch_id).map(batch_report);
+        match batch {
+            Some(report) if report.finished_at.is_some() => break report,
+            Some(_) => {}
+            None => bail!("download batch disappeared"),
+        }
+    };
+
+    for item in &report.items {
+        if let Some(error) = &item.error {
+            eprintln!("failed {}: {error}", item.title);
+        }
+    }
+    let summary = &report.summary;
+    println!(
+        "{} succeeded, {} failed, {} skipped in {}s ({} bytes)",
+        summary.succeeded, summary.failed, summary.skipped, summary.elapsed_secs, summary.total_bytes
+    );
+    if let Some(playlist) = &report.playlist {
+        println!("playlist written to {}", playlist.display());
+    }
+    if summary.failed > 0 || import.failed > 0 {
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// One line per item whenever its state changes or its progress passes another 10%.
+async fn print_progress(state: &AppState, queue_id: &str, printed: &mut HashMap<String, String>) {
+    let queue = state.queue.lock().await;
+    for item in queue.iter().filter(|item| item.queue_id == queue_id) {
+        let status = match item.state {
+            DownloadState::Working => match item.progress {
+                Some(progress) => format!("{:>3}%", (progress / 10.0).floor() as u32 * 10),
+                None => "working".to_string(),
+            },
+            DownloadState::Recording => "recording".to_string(),
+            DownloadState::Complete => "done".to_string(),
+            DownloadState::Failed => "failed".to_string(),
+            DownloadState::Paused => "paused".to_string(),
+            DownloadState::Cancelled => "cancelled".to_string(),
+            DownloadState::Waiting | DownloadState::Scheduled => continue,
+        };
+        if printed.get(&item.id) == Some(&status) {
+            continue;
+        }
+        println!("{status:>9}  {} - {}", item.artist, item.title);
+        printed.insert(item.id.clone(), status);
+    }
+}
@@ -0,0 +1,47 @@
+use anyhow::{anyhow, Result};
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+
+use crate::settings::ClipboardSettings;
+
+#[derive(Clone, Serialize)]
+pub struct ClipboardCandidate {
+    pub id: String,
+    pub url: String,
+    pub detected_at: String,
+}
+
+impl ClipboardCandidate {
+    pub fn new(url: String) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            url,
+            detected_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+// arboard talks to the display server synchronously, so callers run this on a blocking thread.
+pub fn read_clipboard_text() -> Result<String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|err| anyhow!("{err}"))?;
+    clipboard.get_text().map_err(|err| anyhow!("{err}"))
+}
+
+// Only a clipboard holding a single link is considered, so copied paragraphs that happen to
+// mention a video are left alone.
+pub fn extract_media_url(settings: &ClipboardSettings, text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.is_empty() || text.contains(char::is_whitespace) {
+        return None;
+    }
+    let url = reqwest::Url::parse(text).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    let host = url.host_str()?.to_lowercase();
+    let known = settings.hosts.iter().any(|allowed| {
+        let allowed = allowed.trim().to_lowercase();
+        host == allowed || host.ends_with(&format!(".{allowed}"))
+    });
+    known.then(|| url.to_string())
+}
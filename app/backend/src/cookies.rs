@@ -0,0 +1,236 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+use crate::settings::{write_private, CookieSettings};
+
+pub const SUPPORTED_BROWSERS: [&str; 9] = [
+    "brave", "chrome", "chromium", "edge", "firefox", "opera", "safari", "vivaldi", "whale",
+];
+
+#[derive(Serialize)]
+pub struct BrowserProfiles {
+    pub browser: String,
+    pub profiles: Vec<String>,
+}
+
+// What yt-dlp is given; an uploaded file wins over a browser.
+#[derive(Clone, Default)]
+pub struct CookieSource {
+    pub file: Option<PathBuf>,
+    pub browser: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CookieStatus {
+    pub source: Option<String>,
+    pub in_use: bool,
+    pub cookie_count: usize,
+    pub expires_at: Option<String>,
+    pub expired: bool,
+}
+
+// The `--cookies-from-browser` value for the saved browser and profile.
+pub fn browser_spec(settings: &CookieSettings) -> Option<String> {
+    settings
+        .browser
+        .as_ref()
+        .map(|browser| match settings.profile.as_deref() {
+            Some(profile) if !profile.is_empty() => format!("{browser}:{profile}"),
+            _ => browser.clone(),
+        })
+}
+
+pub fn validate_cookie_settings(settings: &CookieSettings) -> Result<(), String> {
+    match settings.browser.as_deref() {
+        Some(browser) if !SUPPORTED_BROWSERS.contains(&browser) => {
+            Err(format!("unsupported browser {browser}"))
+        }
+        None if settings.profile.is_some() => Err("profile requires a browser".to_string()),
+        _ => Ok(()),
+    }
+}
+
+pub fn detect_browsers() -> Vec<BrowserProfiles> {
+    browser_roots()
+        .into_iter()
+        .filter(|(_, root, _)| root.is_dir())
+        .map(|(browser, root, layout)| BrowserProfiles {
+            browser: browser.to_string(),
+            profiles: list_profiles(&root, layout),
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy)]
+enum ProfileLayout {
+    Chromium,
+    Firefox,
+    Single,
+}
+
+fn browser_roots() -> Vec<(&'static str, PathBuf, ProfileLayout)> {
+    let mut roots = Vec::new();
+    let chromium = [
+        (
+            "brave",
+            ["BraveSoftware/Brave-Browser", "BraveSoftware/Brave-Browser"],
+        ),
+        ("chrome", ["Google/Chrome", "google-chrome"]),
+        ("chromium", ["Chromium", "chromium"]),
+        ("edge", ["Microsoft/Edge", "microsoft-edge"]),
+        ("vivaldi", ["Vivaldi", "vivaldi"]),
+    ];
+    if cfg!(target_os = "windows") {
+        if let Some(local) = dirs::data_local_dir() {
+            for (browser, [path, _]) in chromium {
+                roots.push((
+                    browser,
+                    local.join(path).join("User Data"),
+                    ProfileLayout::Chromium,
+                ));
+            }
+        }
+        if let Some(roaming) = dirs::config_dir() {
+            roots.push((
+                "opera",
+                roaming.join("Opera Software/Opera Stable"),
+                ProfileLayout::Single,
+            ));
+            roots.push((
+                "firefox",
+                roaming.join("Mozilla/Firefox/Profiles"),
+                ProfileLayout::Firefox,
+            ));
+        }
+    } else if cfg!(target_os = "macos") {
+        if let Some(support) = dirs::config_dir() {
+            for (browser, [path, _]) in chromium {
+                let path = if browser == "edge" {
+                    "Microsoft Edge"
+                } else {
+                    path
+                };
+                roots.push((browser, support.join(path), ProfileLayout::Chromium));
+            }
+            roots.push((
+                "opera",
+                support.join("com.operasoftware.Opera"),
+                ProfileLayout::Single,
+            ));
+            roots.push((
+                "firefox",
+                support.join("Firefox/Profiles"),
+                ProfileLayout::Firefox,
+            ));
+        }
+        if let Some(home) = dirs::home_dir() {
+            roots.push(("safari", home.join("Library/Safari"), ProfileLayout::Single));
+        }
+    } else {
+        if let Some(config) = dirs::config_dir() {
+            for (browser, [_, path]) in chromium {
+                roots.push((browser, config.join(path), ProfileLayout::Chromium));
+            }
+            roots.push(("opera", config.join("opera"), ProfileLayout::Single));
+        }
+        if let Some(home) = dirs::home_dir() {
+            roots.push((
+                "firefox",
+                home.join(".mozilla/firefox"),
+                ProfileLayout::Firefox,
+            ));
+        }
+    }
+    roots
+}
+
+fn list_profiles(root: &Path, layout: ProfileLayout) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut profiles: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+        .filter(|name| match layout {
+            ProfileLayout::Chromium => name == "Default" || name.starts_with("Profile "),
+            ProfileLayout::Firefox => root.join(name).join("cookies.sqlite").exists(),
+            ProfileLayout::Single => false,
+        })
+        .collect();
+    profiles.sort();
+    profiles
+}
+
+pub fn save_cookies_file(path: &Path, content: &str) -> Result<()> {
+    let expiries = parse_cookie_expiries(content);
+    if expiries.is_none() {
+        return Err(anyhow!("file is not a Netscape cookies.txt export"));
+    }
+    write_private(path, content.as_bytes())
+}
+
+pub fn cookie_status(cookies: &CookieSource) -> CookieStatus {
+    let env_file = std::env::var("YTDLP_COOKIES")
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+    let (source, path) = match (cookies.file.clone(), env_file) {
+        (Some(path), _) => (Some("uploaded".to_string()), Some(path)),
+        (None, Some(path)) => (Some("env".to_string()), Some(PathBuf::from(path))),
+        (None, None) => (None, None),
+    };
+    if source.is_none() {
+        if let Some(browser) = &cookies.browser {
+            return CookieStatus {
+                source: Some(format!("browser:{browser}")),
+                in_use: true,
+                cookie_count: 0,
+                expires_at: None,
+                expired: false,
+            };
+        }
+    }
+
+    let expiries = path
+        .as_deref()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| parse_cookie_expiries(&content))
+        .unwrap_or_default();
+    let session_cookies = expiries.iter().filter(|expiry| **expiry == 0).count();
+    let expires_at = expiries
+        .iter()
+        .filter(|expiry| **expiry > 0)
+        .max()
+        .and_then(|expiry| DateTime::<Utc>::from_timestamp(*expiry, 0));
+    let expired =
+        session_cookies == 0 && expires_at.is_some_and(|expires_at| expires_at < Utc::now());
+
+    CookieStatus {
+        in_use: source.is_some() && !expiries.is_empty(),
+        source,
+        cookie_count: expiries.len(),
+        expires_at: expires_at.map(|at| at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        expired,
+    }
+}
+
+fn parse_cookie_expiries(content: &str) -> Option<Vec<i64>> {
+    let mut expiries = Vec::new();
+    for line in content.lines() {
+        let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 7 {
+            return None;
+        }
+        expiries.push(fields[4].trim().parse().ok()?);
+    }
+    Some(expiries).filter(|expiries| !expiries.is_empty())
+}
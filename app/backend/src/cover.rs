@@ -0,0 +1,225 @@
+use std::process::Stdio;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::error;
+
+use crate::media::fetch_thumbnail;
+use crate::settings::CoverArtSettings;
+use crate::types::QueueItem;
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverArtProvider {
+    CoverArtArchive,
+    Itunes,
+    Thumbnail,
+}
+
+#[derive(Deserialize)]
+struct ItunesSearch {
+    results: Vec<ItunesResult>,
+}
+
+#[derive(Deserialize)]
+struct ItunesResult {
+    #[serde(rename = "artistName")]
+    artist_name: Option<String>,
+    #[serde(rename = "artworkUrl100")]
+    artwork_url: Option<String>,
+}
+
+pub async fn fetch_cover_art(
+    client: &reqwest::Client,
+    settings: &CoverArtSettings,
+    item: &QueueItem,
+) -> Option<Vec<u8>> {
+    for provider in &settings.providers {
+        let result = match provider {
+            CoverArtProvider::CoverArtArchive => fetch_cover_art_archive(client, item).await,
+            CoverArtProvider::Itunes => fetch_itunes_artwork(client, settings, item).await,
+            CoverArtProvider::Thumbnail => fetch_best_thumbnail(client, item).await,
+        };
+        match result {
+            Ok(Some(bytes)) if !bytes.is_empty() => return Some(bytes),
+            Ok(_) => {}
+            Err(err) => error!("cover art provider failed for {}: {err}", item.id),
+        }
+    }
+    None
+}
+
+async fn fetch_best_thumbnail(
+    client: &reqwest::Client,
+    item: &QueueItem,
+) -> Result<Option<Vec<u8>>> {
+    let mut candidates: Vec<&str> = item.thumbnail_url.as_deref().into_iter().collect();
+    candidates.extend(
+        item.thumbnail_candidates
+            .iter()
+            .map(String::as_str)
+            .filter(|url| Some(*url) != item.thumbnail_url.as_deref()),
+    );
+
+    let mut last_error = None;
+    for url in candidates {
+        match fetch_thumbnail(client, url).await {
+            Ok(bytes) => return Ok(Some(bytes)),
+            Err(err) => last_error = Some(err),
+        }
+    }
+    match last_error {
+        Some(err) => Err(err),
+        None => Ok(None),
+    }
+}
+
+async fn fetch_cover_art_archive(
+    client: &reqwest::Client,
+    item: &QueueItem,
+) -> Result<Option<Vec<u8>>> {
+    let Some(release_id) = item.musicbrainz_release_id.as_deref() else {
+        return Ok(None);
+    };
+    let url = format!("https://coverartarchive.org/release/{release_id}/front-1200");
+    let response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+    Ok(Some(response.bytes().await?.to_vec()))
+}
+
+async fn fetch_itunes_artwork(
+    client: &reqwest::Client,
+    settings: &CoverArtSettings,
+    item: &QueueItem,
+) -> Result<Option<Vec<u8>>> {
+    let artist = item.artists.first().unwrap_or(&item.artist);
+    let term = format!("{artist} {}", item.title);
+    let response = client
+        .get("https://itunes.apple.com/search")
+        .query(&[("term", term.as_str()), ("entity", "song"), ("limit", "5")])
+        .send()
+        .await?
+        .error_for_status()?;
+    let search: ItunesSearch = response.json().await?;
+
+    let artist_lower = artist.to_lowercase();
+    let Some(artwork_url) = search
+        .results
+        .into_iter()
+        .filter(|result| {
+            result
+                .artist_name
+                .as_deref()
+                .map(|name| name.to_lowercase().contains(&artist_lower))
+                .unwrap_or(false)
+        })
+        .find_map(|result| result.artwork_url)
+    else {
+        return Ok(None);
+    };
+
+    let size = settings.itunes_size;
+    let url = artwork_url.replace("100x100bb", &format!("{size}x{size}bb"));
+    let response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "itunes artwork request failed: {}",
+            response.status()
+        ));
+    }
+    Ok(Some(response.bytes().await?.to_vec()))
+}
+
+pub async fn process_cover_art(settings: &CoverArtSettings, bytes: Vec<u8>) -> Vec<u8> {
+    if !settings.square_crop && settings.max_dimension.is_none() && settings.max_bytes.is_none() {
+        return bytes;
+    }
+    match resize_cover_art(settings, &bytes).await {
+        Ok(processed) if !processed.is_empty() => processed,
+        Ok(_) => bytes,
+        Err(err) => {
+            error!("cover art processing failed: {err}");
+            bytes
+        }
+    }
+}
+
+async fn resize_cover_art(settings: &CoverArtSettings, bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut filters = Vec::new();
+    if settings.square_crop {
+        filters.push("crop='min(iw,ih)':'min(iw,ih)'".to_string());
+    }
+    if let Some(max) = settings.max_dimension {
+        filters.push(format!(
+            "scale='min({max},iw)':'min({max},ih)':force_original_aspect_ratio=decrease"
+        ));
+    }
+
+    let mut quality = 2;
+    loop {
+        let output = run_ffmpeg_image(bytes, &filters, quality).await?;
+        let fits = settings
+            .max_bytes
+            .map(|max| output.len() as u64 <= max)
+            .unwrap_or(true);
+        if fits || quality >= 31 {
+            return Ok(output);
+        }
+        quality += 4;
+    }
+}
+
+pub async fn resize_image(bytes: &[u8], max_dimension: u32) -> Result<Vec<u8>> {
+    let filters = [format!(
+        "scale='min({max_dimension},iw)':'min({max_dimension},ih)':force_original_aspect_ratio=decrease"
+    )];
+    run_ffmpeg_image(bytes, &filters, 4).await
+}
+
+async fn run_ffmpeg_image(bytes: &[u8], filters: &[String], quality: u32) -> Result<Vec<u8>> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.arg("-hide_banner")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-i")
+        .arg("pipe:0");
+    if !filters.is_empty() {
+        cmd.arg("-vf").arg(filters.join(","));
+    }
+    cmd.arg("-frames:v")
+        .arg("1")
+        .arg("-q:v")
+        .arg(quality.min(31).to_string())
+        .arg("-f")
+        .arg("mjpeg")
+        .arg("pipe:1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = cmd.spawn().context("ffmpeg execution failed")?;
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("ffmpeg stdin unavailable"))?;
+    let input = bytes.to_vec();
+    let writer = tokio::spawn(async move {
+        let _ = stdin.write_all(&input).await;
+    });
+    let output = child
+        .wait_with_output()
+        .await
+        .context("ffmpeg execution failed")?;
+    let _ = writer.await;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("ffmpeg image processing failed: {stderr}"));
+    }
+    Ok(output.stdout)
+}
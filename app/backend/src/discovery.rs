@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use anyhow::{Context, Result};
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+
+pub const SERVICE_TYPE: &str = "_audiodl._tcp.local.";
+
+// The daemon keeps answering queries until it is dropped, so the caller holds on to it.
+pub fn advertise(address: SocketAddr) -> Result<Option<ServiceDaemon>> {
+    if address.ip().is_loopback() {
+        return Ok(None);
+    }
+    let host = std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| "audiodl".to_string());
+    let properties = HashMap::from([
+        ("version".to_string(), env!("CARGO_PKG_VERSION").to_string()),
+        ("path".to_string(), "/".to_string()),
+    ]);
+    let host_name = format!("{host}.local.");
+    let info = if address.ip().is_unspecified() {
+        ServiceInfo::new(SERVICE_TYPE, &host, &host_name, (), address.port(), properties)
+            .map(ServiceInfo::enable_addr_auto)
+    } else {
+        ServiceInfo::new(SERVICE_TYPE, &host, &host_name, address.ip(), address.port(), properties)
+    }
+    .context("failed to describe mdns service")?;
+    let daemon = ServiceDaemon::new().context("failed to start mdns daemon")?;
+    daemon.register(info).context("failed to register mdns service")?;
+    Ok(Some(daemon))
+}
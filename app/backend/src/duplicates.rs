@@ -0,0 +1,120 @@
+use crate::archive::ArchiveEntry;
+use crate::types::QueueItem;
+
+// Re-uploads of the same track rarely differ by more than an intro or outro card.
+const DURATION_TOLERANCE_SECS: u64 = 5;
+// Normalized titles and artists this similar still count as one song, which absorbs typos and
+// small wording differences between uploads.
+const MIN_SIMILARITY: f64 = 0.85;
+
+// Callers pass only what the item's workspace owns so matches never reveal another user's titles.
+pub fn find_duplicate<'a>(
+    queue: impl IntoIterator<Item = &'a QueueItem>,
+    item: &QueueItem,
+) -> Option<&'a QueueItem> {
+    let title = normalize(&item.title);
+    if title.is_empty() {
+        return None;
+    }
+    let artist = normalize(&item.artist);
+    queue.into_iter().find(|existing| {
+        !existing.matches_key(&item.queue_id, &item.id)
+            && similar(&normalize(&existing.title), &title)
+            && similar(&normalize(&existing.artist), &artist)
+            && durations_close(existing.duration, item.duration)
+    })
+}
+
+// The archive does not keep durations, so similar titles and artists are enough there.
+pub fn find_archived_duplicate<'a>(
+    entries: impl IntoIterator<Item = &'a ArchiveEntry>,
+    item: &QueueItem,
+) -> Option<&'a ArchiveEntry> {
+    let title = normalize(&item.title);
+    if title.is_empty() {
+        return None;
+    }
+    let artist = normalize(&item.artist);
+    entries.into_iter().find(|entry| {
+        entry.id != item.id
+            && similar(&normalize(&entry.title), &title)
+            && similar(&normalize(&entry.artist), &artist)
+    })
+}
+
+pub fn duplicate_message(title: &str, artist: &str) -> String {
+    format!("possible duplicate of \"{title}\" by {artist}")
+}
+
+// Drops bracketed qualifiers such as "(Official Video)" or "[HD]", featured artists,
+// punctuation and case so different uploads of one song compare equal.
+fn normalize(text: &str) -> String {
+    let mut depth = 0usize;
+    let mut stripped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => stripped.push(ch),
+            _ => {}
+        }
+    }
+    let lower = stripped.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let end = words
+        .iter()
+        .position(|word| matches!(*word, "feat" | "ft" | "featuring"))
+        .unwrap_or(words.len());
+    words[..end].join(" ")
+}
+
+fn durations_close(a: Option<u64>, b: Option<u64>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.abs_diff(b) <= DURATION_TOLERANCE_SECS,
+        _ => true,
+    }
+}
+
+// Scores the character Levenshtein distance so identical strings get 1.0.
+fn similar(a: &str, b: &str) -> bool {
+    if a == b {
+        return true;
+    }
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = Vec::with_capacity(b.len() + 1);
+        current.push(i + 1);
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    let distance = previous[b.len()] as f64;
+    1.0 - distance / a.len().max(b.len()) as f64 >= MIN_SIMILARITY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_qualifiers_features_and_punctuation() {
+        assert_eq!(normalize("Song Title (Official Video) [HD]"), "song title");
+        assert_eq!(normalize("Song Title feat. Someone"), "song title");
+        assert_eq!(normalize("SONG-TITLE!"), "song title");
+    }
+
+    #[test]
+    fn tolerates_small_differences() {
+        assert!(similar("bohemian rhapsody", "bohemian rapsody"));
+        assert!(similar("", ""));
+        assert!(!similar("hello", "yellow"));
+        assert!(!similar("song one", "song two"));
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::metadata::date_year;
+use crate::types::QueueItem;
+
+const MUSICBRAINZ_USER_AGENT: &str = concat!(
+    "AudioDownloader/",
+    env!("CARGO_PKG_VERSION"),
+    " ( https://github.com/Xuan-Yi/Rust-Audio-Downloader )"
+);
+const MIN_SCORE: u32 = 80;
+const DURATION_TOLERANCE_SECS: u64 = 5;
+
+#[derive(Deserialize)]
+struct RecordingSearch {
+    recordings: Vec<Recording>,
+}
+
+#[derive(Deserialize)]
+struct Recording {
+    id: String,
+    score: Option<u32>,
+    length: Option<u64>,
+    #[serde(default)]
+    releases: Vec<Release>,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    id: String,
+    title: String,
+    date: Option<String>,
+    #[serde(default)]
+    media: Vec<Medium>,
+}
+
+#[derive(Deserialize)]
+struct Medium {
+    #[serde(default)]
+    track: Vec<Track>,
+}
+
+#[derive(Deserialize)]
+struct Track {
+    number: Option<String>,
+    position: Option<u32>,
+}
+
+pub struct Enrichment {
+    pub recording_id: String,
+    pub release_id: Option<String>,
+    pub album: Option<String>,
+    pub date: Option<String>,
+    pub track_number: Option<u32>,
+}
+
+pub async fn lookup_musicbrainz(
+    client: &reqwest::Client,
+    item: &QueueItem,
+) -> Result<Option<Enrichment>> {
+    let artist = item.artists.first().unwrap_or(&item.artist);
+    let query = format!(
+        "recording:\"{}\" AND artist:\"{}\"",
+        escape_lucene(&item.title),
+        escape_lucene(artist)
+    );
+    let response = client
+        .get("https://musicbrainz.org/ws/2/recording")
+        .header(reqwest::header::USER_AGENT, MUSICBRAINZ_USER_AGENT)
+        .query(&[("query", query.as_str()), ("fmt", "json"), ("limit", "10")])
+        .send()
+        .await?
+        .error_for_status()?;
+    let search: RecordingSearch = response.json().await?;
+
+    let mut candidates = search
+        .recordings
+        .into_iter()
+        .filter(|recording| recording.score.unwrap_or(0) >= MIN_SCORE);
+    let recording = match item.duration {
+        Some(duration) => candidates.find(|recording| {
+            recording
+                .length
+                .map(|length| (length / 1000).abs_diff(duration) <= DURATION_TOLERANCE_SECS)
+                .unwrap_or(false)
+        }),
+        None => candidates.next(),
+    };
+    let Some(recording) = recording else {
+        return Ok(None);
+    };
+
+    let release = recording.releases.into_iter().min_by(|a, b| {
+        let a_date = a.date.as_deref().filter(|date| !date.is_empty());
+        let b_date = b.date.as_deref().filter(|date| !date.is_empty());
+        match (a_date, b_date) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        }
+    });
+    let track_number = release.as_ref().and_then(|release| {
+        let track = release.media.first()?.track.first()?;
+        track
+            .number
+            .as_deref()
+            .and_then(|number| number.parse().ok())
+            .or(track.position)
+    });
+
+    Ok(Some(Enrichment {
+        recording_id: recording.id,
+        release_id: release.as_ref().map(|release| release.id.clone()),
+        album: release.as_ref().map(|release| release.title.clone()),
+        date: release
+            .as_ref()
+            .and_then(|release| release.date.clone())
+            .filter(|date| date_year(date).is_some()),
+        track_number,
+    }))
+}
+
+pub fn apply_enrichment(item: &mut QueueItem, enrichment: Enrichment, overwrite: bool) {
+    item.musicbrainz_recording_id = Some(enrichment.recording_id);
+    if enrichment.release_id.is_some() {
+        item.musicbrainz_release_id = enrichment.release_id;
+    }
+    if overwrite || item.album.is_none() {
+        item.album = enrichment.album.or(item.album.take());
+    }
+    if overwrite || item.year.is_none() || item.year_from_upload {
+        if let Some(date) = enrichment.date {
+            item.year = date_year(&date);
+            item.year_from_upload = false;
+            item.date = Some(date);
+        }
+    }
+    if overwrite || item.track_number.is_none() {
+        item.track_number = enrichment.track_number.or(item.track_number);
+    }
+}
+
+fn escape_lucene(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -1,6 +1,9 @@
+use std::fmt;
+
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug)]
 pub struct AppError {
@@ -23,6 +26,20 @@ impl AppError {
         }
     }
 
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::UNAUTHORIZED,
+            message: message.into(),
+        }
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::FORBIDDEN,
+            message: message.into(),
+        }
+    }
+
     pub fn conflict(message: impl Into<String>) -> Self {
         Self {
             status: StatusCode::CONFLICT,
@@ -30,6 +47,20 @@ impl AppError {
         }
     }
 
+    pub fn insufficient_storage(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::INSUFFICIENT_STORAGE,
+            message: message.into(),
+        }
+    }
+
+    pub fn not_implemented(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::NOT_IMPLEMENTED,
+            message: message.into(),
+        }
+    }
+
     pub fn internal(message: impl Into<String>) -> Self {
         Self {
             status: StatusCode::INTERNAL_SERVER_ERROR,
@@ -38,9 +69,99 @@ impl AppError {
     }
 }
 
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let payload = serde_json::json!({ "error": self.message });
         (self.status, Json(payload)).into_response()
     }
 }
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    AgeRestricted,
+    Private,
+    MembersOnly,
+    Removed,
+    GeoBlocked,
+    RateLimited,
+    Drm,
+    Network,
+    Unknown,
+}
+
+impl ErrorCode {
+    pub fn from_yt_dlp_output(output: &str) -> Self {
+        let output = output.to_lowercase();
+        let matches = |needles: &[&str]| needles.iter().any(|needle| output.contains(needle));
+        if matches(&["confirm your age", "age-restricted", "inappropriate for some users"]) {
+            Self::AgeRestricted
+        } else if matches(&["private video", "video is private"]) {
+            Self::Private
+        } else if matches(&["members-only", "members only", "join this channel"]) {
+            Self::MembersOnly
+        } else if matches(&["not available in your country", "geo restrict", "in your country"]) {
+            Self::GeoBlocked
+        } else if matches(&["http error 429", "too many requests", "not a bot", "rate-limit"]) {
+            Self::RateLimited
+        } else if matches(&["this video is drm protected"]) {
+            Self::Drm
+        } else if matches(&[
+            "has been removed",
+            "no longer available",
+            "account associated with this video has been terminated",
+            "video unavailable",
+        ]) {
+            Self::Removed
+        } else if matches(&[
+            "unable to download webpage",
+            "getaddrinfo",
+            "name resolution",
+            "connection reset",
+            "timed out",
+            "network is unreachable",
+        ]) {
+            Self::Network
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DownloadError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub exit_status: Option<i32>,
+}
+
+impl DownloadError {
+    pub fn from_yt_dlp_output(lines: &[String], exit_status: Option<i32>) -> Self {
+        let output = lines.join("\n");
+        let message = lines
+            .iter()
+            .rev()
+            .find_map(|line| line.strip_prefix("ERROR:"))
+            .map(|line| line.trim().to_string())
+            .unwrap_or_else(|| "yt-dlp download failed".to_string());
+        Self {
+            code: ErrorCode::from_yt_dlp_output(&output),
+            message,
+            exit_status,
+        }
+    }
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for DownloadError {}
@@ -0,0 +1,164 @@
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+#[derive(Default)]
+pub struct Feed {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub image: Option<String>,
+    pub episodes: Vec<FeedEpisode>,
+}
+
+#[derive(Clone, Default)]
+pub struct FeedEpisode {
+    pub guid: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub enclosure_url: Option<String>,
+    pub image: Option<String>,
+    pub duration: Option<u64>,
+    pub published: Option<String>,
+}
+
+impl FeedEpisode {
+    pub fn id(&self) -> Option<String> {
+        let key = self.guid.as_ref().or(self.enclosure_url.as_ref())?;
+        // FNV-1a keeps the id stable across restarts and safe to use in file names.
+        let hash = key.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+        Some(format!("feed-{hash:016x}"))
+    }
+
+    pub fn upload_date(&self) -> Option<String> {
+        let published = self.published.as_deref()?.trim();
+        let date = DateTime::parse_from_rfc2822(published)
+            .or_else(|_| DateTime::parse_from_rfc3339(published))
+            .ok()?;
+        Some(date.format("%Y%m%d").to_string())
+    }
+}
+
+pub fn parse_feed(content: &str) -> Result<Feed> {
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(true);
+    let mut feed = Feed::default();
+    let mut path: Vec<String> = Vec::new();
+    let mut episode: Option<FeedEpisode> = None;
+    loop {
+        let event = reader
+            .read_event()
+            .with_context(|| format!("invalid feed at byte {}", reader.buffer_position()))?;
+        match event {
+            Event::Start(element) => {
+                let name = local_name(&element);
+                if matches!(name.as_str(), "item" | "entry") {
+                    episode = Some(FeedEpisode::default());
+                }
+                read_attributes(&reader, &element, &name, &mut feed, episode.as_mut());
+                path.push(name);
+            }
+            Event::Empty(element) => {
+                let name = local_name(&element);
+                read_attributes(&reader, &element, &name, &mut feed, episode.as_mut());
+            }
+            Event::End(_) => {
+                let name = path.pop().unwrap_or_default();
+                if matches!(name.as_str(), "item" | "entry") {
+                    if let Some(episode) = episode.take().filter(|ep| ep.enclosure_url.is_some()) {
+                        feed.episodes.push(episode);
+                    }
+                }
+            }
+            Event::Text(text) => {
+                let text = text.unescape().context("invalid feed text")?;
+                read_text(&path, text.trim(), &mut feed, episode.as_mut());
+            }
+            Event::CData(data) => {
+                let text = String::from_utf8_lossy(&data);
+                read_text(&path, text.trim(), &mut feed, episode.as_mut());
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(feed)
+}
+
+fn local_name(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).into_owned()
+}
+
+fn read_attributes(
+    reader: &Reader<&[u8]>,
+    element: &BytesStart,
+    name: &str,
+    feed: &mut Feed,
+    episode: Option<&mut FeedEpisode>,
+) {
+    let attribute = |key: &str| {
+        element
+            .attributes()
+            .flatten()
+            .find(|attr| attr.key.local_name().as_ref() == key.as_bytes())
+            .and_then(|attr| attr.decode_and_unescape_value(reader).ok())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    match (name, episode) {
+        ("enclosure", Some(episode)) => {
+            let is_audio = attribute("type").is_none_or(|kind| kind.starts_with("audio/"));
+            if is_audio && episode.enclosure_url.is_none() {
+                episode.enclosure_url = attribute("url");
+            }
+        }
+        ("link", Some(episode))
+            if episode.enclosure_url.is_none()
+                && attribute("rel").as_deref() == Some("enclosure") =>
+        {
+            episode.enclosure_url = attribute("href");
+        }
+        ("image", Some(episode)) => episode.image = attribute("href").or(episode.image.take()),
+        ("image", None) => feed.image = attribute("href").or(feed.image.take()),
+        _ => {}
+    }
+}
+
+fn read_text(path: &[String], text: &str, feed: &mut Feed, episode: Option<&mut FeedEpisode>) {
+    if text.is_empty() {
+        return;
+    }
+    let current = path.last().map(String::as_str).unwrap_or("");
+    let parent = path.len().checked_sub(2).map(|index| path[index].as_str());
+    let value = Some(text.to_string());
+    match episode {
+        Some(episode) => match (current, parent) {
+            ("title", Some("item" | "entry")) => episode.title = value,
+            ("guid" | "id", Some("item" | "entry")) => episode.guid = value,
+            ("author", Some("item" | "entry")) | ("name", Some("author")) => {
+                episode.author = episode.author.take().or(value)
+            }
+            ("duration", _) => episode.duration = parse_duration(text),
+            ("pubDate" | "published", _) => episode.published = value,
+            ("updated", _) => episode.published = episode.published.take().or(value),
+            _ => {}
+        },
+        None => match (current, parent) {
+            ("title", Some("channel" | "feed")) => feed.title = value,
+            ("author", Some("channel" | "feed")) | ("name", Some("author")) => {
+                feed.author = feed.author.take().or(value)
+            }
+            ("url", Some("image")) => feed.image = feed.image.take().or(value),
+            _ => {}
+        },
+    }
+}
+
+fn parse_duration(value: &str) -> Option<u64> {
+    value.split(':').try_fold(0u64, |total, part| {
+        let part: f64 = part.trim().parse().ok()?;
+        Some(total * 60 + part as u64)
+    })
+}
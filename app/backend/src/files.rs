@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use anyhow::{anyhow, bail, Context, Result};
+use tokio::process::Command;
+
+use crate::errors::AppError;
+use crate::types::{FsEntry, FsListResponse};
+
+const WRITE_PROBE: &str = ".audiodownloader-write-test";
+
+pub fn browser_roots(configured: &[PathBuf]) -> Vec<PathBuf> {
+    let roots: Vec<PathBuf> = if configured.is_empty() {
+        dirs::home_dir().into_iter().collect()
+    } else {
+        configured.to_vec()
+    };
+    roots
+        .iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .filter(|root| root.is_dir())
+        .collect()
+}
+
+// Paths are canonicalized before the root check so `..` and symlinks cannot escape a root.
+pub fn list_directory(
+    roots: &[PathBuf],
+    path: Option<&Path>,
+    hidden: bool,
+) -> Result<FsListResponse, AppError> {
+    let Some(path) = path else {
+        let entries = roots
+            .iter()
+            .map(|root| FsEntry {
+                name: root.display().to_string(),
+                path: root.clone(),
+            })
+            .collect();
+        return Ok(FsListResponse {
+            path: None,
+            parent: None,
+            writable: false,
+            entries,
+        });
+    };
+    let path = path
+        .canonicalize()
+        .map_err(|_| AppError::not_found("directory not found"))?;
+    let Some(root) = roots.iter().find(|root| path.starts_with(root)) else {
+        return Err(AppError::forbidden(
+            "directory is outside the allowed roots",
+        ));
+    };
+    if !path.is_dir() {
+        return Err(AppError::bad_request("path is not a directory"));
+    }
+    let read_dir = fs::read_dir(&path).map_err(|err| match err.kind() {
+        std::io::ErrorKind::PermissionDenied => AppError::forbidden("permission denied"),
+        _ => AppError::internal(err.to_string()),
+    })?;
+    let mut entries: Vec<FsEntry> = read_dir
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            (hidden || !name.starts_with('.')).then(|| FsEntry {
+                name,
+                path: entry.path(),
+            })
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.name.to_lowercase());
+    let parent = path
+        .parent()
+        .filter(|_| path != *root)
+        .map(Path::to_path_buf);
+    Ok(FsListResponse {
+        writable: is_writable(&path),
+        path: Some(path),
+        parent,
+        entries,
+    })
+}
+
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(WRITE_PROBE);
+    let writable = fs::File::create(&probe).is_ok();
+    if writable {
+        let _ = fs::remove_file(&probe);
+    }
+    writable
+}
+
+pub async fn reveal_in_file_manager(path: &Path) -> Result<()> {
+    if cfg!(target_os = "windows") {
+        // Explorer exits non-zero even when the window opens, so only a failed spawn is an error.
+        let mut select = std::ffi::OsString::from("/select,");
+        select.push(path);
+        Command::new("explorer")
+            .arg(select)
+            .spawn()
+            .context("failed to start explorer")?;
+        return Ok(());
+    }
+    if cfg!(target_os = "macos") {
+        return run_opener(Command::new("open").arg("-R").arg(path)).await;
+    }
+    let uri = reqwest::Url::from_file_path(path)
+        .map_err(|_| anyhow!("invalid file path {}", path.display()))?;
+    let mut show_items = Command::new("dbus-send");
+    show_items.args([
+        "--session",
+        "--dest=org.freedesktop.FileManager1",
+        "--type=method_call",
+        "/org/freedesktop/FileManager1",
+        "org.freedesktop.FileManager1.ShowItems",
+        &format!("array:string:{uri}"),
+        "string:",
+    ]);
+    if run_opener(&mut show_items).await.is_ok() {
+        return Ok(());
+    }
+    // Without a FileManager1 service the best we can do is open the containing folder.
+    let dir = path.parent().unwrap_or(path);
+    run_opener(Command::new("xdg-open").arg(dir)).await
+}
+
+async fn run_opener(command: &mut Command) -> Result<()> {
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .status()
+        .await
+        .context("failed to start file manager")?;
+    if !status.success() {
+        bail!("file manager exited with {status}");
+    }
+    Ok(())
+}
@@ -1,28 +1,92 @@
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::Arc;
 
 use anyhow::{anyhow, Context, Result};
-use axum::extract::{Multipart, Path as AxumPath, State};
+use axum::extract::{Multipart, Path as AxumPath, Query, Request, State};
+use axum::middleware::Next;
 use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
-use axum::Json;
+use axum::{Extension, Json};
+use chrono::{SecondsFormat, Utc};
 use dirs::download_dir;
+use lofty::MimeType;
 use mime_guess::MimeGuess;
 use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 use tokio::process::Command;
+use tokio::sync::OwnedSemaphorePermit;
 use tokio_util::io::ReaderStream;
-use tracing::error;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+use url::form_urlencoded;
 
-use crate::errors::AppError;
+use crate::access::{validate_server, Workspace};
+use crate::analysis::{analyze_loudness, render_spectrogram, waveform_peaks, LoudnessAnalysis};
+use crate::archive::{file_sha256, save_archive, verify_entry, ArchiveEntry, ArchiveVerification};
+use crate::cache::{clear_preview_cache, evict_preview_cache, touch_preview, EvictionReport};
+use crate::clipboard::{extract_media_url, read_clipboard_text, ClipboardCandidate};
+use crate::cookies::{
+    browser_spec, cookie_status, detect_browsers, save_cookies_file, validate_cookie_settings,
+    BrowserProfiles, CookieStatus,
+};
+use crate::cover::{fetch_cover_art, process_cover_art, resize_image, CoverArtProvider};
+use crate::duplicates::{duplicate_message, find_archived_duplicate, find_duplicate};
+use crate::enrich::{apply_enrichment, lookup_musicbrainz};
+use crate::errors::{AppError, DownloadError, ErrorCode};
+use crate::feeds::{parse_feed, Feed, FeedEpisode};
+use crate::files::{browser_roots, list_directory, reveal_in_file_manager};
+use crate::hooks::run_post_download_hook;
+use crate::jobs::{Job, JobKind, JobState};
+use crate::library::{refresh_media_library, validate_media_server};
+use crate::live::{record_livestream, wait_for_release};
+use crate::lyrics::write_lrc_sidecar;
 use crate::media::{
-    apply_yt_dlp_common_args, download_preview, fetch_thumbnail, fetch_video_info,
-    find_downloaded_file, find_preview_file, parse_yt_dlp_progress, sanitize_text, tag_audio,
+    apply_yt_dlp_common_args, detect_mime, download_preview, find_downloaded_file,
+    find_preview_file, list_playlist_ids, normalize_custom_tags, organized_dir, output_file_stem,
+    parse_yt_dlp_progress, parse_yt_dlp_stage, probe_video_info, publish_staged_files,
+    sanitize_text, tag_audio, validate_output, TransferProgress, YT_DLP_PROGRESS_TEMPLATE,
+};
+use crate::metadata::{
+    clean_title, date_year, derive_genre, format_yt_dlp_date, join_artists, normalize_tag_date,
+    apply_title_rule, parse_artists, parse_featured_from_title, split_artist_title,
+    validate_title_rules, ArtistTitle,
+};
+use crate::port::{
+    create_sample_xlsx, export_m3u8, export_music_list, export_xspf, get_version_info,
+    google_sheet_csv_url, import_music_list, podcast_feed, preview_music_list, ImportPreview,
+    MusicRow, PlaylistEntry, PodcastEntry, DEFAULT_EXPORT_COLUMNS,
+};
+use crate::notifications::notify_desktop;
+use crate::postprocess::{
+    convert_audio, post_process_audio, probe_audio_codec, transcode_audio, validate_resample,
+    verify_duration, PostProcessOutcome, ProgressTarget,
+};
+use crate::queues::{
+    default_queue, default_queue_id, save_queues, workspace_queue_id, NamedQueue, DEFAULT_QUEUE_ID,
+};
+use crate::presets::{save_presets, validate_preset_name, Preset};
+use crate::scheduler::{save_schedules, CronExpr, Schedule, ScheduleRun};
+use crate::session::{reset_interrupted, Session};
+use crate::sidecar::{write_cue_sheet, write_metadata_sidecars};
+use crate::subscriptions::{save_subscriptions, Subscription, SubscriptionKind};
+use crate::settings::{
+    save_settings, AppSettings, ConflictPolicy, CookieSettings, CoverArtSettings, PlaylistPaths,
+    WebhookEvent,
 };
-use crate::port::{create_sample_xlsx, export_music_list, get_version_info, import_music_list, MusicRow};
 use crate::types::{
-    AddRequest, AppState, ClearRequest, DefaultDirResponse, DownloadRequest, DownloadResponse,
-    DownloadState, ExportRequest, PreviewResponse, QueueItem, UpdateRequest, VersionResponse,
+    AddRequest, AppState, AttemptOutcome, AudioFormat, BatchItem, BatchReport, BatchSummary,
+    BatchUpdateRequest, BrowserSelection, CancelAllResponse, ClearRequest, CreateQueueRequest,
+    CreateScheduleRequest, CreateSubscriptionRequest, DefaultDirResponse, DownloadAttempt,
+    DownloadBatch, DownloadRequest, DownloadResponse, DownloadStage, DownloadState, EnrichRequest,
+    ExportRequest, FeedQuery, FormatsQuery, FsListQuery, FsListResponse, ImportMode, ImportQuery,
+    ItemChanges, JobResponse, PlaylistFormat, PlaylistQuery, PreviewResponse, PruneResponse,
+    QueueItem, QueueQuery, RetryFailedRequest, SheetImportRequest, SyncRemoval, SyncReport,
+    SyncRequest, ThumbnailQuery, UpdateRequest, VersionResponse, VideoInfo, WaveformQuery,
+    WaveformResponse,
 };
+use crate::upload::{upload_s3, upload_webdav, validate_s3, validate_webdav};
+use crate::webhooks::{dispatch_webhooks, validate_webhooks, WebhookData};
 
 pub async fn version_info(State(state): State<AppState>) -> Result<Json<VersionResponse>, AppError> {
     let project_root = state.project_root.clone();
@@ -43,6 +107,245 @@ pub async fn version_info(State(state): State<AppState>) -> Result<Json<VersionR
     }))
 }
 
+pub async fn get_settings(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<AppSettings>, AppError> {
+    workspace.require_admin()?;
+    let settings = state.settings.lock().await;
+    Ok(Json(settings.redacted()))
+}
+
+pub async fn update_settings(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<AppSettings>,
+) -> Result<Json<AppSettings>, AppError> {
+    workspace.require_admin()?;
+    apply_settings(&state, req).await.map(|settings| Json(settings.redacted()))
+}
+
+async fn apply_settings(state: &AppState, mut req: AppSettings) -> Result<AppSettings, AppError> {
+    req.keep_secrets(&*state.settings.lock().await);
+    validate_title_rules(&req.title_cleanup.rules).map_err(AppError::bad_request)?;
+    validate_resample(&req.resample).map_err(AppError::bad_request)?;
+    validate_cookie_settings(&req.cookies).map_err(AppError::bad_request)?;
+    validate_webhooks(&req.webhooks).map_err(AppError::bad_request)?;
+    validate_media_server(&req.media_server).map_err(AppError::bad_request)?;
+    validate_server(&req.server).map_err(AppError::bad_request)?;
+    validate_output(&req.output).map_err(AppError::bad_request)?;
+    if let Some(s3) = &req.upload.s3 {
+        validate_s3(s3).map_err(AppError::bad_request)?;
+    }
+    if let Some(webdav) = &req.upload.webdav {
+        validate_webdav(webdav).map_err(AppError::bad_request)?;
+    }
+    if let Some(rate) = req.network.limit_rate.as_deref() {
+        parse_rate_limit(rate)?;
+    }
+    if req.network.max_concurrent_downloads == 0 || req.network.max_concurrent_probes == 0 {
+        return Err(AppError::bad_request(
+            "network.max_concurrent_downloads and network.max_concurrent_probes must be at least 1",
+        ));
+    }
+
+    let mut settings = state.settings.lock().await;
+    // Hooks run shell commands, so they only come from settings.json at startup, never the API.
+    req.hooks = settings.hooks.clone();
+    save_settings(&state.settings_path, &req).map_err(|err| AppError::internal(err.to_string()))?;
+    state.cookies.lock().await.browser = browser_spec(&req.cookies);
+    let (old, new) = (&settings.network, &req.network);
+    resize_semaphore(
+        &state.download_semaphore,
+        old.max_concurrent_downloads.max(1),
+        new.max_concurrent_downloads,
+    );
+    resize_semaphore(
+        &state.metadata_semaphore,
+        old.max_concurrent_probes.max(1),
+        new.max_concurrent_probes,
+    );
+    *settings = req;
+    Ok(settings.clone())
+}
+
+// Shrinking takes back idle permits now and waits for running work to return the rest.
+fn resize_semaphore(semaphore: &Arc<tokio::sync::Semaphore>, from: usize, to: usize) {
+    if to >= from {
+        semaphore.add_permits(to - from);
+        return;
+    }
+    let owed = from - to - semaphore.forget_permits(from - to);
+    if owed > 0 {
+        let semaphore = semaphore.clone();
+        tokio::spawn(async move {
+            if let Ok(permits) = semaphore.acquire_many_owned(owed as u32).await {
+                permits.forget();
+            }
+        });
+    }
+}
+
+pub async fn refresh_media_server(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<StatusCode, AppError> {
+    workspace.require_admin()?;
+    let settings = state.settings.lock().await.media_server.clone();
+    if settings.kind.is_none() {
+        return Err(AppError::bad_request("no media server is configured"));
+    }
+    refresh_media_library(&state.client, &settings)
+        .await
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_session(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<Session>, AppError> {
+    workspace.require_admin()?;
+    Ok(Json(snapshot_session(&state).await))
+}
+
+pub async fn restore_session(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+    Json(mut session): Json<Session>,
+) -> Result<Json<Session>, AppError> {
+    workspace.require_admin()?;
+    let active = state.queue.lock().await.iter().any(|item| {
+        matches!(item.state, DownloadState::Working | DownloadState::Recording)
+    });
+    if active {
+        return Err(AppError::conflict("stop active downloads before restoring a session"));
+    }
+    apply_settings(&state, session.settings).await?;
+    session.queue.iter_mut().for_each(reset_interrupted);
+    *state.queue.lock().await = session.queue;
+    *state.batches.lock().await = session
+        .batches
+        .into_iter()
+        .map(|batch| (batch.id.clone(), batch))
+        .collect();
+    if !session.queues.is_empty() {
+        let mut queues = state.queues.lock().await;
+        *queues = session.queues;
+        if !queues.iter().any(|queue| queue.id == DEFAULT_QUEUE_ID) {
+            queues.insert(0, default_queue());
+        }
+        save_queues(&state.queues_path, &queues)
+            .map_err(|err| AppError::internal(err.to_string()))?;
+    }
+    Ok(Json(snapshot_session(&state).await))
+}
+
+async fn snapshot_session(state: &AppState) -> Session {
+    let queue = state.queue.lock().await.clone();
+    let settings = state.settings.lock().await.redacted();
+    let mut batches: Vec<DownloadBatch> = state.batches.lock().await.values().cloned().collect();
+    batches.sort_by_key(|batch| batch.started_at);
+    let queues = state.queues.lock().await.clone();
+    Session {
+        queue,
+        settings,
+        batches,
+        queues,
+    }
+}
+
+pub async fn get_cookies(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<CookieStatus>, AppError> {
+    workspace.require_admin()?;
+    Ok(Json(cookie_status(&*state.cookies.lock().await)))
+}
+
+pub async fn upload_cookies(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+    mut multipart: Multipart,
+) -> Result<Json<CookieStatus>, AppError> {
+    workspace.require_admin()?;
+    let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| AppError::bad_request(err.to_string()))?
+    else {
+        return Err(AppError::bad_request("no file uploaded"));
+    };
+    let content = field
+        .text()
+        .await
+        .map_err(|err| AppError::bad_request(err.to_string()))?;
+    let path = state.cookies_path.clone();
+    tokio::task::spawn_blocking(move || save_cookies_file(&path, &content))
+        .await
+        .map_err(|err| AppError::internal(err.to_string()))?
+        .map_err(|err| AppError::bad_request(err.to_string()))?;
+    let mut cookies = state.cookies.lock().await;
+    cookies.file = Some(state.cookies_path.clone());
+    Ok(Json(cookie_status(&cookies)))
+}
+
+pub async fn delete_cookies(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<StatusCode, AppError> {
+    workspace.require_admin()?;
+    state.cookies.lock().await.file = None;
+    if state.cookies_path.exists() {
+        tokio::fs::remove_file(&state.cookies_path)
+            .await
+            .map_err(|err| AppError::internal(err.to_string()))?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_browsers(
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<Vec<BrowserProfiles>>, AppError> {
+    workspace.require_admin()?;
+    Ok(Json(detect_browsers()))
+}
+
+pub async fn select_browser(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<BrowserSelection>,
+) -> Result<Json<CookieStatus>, AppError> {
+    workspace.require_admin()?;
+    let cookies = CookieSettings {
+        browser: Some(req.browser.trim().to_lowercase()),
+        profile: req.profile.as_deref().and_then(non_empty),
+    };
+    validate_cookie_settings(&cookies).map_err(AppError::bad_request)?;
+    store_cookie_settings(&state, cookies).await?;
+    Ok(Json(cookie_status(&*state.cookies.lock().await)))
+}
+
+pub async fn clear_browser(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<StatusCode, AppError> {
+    workspace.require_admin()?;
+    store_cookie_settings(&state, CookieSettings::default()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn store_cookie_settings(state: &AppState, cookies: CookieSettings) -> Result<(), AppError> {
+    let mut settings = state.settings.lock().await;
+    let mut updated = settings.clone();
+    updated.cookies = cookies;
+    save_settings(&state.settings_path, &updated)
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    state.cookies.lock().await.browser = browser_spec(&updated.cookies);
+    *settings = updated;
+    Ok(())
+}
+
 pub async fn default_dir() -> Json<DefaultDirResponse> {
     let path = download_dir()
         .unwrap_or_else(|| PathBuf::from("."))
@@ -51,7 +354,12 @@ pub async fn default_dir() -> Json<DefaultDirResponse> {
     Json(DefaultDirResponse { path })
 }
 
-pub async fn select_dir() -> Result<Json<DefaultDirResponse>, AppError> {
+pub async fn select_dir(State(state): State<AppState>) -> Result<Json<DefaultDirResponse>, AppError> {
+    if state.headless {
+        return Err(AppError::not_implemented(
+            "no folder picker in headless mode; browse with /api/fs/list instead",
+        ));
+    }
     let picked = tokio::task::spawn_blocking(|| rfd::FileDialog::new().pick_folder())
         .await
         .map_err(|err| AppError::internal(err.to_string()))?;
@@ -61,219 +369,2976 @@ pub async fn select_dir() -> Result<Json<DefaultDirResponse>, AppError> {
     Ok(Json(DefaultDirResponse { path }))
 }
 
-pub async fn list_queue(State(state): State<AppState>) -> Json<Vec<QueueItem>> {
-    let queue = state.queue.lock().await;
-    Json(queue.clone())
+pub async fn list_fs(
+    State(state): State<AppState>,
+    Query(query): Query<FsListQuery>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<FsListResponse>, AppError> {
+    workspace.require_admin()?;
+    let configured = state.settings.lock().await.file_browser.roots.clone();
+    tokio::task::spawn_blocking(move || {
+        let roots = browser_roots(&configured);
+        list_directory(&roots, query.path.as_deref(), query.hidden)
+    })
+    .await
+    .map_err(|err| AppError::internal(err.to_string()))?
+    .map(Json)
 }
 
-pub async fn add_queue(
+pub async fn reveal_file(
+    AxumPath(id): AxumPath<String>,
     State(state): State<AppState>,
-    Json(req): Json<AddRequest>,
-) -> Result<Json<QueueItem>, AppError> {
-    let info = fetch_video_info(&req.url).await?;
-    let title = sanitize_text(&info.title);
-    let artist = sanitize_text(&info.artist);
+    Extension(workspace): Extension<Workspace>,
+) -> Result<StatusCode, AppError> {
+    workspace.require_admin()?;
+    if state.headless {
+        return Err(AppError::not_implemented(
+            "no file manager in headless mode; download the file from /api/library/:id instead",
+        ));
+    }
+    let path = downloaded_file(&state, &workspace, &id).await?;
+    reveal_in_file_manager(&path)
+        .await
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    let item = QueueItem {
-        id: info.id.clone(),
-        youtube_url: req.url,
-        title: if title.is_empty() { "Unknown".to_string() } else { title },
-        artist: if artist.is_empty() { "Unknown".to_string() } else { artist },
-        thumbnail_url: info.thumbnail_url,
-        duration: info.duration,
-        state: DownloadState::Waiting,
-        progress: None,
-        error: None,
+pub async fn file_spectrogram(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Response, AppError> {
+    let path = match downloaded_file(&state, &workspace, &id).await {
+        Ok(path) => path,
+        Err(err) => {
+            let visible = owns_item(&state, &workspace, &id).await;
+            if !visible || state.pending_previews.lock().await.contains_key(&id) {
+                return Err(err);
+            }
+            find_preview_file(&state.preview_dir, &id).ok_or(err)?
+        }
     };
+    let png = render_spectrogram(&path)
+        .await
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(([(header::CONTENT_TYPE, "image/png")], png).into_response())
+}
 
-    let mut queue = state.queue.lock().await;
-    if queue.iter().any(|existing| existing.id == item.id) {
-        return Err(AppError::conflict("queue already contains this video"));
+pub async fn file_analysis(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<LoudnessAnalysis>, AppError> {
+    let path = downloaded_file(&state, &workspace, &id).await?;
+    let analysis = analyze_loudness(&path)
+        .await
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(Json(analysis))
+}
+
+async fn downloaded_file(
+    state: &AppState,
+    workspace: &Workspace,
+    id: &str,
+) -> Result<PathBuf, AppError> {
+    let owned = owned_queue_ids(state, workspace).await;
+    let from_queue = {
+        let queue = state.queue.lock().await;
+        queue
+            .iter()
+            .find(|item| item.id == id && owned.contains(&item.queue_id))
+            .and_then(|item| item.file_path.clone())
+    };
+    let path = match from_queue {
+        Some(path) => path,
+        None => {
+            let archive = state.archive.lock().await;
+            archive
+                .entries
+                .iter()
+                .find(|entry| entry.id == id && workspace.owns(entry.workspace.as_deref()))
+                .map(|entry| entry.path.clone())
+                .ok_or_else(|| AppError::not_found("download not found"))?
+        }
+    };
+    if !path.exists() {
+        return Err(AppError::not_found("downloaded file is missing"));
     }
-    queue.push(item.clone());
-    Ok(Json(item))
+    Ok(path)
 }
 
-pub async fn update_queue(
+pub async fn list_queues(
     State(state): State<AppState>,
-    Json(req): Json<UpdateRequest>,
-) -> Result<Json<QueueItem>, AppError> {
+    Extension(workspace): Extension<Workspace>,
+) -> Json<Vec<NamedQueue>> {
+    let queues = state.queues.lock().await;
+    Json(
+        queues
+            .iter()
+            .filter(|queue| workspace.owns(queue.workspace.as_deref()))
+            .cloned()
+            .collect(),
+    )
+}
+
+pub async fn create_queue(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<CreateQueueRequest>,
+) -> Result<Json<NamedQueue>, AppError> {
+    let name = req.name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::bad_request("queue name is required"));
+    }
+    // Downloads write into the queue's directory, so users keep the one from their settings.
+    if req.output_dir.is_some() {
+        workspace.require_admin()?;
+    }
+    let output_dir = match &workspace.0 {
+        None => req.output_dir,
+        Some(owner) => {
+            let settings = state.settings.lock().await;
+            let users = &settings.server.users;
+            users
+                .iter()
+                .find(|user| &user.name == owner)
+                .and_then(|user| user.output_dir.clone())
+        }
+    };
+    let format = req.format.as_deref().map(normalize_format).transpose()?;
+    let quality = match format {
+        Some(format) => validate_quality(format, req.quality)?,
+        None if req.quality.is_some() => {
+            return Err(AppError::bad_request("a format is required when setting quality"));
+        }
+        None => None,
+    };
+    let created = NamedQueue {
+        id: uuid::Uuid::new_v4().to_string(),
+        name,
+        output_dir,
+        format: format.map(str::to_string),
+        quality,
+        workspace: workspace.0,
+    };
+    let mut queues = state.queues.lock().await;
+    queues.push(created.clone());
+    save_queues(&state.queues_path, &queues).map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(Json(created))
+}
+
+pub async fn delete_named_queue(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<StatusCode, AppError> {
+    let user_queue = workspace.0.as_deref().map(workspace_queue_id);
+    if id == DEFAULT_QUEUE_ID || user_queue.as_deref() == Some(id.as_str()) {
+        return Err(AppError::bad_request("the default queue cannot be deleted"));
+    }
+    let mut queues = state.queues.lock().await;
+    let owned = queues
+        .iter()
+        .any(|queue| queue.id == id && workspace.owns(queue.workspace.as_deref()));
+    if !owned {
+        return Err(AppError::not_found("queue not found"));
+    }
     let mut queue = state.queue.lock().await;
-    let Some(item) = queue.iter_mut().find(|item| item.id == req.id) else {
-        return Err(AppError::not_found("queue item not found"));
+    let active = queue.iter().any(|item| {
+        item.queue_id == id
+            && matches!(item.state, DownloadState::Working | DownloadState::Recording)
+    });
+    if active {
+        return Err(AppError::conflict("queue has active downloads"));
+    }
+    queue.retain(|item| item.queue_id != id);
+    queues.retain(|queue| queue.id != id);
+    save_queues(&state.queues_path, &queues).map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Users land on their own queue by default and cannot see queues owned by anyone else.
+async fn resolve_queue(
+    state: &AppState,
+    workspace: &Workspace,
+    id: Option<&str>,
+) -> Result<NamedQueue, AppError> {
+    let id = match (id, &workspace.0) {
+        (Some(id), _) => id.to_string(),
+        (None, Some(user)) => workspace_queue_id(user),
+        (None, None) => DEFAULT_QUEUE_ID.to_string(),
     };
+    state
+        .queues
+        .lock()
+        .await
+        .iter()
+        .find(|queue| queue.id == id && workspace.owns(queue.workspace.as_deref()))
+        .cloned()
+        .ok_or_else(|| AppError::not_found("queue not found"))
+}
 
-    if let Some(title) = req.title {
-        let clean = sanitize_text(&title);
-        if !clean.is_empty() {
-            item.title = clean;
+// Subscriptions, schedules and downloaded files belong to whoever owns their queue.
+// Previews and thumbnails are cached by video id alone, so users may only read those of videos
+// queued in their own workspace.
+async fn owns_item(state: &AppState, workspace: &Workspace, id: &str) -> bool {
+    if workspace.0.is_none() {
+        return true;
+    }
+    let owned = owned_queue_ids(state, workspace).await;
+    let queue = state.queue.lock().await;
+    queue.iter().any(|item| item.id == id && owned.contains(&item.queue_id))
+}
+
+pub async fn require_preview_owner(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let id = Path::new(request.uri().path())
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('.').next())
+        .unwrap_or_default();
+    if !owns_item(&state, &workspace, id).await {
+        return Err(AppError::not_found("preview not found"));
+    }
+    Ok(next.run(request).await)
+}
+
+async fn owned_queue_ids(state: &AppState, workspace: &Workspace) -> HashSet<String> {
+    state
+        .queues
+        .lock()
+        .await
+        .iter()
+        .filter(|queue| workspace.owns(queue.workspace.as_deref()))
+        .map(|queue| queue.id.clone())
+        .collect()
+}
+
+const SUBSCRIPTION_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+const MIN_SUBSCRIPTION_INTERVAL_SECS: u64 = 5 * 60;
+
+pub async fn list_subscriptions(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Json<Vec<Subscription>> {
+    let owned = owned_queue_ids(&state, &workspace).await;
+    let subscriptions = state.subscriptions.lock().await;
+    Json(
+        subscriptions
+            .iter()
+            .filter(|subscription| owned.contains(&subscription.queue_id))
+            .cloned()
+            .collect(),
+    )
+}
+
+pub async fn create_subscription(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<CreateSubscriptionRequest>,
+) -> Result<Json<Subscription>, AppError> {
+    let url = req.url.trim().to_string();
+    if !matches!(reqwest::Url::parse(&url), Ok(parsed) if parsed.scheme().starts_with("http")) {
+        return Err(AppError::bad_request("subscription url must be an http(s) link"));
+    }
+    let target = resolve_queue(&state, &workspace, req.queue.as_deref()).await?;
+    let format = normalize_format(req.format.as_deref().unwrap_or("flac"))?;
+    let interval_secs = req.interval_secs.unwrap_or(60 * 60);
+    if interval_secs < MIN_SUBSCRIPTION_INTERVAL_SECS {
+        return Err(AppError::bad_request(format!(
+            "interval_secs must be at least {MIN_SUBSCRIPTION_INTERVAL_SECS}"
+        )));
+    }
+    let subscription = Subscription {
+        id: uuid::Uuid::new_v4().to_string(),
+        kind: req.kind,
+        url,
+        name: req.name.filter(|name| !name.trim().is_empty()),
+        queue_id: target.id,
+        interval_secs,
+        enabled: true,
+        backfill: req.backfill,
+        auto_download: req.auto_download,
+        format: format.to_string(),
+        quality: validate_quality(format, req.quality)?,
+        last_checked: None,
+        last_error: None,
+        last_added: 0,
+        seen: Vec::new(),
+    };
+    let mut subscriptions = state.subscriptions.lock().await;
+    subscriptions.push(subscription.clone());
+    save_subscriptions(&state.subscriptions_path, &subscriptions)
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(Json(subscription))
+}
+
+pub async fn delete_subscription(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<StatusCode, AppError> {
+    find_subscription(&state, &workspace, &id).await?;
+    let mut subscriptions = state.subscriptions.lock().await;
+    let before = subscriptions.len();
+    subscriptions.retain(|subscription| subscription.id != id);
+    if subscriptions.len() == before {
+        return Err(AppError::not_found("subscription not found"));
+    }
+    save_subscriptions(&state.subscriptions_path, &subscriptions)
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn check_subscription(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<Subscription>, AppError> {
+    find_subscription(&state, &workspace, &id).await?;
+    refresh_subscription(&state, &id).await.map(Json)
+}
+
+async fn find_subscription(
+    state: &AppState,
+    workspace: &Workspace,
+    id: &str,
+) -> Result<Subscription, AppError> {
+    let owned = owned_queue_ids(state, workspace).await;
+    state
+        .subscriptions
+        .lock()
+        .await
+        .iter()
+        .find(|subscription| subscription.id == id && owned.contains(&subscription.queue_id))
+        .cloned()
+        .ok_or_else(|| AppError::not_found("subscription not found"))
+}
+
+pub async fn sync_playlist_mirror(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+    body: Option<Json<SyncRequest>>,
+) -> Result<Json<SyncReport>, AppError> {
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    let subscription = find_subscription(&state, &workspace, &id).await?;
+    if subscription.kind != SubscriptionKind::Playlist {
+        return Err(AppError::bad_request("only playlist subscriptions can be mirrored"));
+    }
+    let target = resolve_queue(&state, &workspace, Some(&subscription.queue_id)).await?;
+    let cookies = state.cookies.lock().await.clone();
+    let ids = list_playlist_ids(&subscription.url, &cookies).await?;
+    let current: HashSet<&String> = ids.iter().collect();
+
+    // Archived entries whose file was deleted count as missing, so they are downloaded again.
+    let owner = target.workspace.as_deref();
+    let (missing, removed) = {
+        let archive = state.archive.lock().await;
+        let missing: Vec<String> = ids
+            .iter()
+            .filter(|id| {
+                !archive
+                    .entries
+                    .iter()
+                    .any(|entry| entry.matches_key(owner, id) && entry.path.exists())
+            })
+            .cloned()
+            .collect();
+        let removed: Vec<SyncRemoval> = archive
+            .entries
+            .iter()
+            .filter(|entry| entry.workspace.as_deref() == owner)
+            .filter(|entry| subscription.seen.contains(&entry.id) && !current.contains(&entry.id))
+            .filter(|entry| entry.path.exists())
+            .map(|entry| SyncRemoval {
+                id: entry.id.clone(),
+                title: entry.title.clone(),
+                path: entry.path.clone(),
+            })
+            .collect();
+        (missing, removed)
+    };
+    let mut report = SyncReport {
+        subscription_id: subscription.id.clone(),
+        total: ids.len(),
+        present: ids.len() - missing.len(),
+        missing,
+        queued: 0,
+        removed,
+        pruned: false,
+        batch_id: None,
+    };
+    if req.dry_run {
+        return Ok(Json(report));
+    }
+
+    let settings = state.settings.lock().await.clone();
+    let mut downloads = HashSet::new();
+    {
+        let mut archive = state.archive.lock().await;
+        for id in &report.missing {
+            archive.remove(owner, id);
         }
+        save_archive(&state.archive_path, &archive)
+            .map_err(|err| AppError::internal(err.to_string()))?;
     }
-    if let Some(artist) = req.artist {
-        let clean = sanitize_text(&artist);
-        if !clean.is_empty() {
-            item.artist = clean;
+    for id in &report.missing {
+        let queued = state.queue.lock().await.iter().any(|item| item.matches_key(&target.id, id));
+        if !queued {
+            let url = format!("https://www.youtube.com/watch?v={id}");
+            match probe_video_info(&state, &url, false).await {
+                Ok(info) => {
+                    let mut item = build_queue_item(&settings, info, &url, None);
+                    item.queue_id = target.id.clone();
+                    state.queue.lock().await.push(item);
+                    report.queued += 1;
+                }
+                Err(err) => {
+                    error!("failed to add {url} while mirroring {}: {err}", subscription.id);
+                    continue;
+                }
+            }
+        }
+        downloads.insert(id.clone());
+    }
+    if !downloads.is_empty() {
+        let req = DownloadRequest {
+            format: subscription.format.clone(),
+            quality: subscription.quality,
+            limit_rate: None,
+            number_tracks: false,
+            preset: None,
+        };
+        let response = start_download(&state, &target, req, Some(&downloads)).await?;
+        report.batch_id = Some(response.batch_id);
+    }
+
+    if req.prune && !report.removed.is_empty() {
+        for removal in &report.removed {
+            if let Err(err) = tokio::fs::remove_file(&removal.path).await {
+                error!("failed to remove {}: {err}", removal.path.display());
+            }
+        }
+        let mut archive = state.archive.lock().await;
+        for removal in &report.removed {
+            archive.remove(owner, &removal.id);
+        }
+        save_archive(&state.archive_path, &archive)
+            .map_err(|err| AppError::internal(err.to_string()))?;
+        report.pruned = true;
+    }
+
+    let mut subscriptions = state.subscriptions.lock().await;
+    if let Some(entry) = subscriptions.iter_mut().find(|entry| entry.id == subscription.id) {
+        if report.pruned {
+            entry.seen.retain(|id| current.contains(id));
         }
+        for id in &ids {
+            if !entry.seen.contains(id) {
+                entry.seen.push(id.clone());
+            }
+        }
+        entry.last_checked = Some(Utc::now().timestamp());
+        entry.last_error = None;
+    }
+    save_subscriptions(&state.subscriptions_path, &subscriptions)
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(Json(report))
+}
+
+pub async fn poll_subscriptions(state: AppState) {
+    loop {
+        tokio::time::sleep(SUBSCRIPTION_POLL_INTERVAL).await;
+        let now = Utc::now().timestamp();
+        let due: Vec<String> = state
+            .subscriptions
+            .lock()
+            .await
+            .iter()
+            .filter(|subscription| subscription.is_due(now))
+            .map(|subscription| subscription.id.clone())
+            .collect();
+        for id in due {
+            if let Err(err) = refresh_subscription(&state, &id).await {
+                error!("subscription check failed for {id}: {err}");
+            }
+        }
+    }
+}
+
+async fn refresh_subscription(state: &AppState, id: &str) -> Result<Subscription, AppError> {
+    let subscription = state
+        .subscriptions
+        .lock()
+        .await
+        .iter()
+        .find(|subscription| subscription.id == id)
+        .cloned()
+        .ok_or_else(|| AppError::not_found("subscription not found"))?;
+    let result = sync_subscription(state, &subscription).await;
+
+    let mut subscriptions = state.subscriptions.lock().await;
+    let entry = subscriptions
+        .iter_mut()
+        .find(|subscription| subscription.id == id)
+        .ok_or_else(|| AppError::not_found("subscription not found"))?;
+    entry.last_checked = Some(Utc::now().timestamp());
+    match result {
+        Ok((seen, added)) => {
+            entry.seen = seen;
+            entry.last_added = added;
+            entry.last_error = None;
+        }
+        Err(err) => {
+            entry.last_added = 0;
+            entry.last_error = Some(err.to_string());
+        }
+    }
+    let updated = entry.clone();
+    save_subscriptions(&state.subscriptions_path, &subscriptions)
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(updated)
+}
+
+async fn sync_subscription(
+    state: &AppState,
+    subscription: &Subscription,
+) -> Result<(Vec<String>, usize), AppError> {
+    let (feed, entries) = match subscription.kind {
+        SubscriptionKind::Playlist => {
+            let cookies = state.cookies.lock().await.clone();
+            let ids = list_playlist_ids(&subscription.url, &cookies).await?;
+            (None, ids.into_iter().map(|id| (id, None)).collect::<Vec<_>>())
+        }
+        SubscriptionKind::Feed => {
+            let feed = fetch_feed(state, &subscription.url).await?;
+            let entries = feed
+                .episodes
+                .iter()
+                .filter_map(|episode| Some((episode.id()?, Some(episode.clone()))))
+                .collect();
+            (Some(feed), entries)
+        }
+    };
+    let target =
+        resolve_queue(state, &Workspace::default(), Some(&subscription.queue_id)).await?;
+    // Copied out so the archive lock is never held while waiting on the queue.
+    let archived: HashSet<String> = {
+        let archive = state.archive.lock().await;
+        entries
+            .iter()
+            .filter(|(id, _)| archive.contains(target.workspace.as_deref(), id))
+            .map(|(id, _)| id.clone())
+            .collect()
+    };
+    let new_entries: Vec<(String, Option<FeedEpisode>)> = {
+        let queue = state.queue.lock().await;
+        entries
+            .into_iter()
+            .filter(|(id, _)| {
+                !subscription.seen.contains(id)
+                    && !archived.contains(id)
+                    && !queue.iter().any(|item| item.matches_key(&target.id, id))
+            })
+            .collect()
+    };
+    let mut seen = subscription.seen.clone();
+    if subscription.last_checked.is_none() && !subscription.backfill {
+        seen.extend(new_entries.into_iter().map(|(id, _)| id));
+        return Ok((seen, 0));
+    }
+
+    let settings = state.settings.lock().await.clone();
+    let mut added = HashSet::new();
+    for (id, episode) in new_entries {
+        let mut item = match (&feed, episode) {
+            (Some(feed), Some(episode)) => {
+                match feed_queue_item(&settings, feed, &episode, &subscription.url) {
+                    Some(item) => item,
+                    None => continue,
+                }
+            }
+            _ => {
+                let url = format!("https://www.youtube.com/watch?v={id}");
+                match probe_video_info(state, &url, false).await {
+                    Ok(info) => build_queue_item(&settings, info, &url, None),
+                    Err(err) => {
+                        // Left unseen so the next check retries it.
+                        error!("failed to add {url} from subscription {}: {err}", subscription.id);
+                        continue;
+                    }
+                }
+            }
+        };
+        item.queue_id = target.id.clone();
+        let mut queue = state.queue.lock().await;
+        if !queue.iter().any(|existing| existing.matches_key(&target.id, &item.id)) {
+            added.insert(item.id.clone());
+            queue.push(item);
+        }
+        seen.push(id);
+    }
+
+    if subscription.auto_download && !added.is_empty() {
+        let req = DownloadRequest {
+            format: subscription.format.clone(),
+            quality: subscription.quality,
+            limit_rate: None,
+            number_tracks: false,
+            preset: None,
+        };
+        if let Err(err) = start_download(state, &target, req, Some(&added)).await {
+            error!("auto-download failed for subscription {}: {err}", subscription.id);
+        }
+    }
+    Ok((seen, added.len()))
+}
+
+async fn fetch_feed(state: &AppState, url: &str) -> Result<Feed, AppError> {
+    let response = state
+        .client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| AppError::bad_request(format!("failed to fetch feed: {err}")))?;
+    let content = response
+        .text()
+        .await
+        .map_err(|err| AppError::bad_request(format!("failed to fetch feed: {err}")))?;
+    parse_feed(&content)
+        .map_err(|err| AppError::bad_request(format!("failed to parse feed: {err}")))
+}
+
+fn feed_queue_item(
+    settings: &AppSettings,
+    feed: &Feed,
+    episode: &FeedEpisode,
+    feed_url: &str,
+) -> Option<QueueItem> {
+    let url = episode.enclosure_url.clone()?;
+    let title = episode.title.clone().unwrap_or_else(|| "Unknown".to_string());
+    let author = episode
+        .author
+        .clone()
+        .or_else(|| feed.author.clone())
+        .or_else(|| feed.title.clone())
+        .unwrap_or_else(|| "Unknown".to_string());
+    let thumbnail_url = episode.image.clone().or_else(|| feed.image.clone());
+    let info = VideoInfo {
+        id: episode.id()?,
+        title: title.clone(),
+        artist: author.clone(),
+        uploader: Some(author.clone()),
+        channel_url: Some(feed_url.to_string()),
+        description: None,
+        thumbnail_candidates: thumbnail_url.iter().cloned().collect(),
+        thumbnail_url,
+        duration: episode.duration,
+        categories: Vec::new(),
+        tags: Vec::new(),
+        upload_date: episode.upload_date(),
+        release_date: None,
+        release_year: None,
+        chapters: Vec::new(),
+        audio_formats: Vec::new(),
+        live_status: None,
+        release_timestamp: None,
+    };
+    let mut item = build_queue_item(settings, info, &url, None);
+    // Episode titles are not "Artist - Title" pairs, so skip the usual split and cleanup.
+    item.title = sanitize_text(&title);
+    item.artist = sanitize_text(&author);
+    item.artists = vec![item.artist.clone()];
+    item.split_confidence = None;
+    if let Some(feed_title) = feed.title.as_deref().map(sanitize_text) {
+        item.custom_tags.insert("PODCAST".to_string(), feed_title.clone());
+        item.album = Some(feed_title);
+    }
+    item.custom_tags.insert("PODCASTURL".to_string(), feed_url.to_string());
+    Some(item)
+}
+
+const SCHEDULE_TICK: std::time::Duration = std::time::Duration::from_secs(20);
+
+pub async fn list_schedules(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Json<Vec<Schedule>> {
+    let owned = owned_queue_ids(&state, &workspace).await;
+    let schedules = state.schedules.lock().await;
+    Json(
+        schedules
+            .iter()
+            .filter(|schedule| owned.contains(&schedule.queue_id))
+            .cloned()
+            .collect(),
+    )
+}
+
+pub async fn create_schedule(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<CreateScheduleRequest>,
+) -> Result<Json<Schedule>, AppError> {
+    let cron = req.cron.split_whitespace().collect::<Vec<_>>().join(" ");
+    CronExpr::parse(&cron).map_err(AppError::bad_request)?;
+    let target = resolve_queue(&state, &workspace, req.queue.as_deref()).await?;
+    // Runs fall back to the queue's own format, or flac when the queue has none either.
+    let format = match (req.format.as_deref(), &target.format) {
+        (Some(format), _) => Some(normalize_format(format)?),
+        (None, Some(_)) if req.quality.is_some() => {
+            return Err(AppError::bad_request("a format is required when setting quality"));
+        }
+        (None, Some(_)) => None,
+        (None, None) => Some("flac"),
+    };
+    let quality = match format {
+        Some(format) => validate_quality(format, req.quality)?,
+        None => None,
+    };
+    let schedule = Schedule {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: req.name.filter(|name| !name.trim().is_empty()),
+        cron,
+        queue_id: target.id,
+        format: format.map(str::to_string),
+        quality,
+        enabled: true,
+        last_run: None,
+    };
+    let mut schedules = state.schedules.lock().await;
+    schedules.push(schedule.clone());
+    save_schedules(&state.schedules_path, &schedules)
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(Json(schedule))
+}
+
+pub async fn delete_schedule(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<StatusCode, AppError> {
+    let owned = owned_queue_ids(&state, &workspace).await;
+    let mut schedules = state.schedules.lock().await;
+    let before = schedules.len();
+    schedules.retain(|schedule| schedule.id != id || !owned.contains(&schedule.queue_id));
+    if schedules.len() == before {
+        return Err(AppError::not_found("schedule not found"));
+    }
+    save_schedules(&state.schedules_path, &schedules)
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_presets(State(state): State<AppState>) -> Json<Vec<Preset>> {
+    Json(state.presets.lock().await.clone())
+}
+
+pub async fn get_preset(
+    AxumPath(name): AxumPath<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Preset>, AppError> {
+    find_preset(&state, &name).await.map(Json)
+}
+
+// Presets are shared by every user, and name output directories, so only the admin edits them.
+pub async fn create_preset(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<Preset>,
+) -> Result<Json<Preset>, AppError> {
+    workspace.require_admin()?;
+    let preset = validate_preset(req)?;
+    let mut presets = state.presets.lock().await;
+    if presets.iter().any(|existing| existing.name.eq_ignore_ascii_case(&preset.name)) {
+        return Err(AppError::conflict("a preset with this name already exists"));
+    }
+    presets.push(preset.clone());
+    save_presets(&state.presets_path, &presets)
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(Json(preset))
+}
+
+pub async fn update_preset(
+    AxumPath(name): AxumPath<String>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+    Json(mut req): Json<Preset>,
+) -> Result<Json<Preset>, AppError> {
+    workspace.require_admin()?;
+    req.name = name;
+    let preset = validate_preset(req)?;
+    let mut presets = state.presets.lock().await;
+    let Some(existing) = presets
+        .iter_mut()
+        .find(|existing| existing.name.eq_ignore_ascii_case(&preset.name))
+    else {
+        return Err(AppError::not_found("preset not found"));
+    };
+    *existing = preset.clone();
+    save_presets(&state.presets_path, &presets)
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(Json(preset))
+}
+
+pub async fn delete_preset(
+    AxumPath(name): AxumPath<String>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<StatusCode, AppError> {
+    workspace.require_admin()?;
+    let mut presets = state.presets.lock().await;
+    let before = presets.len();
+    presets.retain(|preset| !preset.name.eq_ignore_ascii_case(&name));
+    if presets.len() == before {
+        return Err(AppError::not_found("preset not found"));
+    }
+    save_presets(&state.presets_path, &presets)
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn find_preset(state: &AppState, name: &str) -> Result<Preset, AppError> {
+    state
+        .presets
+        .lock()
+        .await
+        .iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name.trim()))
+        .cloned()
+        .ok_or_else(|| AppError::not_found(format!("preset {name} not found")))
+}
+
+fn validate_preset(mut preset: Preset) -> Result<Preset, AppError> {
+    preset.name = preset.name.trim().to_string();
+    validate_preset_name(&preset.name).map_err(AppError::bad_request)?;
+    let format = normalize_format(&preset.format)?;
+    preset.quality = validate_quality(format, preset.quality)?;
+    preset.format = format.to_string();
+    if let Some(rate) = preset.limit_rate.as_deref() {
+        parse_rate_limit(rate)?;
+    }
+    if let Some(output) = &preset.output {
+        validate_output(output).map_err(AppError::bad_request)?;
+    }
+    if let Some(resample) = &preset.resample {
+        validate_resample(resample).map_err(AppError::bad_request)?;
+    }
+    Ok(preset)
+}
+
+pub async fn get_schedule_run(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<BatchReport>, AppError> {
+    let owned = owned_queue_ids(&state, &workspace).await;
+    let batch_id = state
+        .schedules
+        .lock()
+        .await
+        .iter()
+        .find(|schedule| schedule.id == id && owned.contains(&schedule.queue_id))
+        .ok_or_else(|| AppError::not_found("schedule not found"))?
+        .last_run
+        .as_ref()
+        .and_then(|run| run.batch_id.clone())
+        .ok_or_else(|| AppError::not_found("schedule has not started a batch yet"))?;
+    let batches = state.batches.lock().await;
+    let batch = batches
+        .get(&batch_id)
+        .ok_or_else(|| AppError::not_found("download batch not found"))?;
+    Ok(Json(batch_report(batch)))
+}
+
+pub async fn run_schedules(state: AppState) {
+    loop {
+        tokio::time::sleep(SCHEDULE_TICK).await;
+        let now = chrono::Local::now();
+        let due: Vec<Schedule> = state
+            .schedules
+            .lock()
+            .await
+            .iter()
+            .filter(|schedule| schedule.is_due(&now))
+            .cloned()
+            .collect();
+        if due.is_empty() {
+            continue;
+        }
+        let mut runs = Vec::new();
+        for schedule in due {
+            let req = DownloadRequest {
+                format: schedule.format.clone().unwrap_or_default(),
+                quality: schedule.quality,
+                limit_rate: None,
+                number_tracks: false,
+                preset: None,
+            };
+            let target =
+                resolve_queue(&state, &Workspace::default(), Some(&schedule.queue_id)).await;
+            let result = match target {
+                Ok(target) => start_download(&state, &target, req, None).await,
+                Err(err) => Err(err),
+            };
+            let run = match result {
+                Ok(response) => ScheduleRun {
+                    started_at: now.timestamp(),
+                    batch_id: Some(response.batch_id),
+                    started: response.started,
+                    error: None,
+                },
+                Err(err) => {
+                    error!("scheduled download {} failed: {err}", schedule.id);
+                    ScheduleRun {
+                        started_at: now.timestamp(),
+                        batch_id: None,
+                        started: 0,
+                        error: Some(err.to_string()),
+                    }
+                }
+            };
+            runs.push((schedule.id, run));
+        }
+        let mut schedules = state.schedules.lock().await;
+        for (id, run) in runs {
+            if let Some(schedule) = schedules.iter_mut().find(|schedule| schedule.id == id) {
+                schedule.last_run = Some(run);
+            }
+        }
+        if let Err(err) = save_schedules(&state.schedules_path, &schedules) {
+            error!("failed to save schedules: {err}");
+        }
+    }
+}
+
+pub async fn list_queue(
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<Vec<QueueItem>>, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    let queue = state.queue.lock().await;
+    Ok(Json(
+        queue
+            .iter()
+            .filter(|item| item.queue_id == target.id)
+            .cloned()
+            .collect(),
+    ))
+}
+
+pub async fn add_queue(
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<AddRequest>,
+) -> Result<Json<QueueItem>, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    queue_url(&state, target, req).await.map(Json)
+}
+
+async fn queue_url(
+    state: &AppState,
+    target: NamedQueue,
+    req: AddRequest,
+) -> Result<QueueItem, AppError> {
+    let info = probe_video_info(state, &req.url, req.refresh).await?;
+    let settings = state.settings.lock().await.clone();
+    let workspace = Workspace(target.workspace.clone());
+    let peers = owned_queue_ids(state, &workspace).await;
+    let mut item = build_queue_item(&settings, info, &req.url, None);
+    item.queue_id = target.id;
+    item.force_download = req.force;
+
+    let archived = {
+        let archive = state.archive.lock().await;
+        if settings.archive.enabled && !req.force {
+            archive.mark_downloaded(target.workspace.as_deref(), &mut item);
+        }
+        let entries =
+            archive.entries.iter().filter(|entry| workspace.owns(entry.workspace.as_deref()));
+        find_archived_duplicate(entries, &item)
+            .filter(|_| !req.allow_duplicate)
+            .map(|entry| duplicate_message(&entry.title, &entry.artist))
+    };
+    let mut queue = state.queue.lock().await;
+    if queue.iter().any(|existing| existing.matches_key(&item.queue_id, &item.id)) {
+        return Err(AppError::conflict("queue already contains this video"));
+    }
+    if !req.allow_duplicate {
+        let owned = queue.iter().filter(|existing| peers.contains(&existing.queue_id));
+        let queued = find_duplicate(owned, &item)
+            .map(|existing| duplicate_message(&existing.title, &existing.artist));
+        if let Some(message) = queued.or(archived) {
+            return Err(AppError::conflict(message));
+        }
+    }
+    queue.push(item.clone());
+    Ok(item)
+}
+
+const CLIPBOARD_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+pub async fn watch_clipboard(state: AppState) {
+    let mut last: Option<String> = None;
+    loop {
+        tokio::time::sleep(CLIPBOARD_POLL_INTERVAL).await;
+        let settings = state.settings.lock().await.clipboard.clone();
+        if !settings.enabled {
+            last = None;
+            continue;
+        }
+        let text = match tokio::task::spawn_blocking(read_clipboard_text).await {
+            Ok(Ok(text)) => text,
+            _ => continue,
+        };
+        // Whatever was copied before the watcher started (or was enabled) is not a new copy.
+        let Some(previous) = last.replace(text.clone()) else {
+            continue;
+        };
+        if previous == text {
+            continue;
+        }
+        let Some(url) = extract_media_url(&settings, &text) else {
+            continue;
+        };
+        let queued = state.queue.lock().await.iter().any(|item| item.youtube_url == url);
+        if queued {
+            continue;
+        }
+        if settings.auto_add {
+            let req = AddRequest {
+                url: url.clone(),
+                refresh: false,
+                allow_duplicate: false,
+                force: false,
+            };
+            match queue_url(&state, default_queue(), req).await {
+                Ok(item) => info!("queued {} from the clipboard", item.title),
+                Err(err) => error!("failed to queue copied url {url}: {err}"),
+            }
+        } else {
+            let mut pending = state.clipboard.lock().await;
+            if !pending.iter().any(|candidate| candidate.url == url) {
+                pending.push(ClipboardCandidate::new(url));
+            }
+        }
+    }
+}
+
+pub async fn list_clipboard(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<Vec<ClipboardCandidate>>, AppError> {
+    workspace.require_admin()?;
+    Ok(Json(state.clipboard.lock().await.clone()))
+}
+
+pub async fn accept_clipboard(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<QueueItem>, AppError> {
+    workspace.require_admin()?;
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    let url = state
+        .clipboard
+        .lock()
+        .await
+        .iter()
+        .find(|candidate| candidate.id == id)
+        .map(|candidate| candidate.url.clone())
+        .ok_or_else(|| AppError::not_found("clipboard link not found"))?;
+    let req = AddRequest {
+        url,
+        refresh: false,
+        allow_duplicate: false,
+        force: false,
+    };
+    let item = queue_url(&state, target, req).await?;
+    state.clipboard.lock().await.retain(|candidate| candidate.id != id);
+    Ok(Json(item))
+}
+
+pub async fn dismiss_clipboard(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<StatusCode, AppError> {
+    workspace.require_admin()?;
+    let mut pending = state.clipboard.lock().await;
+    let before = pending.len();
+    pending.retain(|candidate| candidate.id != id);
+    if pending.len() == before {
+        return Err(AppError::not_found("clipboard link not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn update_queue(
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<UpdateRequest>,
+) -> Result<Json<QueueItem>, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    let settings = state.settings.lock().await.clone();
+    let mut queue = state.queue.lock().await;
+    let Some(item) = queue.iter_mut().find(|item| item.matches_key(&target.id, &req.id)) else {
+        return Err(AppError::not_found("queue item not found"));
+    };
+    apply_item_changes(item, req.changes, &settings)?;
+    Ok(Json(item.clone()))
+}
+
+// Every item is checked on a copy first, so one invalid row leaves the whole batch unchanged.
+pub async fn update_queue_batch(
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<BatchUpdateRequest>,
+) -> Result<Json<Vec<QueueItem>>, AppError> {
+    if req.ids.is_empty() {
+        return Err(AppError::bad_request("ids must not be empty"));
+    }
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    validate_title_rules(&req.title_rules).map_err(AppError::bad_request)?;
+    let settings = state.settings.lock().await.clone();
+    let mut queue = state.queue.lock().await;
+    let mut updated = Vec::with_capacity(req.ids.len());
+    for id in &req.ids {
+        let Some(item) = queue.iter().find(|item| item.matches_key(&target.id, id)) else {
+            return Err(AppError::not_found(format!("queue item {id} not found")));
+        };
+        let mut item = item.clone();
+        apply_item_changes(&mut item, req.changes.clone(), &settings)?;
+        let title = req
+            .title_rules
+            .iter()
+            .fold(item.title.clone(), |title, rule| apply_title_rule(rule, &title));
+        let title = sanitize_text(&title);
+        if !title.is_empty() {
+            item.title = title;
+        }
+        updated.push(item);
+    }
+    for item in &updated {
+        if let Some(entry) = queue.iter_mut().find(|entry| entry.matches_key(&target.id, &item.id)) {
+            *entry = item.clone();
+        }
+    }
+    Ok(Json(updated))
+}
+
+fn apply_item_changes(
+    item: &mut QueueItem,
+    req: ItemChanges,
+    settings: &AppSettings,
+) -> Result<(), AppError> {
+    let cleanup = &settings.title_cleanup;
+    if let Some(keep_raw_title) = req.keep_raw_title {
+        item.keep_raw_title = keep_raw_title;
+        let title = if keep_raw_title {
+            sanitize_text(&item.raw_title)
+        } else {
+            let uploader = item.uploader.as_deref().unwrap_or("Unknown");
+            let base = item
+                .split_confidence
+                .and_then(|_| configured_split(settings, &item.raw_title, uploader))
+                .map(|parsed| parsed.title)
+                .unwrap_or_else(|| item.raw_title.clone());
+            sanitize_text(&clean_title(cleanup, &base))
+        };
+        if !title.is_empty() {
+            item.title = title;
+        }
+    }
+
+    if let Some(title) = req.title {
+        let clean = sanitize_text(&title);
+        if !clean.is_empty() {
+            item.title = clean;
+        }
+    }
+    if let Some(artist) = req.artist {
+        let clean = sanitize_text(&artist);
+        if !clean.is_empty() {
+            item.artists = parse_artists(&artist);
+            item.artist = clean;
+        }
+    }
+    if let Some(artists) = req.artists {
+        let artists: Vec<String> = artists
+            .iter()
+            .map(|artist| artist.trim().to_string())
+            .filter(|artist| !artist.is_empty())
+            .collect();
+        if !artists.is_empty() {
+            item.artist = sanitize_text(&join_artists(&settings.artists, &artists));
+            item.artists = artists;
+        }
+    }
+    if let Some(album) = req.album {
+        item.album = non_empty(&album);
+    }
+    if let Some(year) = req.year {
+        item.year = Some(year).filter(|value| *value > 0);
+        item.year_from_upload = false;
+        if item.date.as_deref().and_then(date_year) != item.year {
+            item.date = None;
+        }
+    }
+    if let Some(date) = req.date {
+        if date.trim().is_empty() {
+            item.date = None;
+        } else {
+            let Some(date) = normalize_tag_date(&date) else {
+                return Err(AppError::bad_request("date must be YYYY, YYYY-MM or YYYY-MM-DD"));
+            };
+            item.year = date_year(&date);
+            item.year_from_upload = false;
+            item.date = Some(date);
+        }
+    }
+    if let Some(track_number) = req.track_number {
+        item.track_number = Some(track_number).filter(|value| *value > 0);
+    }
+    if let Some(disc_number) = req.disc_number {
+        item.disc_number = Some(disc_number).filter(|value| *value > 0);
+    }
+    if let Some(disc_total) = req.disc_total {
+        item.disc_total = Some(disc_total).filter(|value| *value > 0);
+    }
+    if let (Some(disc), Some(total)) = (item.disc_number, item.disc_total) {
+        if disc > total {
+            return Err(AppError::bad_request("disc_number cannot exceed disc_total"));
+        }
+    }
+    if let Some(compilation) = req.compilation {
+        item.compilation = compilation;
+    }
+    if let Some(genre) = req.genre {
+        item.genre = non_empty(&genre);
+    }
+    if req.start.is_some() || req.end.is_some() {
+        let start = req.start.map_or(item.start, |value| Some(value).filter(|v| *v > 0.0));
+        let end = req.end.map_or(item.end, |value| Some(value).filter(|v| *v > 0.0));
+        validate_clip(item, start, end)?;
+        item.start = start;
+        item.end = end;
+    }
+    if let Some(fade_in) = req.fade_in {
+        item.fade_in = validate_fade(fade_in)?;
+    }
+    if let Some(fade_out) = req.fade_out {
+        item.fade_out = validate_fade(fade_out)?;
+    }
+    if let Some(custom_tags) = req.custom_tags {
+        item.custom_tags = normalize_custom_tags(custom_tags);
+    }
+    if let Some(format_id) = req.format_id {
+        item.format_id = validate_format_id(&format_id)?;
+    }
+    if let Some(language) = req.audio_language {
+        let language = non_empty(&language);
+        if let Some(language) = language.as_ref() {
+            if !item.audio_languages.contains(language) {
+                return Err(AppError::bad_request("audio language not available for this video"));
+            }
+        }
+        item.audio_language = language;
+    }
+    Ok(())
+}
+
+pub async fn list_formats(
+    State(state): State<AppState>,
+    Query(query): Query<FormatsQuery>,
+) -> Result<Json<Vec<AudioFormat>>, AppError> {
+    let info = probe_video_info(&state, &query.url, query.refresh).await?;
+    Ok(Json(info.audio_formats))
+}
+
+pub async fn list_archive(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Json<Vec<ArchiveEntry>> {
+    let archive = state.archive.lock().await;
+    Json(
+        archive
+            .entries
+            .iter()
+            .filter(|entry| workspace.owns(entry.workspace.as_deref()))
+            .cloned()
+            .collect(),
+    )
+}
+
+pub async fn verify_archive(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<Vec<ArchiveVerification>>, AppError> {
+    let mut entries = state.archive.lock().await.entries.clone();
+    entries.retain(|entry| workspace.owns(entry.workspace.as_deref()));
+    let results = tokio::task::spawn_blocking(move || {
+        entries
+            .iter()
+            .map(|entry| ArchiveVerification {
+                id: entry.id.clone(),
+                path: entry.path.clone(),
+                status: verify_entry(entry),
+            })
+            .collect()
+    })
+    .await
+    .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(Json(results))
+}
+
+pub async fn podcast_feed_xml(
+    State(state): State<AppState>,
+    Query(query): Query<FeedQuery>,
+    Extension(workspace): Extension<Workspace>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let server = state.settings.lock().await.server.clone();
+    let base_url = match server.public_url.as_deref() {
+        Some(url) => url.trim_end_matches('/').to_string(),
+        None => {
+            let host = headers
+                .get(header::HOST)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or("127.0.0.1:47815");
+            let scheme = match server.remote && server.tls.is_some() {
+                true => "https",
+                false => "http",
+            };
+            format!("{scheme}://{host}")
+        }
+    };
+    // Podcast apps cannot send headers, so episodes carry the token the feed was fetched with.
+    let token = query
+        .token
+        .map(|token| {
+            let pair = form_urlencoded::Serializer::new(String::new())
+                .append_pair("token", &token)
+                .finish();
+            format!("?{pair}")
+        })
+        .unwrap_or_default();
+    let mut archived = state.archive.lock().await.entries.clone();
+    archived.retain(|entry| workspace.owns(entry.workspace.as_deref()));
+    archived.sort_by(|a, b| b.downloaded_at.cmp(&a.downloaded_at));
+    let mut entries = Vec::new();
+    for entry in archived {
+        let Ok(meta) = tokio::fs::metadata(&entry.path).await else {
+            continue;
+        };
+        let published = chrono::DateTime::parse_from_rfc3339(&entry.downloaded_at)
+            .map(|at| at.to_rfc2822())
+            .unwrap_or_default();
+        entries.push(PodcastEntry {
+            url: format!("{base_url}/api/library/{}{token}", entry.id),
+            id: entry.id,
+            title: entry.title,
+            artist: entry.artist,
+            length: meta.len(),
+            mime: MimeGuess::from_path(&entry.path).first_or_octet_stream().to_string(),
+            published,
+        });
+    }
+    let body = podcast_feed("Audio Downloader", &base_url, &entries);
+    Ok(([(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")], body).into_response())
+}
+
+pub async fn library_file(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Response, AppError> {
+    let path = {
+        let archive = state.archive.lock().await;
+        archive
+            .entries
+            .iter()
+            .find(|entry| entry.id == id && workspace.owns(entry.workspace.as_deref()))
+            .map(|entry| entry.path.clone())
+            .ok_or_else(|| AppError::not_found("download not found"))?
+    };
+    let file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|_| AppError::not_found("downloaded file is missing"))?;
+    let length = file
+        .metadata()
+        .await
+        .map_err(|err| AppError::internal(err.to_string()))?
+        .len();
+    let mime = MimeGuess::from_path(&path).first_or_octet_stream();
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(mime.as_ref()).map_err(|err| AppError::internal(err.to_string()))?,
+    );
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+    let body = axum::body::Body::from_stream(ReaderStream::new(file));
+    Ok((headers, body).into_response())
+}
+
+pub async fn delete_archive_entry(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<StatusCode, AppError> {
+    let mut archive = state.archive.lock().await;
+    let before = archive.entries.len();
+    archive
+        .entries
+        .retain(|entry| entry.id != id || !workspace.owns(entry.workspace.as_deref()));
+    if archive.entries.len() == before {
+        return Err(AppError::not_found("archive entry not found"));
+    }
+    save_archive(&state.archive_path, &archive).map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn clear_archive(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<StatusCode, AppError> {
+    let mut archive = state.archive.lock().await;
+    archive
+        .entries
+        .retain(|entry| !workspace.owns(entry.workspace.as_deref()));
+    save_archive(&state.archive_path, &archive).map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn prune_archive(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<PruneResponse>, AppError> {
+    let mut archive = state.archive.lock().await;
+    let removed = archive.prune_missing(|entry| workspace.owns(entry.workspace.as_deref()));
+    save_archive(&state.archive_path, &archive).map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(Json(PruneResponse { removed }))
+}
+
+pub async fn stop_recording(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<StatusCode, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    if !state.queue.lock().await.iter().any(|item| item.matches_key(&target.id, &id)) {
+        return Err(AppError::not_found("queue item not found"));
+    }
+    let recordings = state.recordings.lock().await;
+    let Some(stop) = recordings.get(&(target.id, id)) else {
+        return Err(AppError::not_found("no active recording for this item"));
+    };
+    stop.notify_one();
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub async fn pause_item(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<QueueItem>, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    stop_item(&state, &target, &id, DownloadState::Paused).await.map(Json)
+}
+
+pub async fn cancel_item(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<QueueItem>, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    stop_item(&state, &target, &id, DownloadState::Cancelled).await.map(Json)
+}
+
+pub async fn resume_item(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<QueueItem>, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    let mut queue = state.queue.lock().await;
+    let item = queue
+        .iter_mut()
+        .find(|item| item.matches_key(&target.id, &id))
+        .ok_or_else(|| AppError::not_found("queue item not found"))?;
+    if !matches!(item.state, DownloadState::Paused | DownloadState::Cancelled) {
+        return Err(AppError::conflict("only paused or cancelled items can be resumed"));
+    }
+    item.state = DownloadState::Waiting;
+    Ok(Json(item.clone()))
+}
+
+// Moves the item first so the cancelled download task leaves it alone, then stops its job.
+async fn stop_item(
+    state: &AppState,
+    queue: &NamedQueue,
+    id: &str,
+    target: DownloadState,
+) -> Result<QueueItem, AppError> {
+    let item = {
+        let mut items = state.queue.lock().await;
+        let item = items
+            .iter_mut()
+            .find(|item| item.matches_key(&queue.id, id))
+            .ok_or_else(|| AppError::not_found("queue item not found"))?;
+        if !item.state.can_transition_to(target) {
+            return Err(AppError::conflict("item cannot be stopped in its current state"));
+        }
+        item.state = target;
+        item.stage = None;
+        item.stage_progress = None;
+        item.progress = None;
+        item.clone()
+    };
+    for job in state.jobs.list().await {
+        let running = job.kind == JobKind::Download && job.state == JobState::Running;
+        let item_job =
+            job.queue_id.as_deref() == Some(queue.id.as_str()) && job.target.as_deref() == Some(id);
+        if running && item_job {
+            state.jobs.cancel(&job.id).await;
+        }
+    }
+    Ok(item)
+}
+
+pub async fn list_attempts(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<Vec<DownloadAttempt>>, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    let queue = state.queue.lock().await;
+    let Some(item) = queue.iter().find(|item| item.matches_key(&target.id, &id)) else {
+        return Err(AppError::not_found("queue item not found"));
+    };
+    Ok(Json(item.attempts.clone()))
+}
+
+pub async fn delete_queue(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<StatusCode, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    let mut queue = state.queue.lock().await;
+    let before = queue.len();
+    queue.retain(|item| !item.matches_key(&target.id, &id));
+    if queue.len() == before {
+        return Err(AppError::not_found("queue item not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn enrich_item(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+    req: Option<Json<EnrichRequest>>,
+) -> Result<Json<QueueItem>, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    let overwrite = req.and_then(|Json(req)| req.overwrite).unwrap_or(false);
+    let item = {
+        let queue = state.queue.lock().await;
+        queue.iter().find(|item| item.matches_key(&target.id, &id)).cloned()
+    };
+    let Some(item) = item else {
+        return Err(AppError::not_found("queue item not found"));
+    };
+
+    let (job_id, cancel) = state
+        .jobs
+        .start(
+            JobKind::Enrich,
+            Some(item.id.clone()),
+            Some(target.id.clone()),
+            target.workspace.clone(),
+        )
+        .await;
+    let result = tokio::select! {
+        result = lookup_musicbrainz(&state.client, &item) => result
+            .map_err(|err| AppError::internal(format!("musicbrainz lookup failed: {err}")))
+            .and_then(|enrichment| {
+                enrichment.ok_or_else(|| AppError::not_found("no matching musicbrainz recording"))
+            }),
+        _ = cancel.cancelled() => Err(AppError::conflict("enrichment cancelled")),
+    };
+    let outcome = result.as_ref().map(|_| ()).map_err(|err| err.to_string());
+    state.jobs.finish(&job_id, outcome).await;
+    let enrichment = result?;
+
+    let mut queue = state.queue.lock().await;
+    let Some(item) = queue.iter_mut().find(|item| item.matches_key(&target.id, &id)) else {
+        return Err(AppError::not_found("queue item not found"));
+    };
+    apply_enrichment(item, enrichment, overwrite);
+    Ok(Json(item.clone()))
+}
+
+pub async fn enrich_queue(
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<EnrichRequest>,
+) -> Result<Json<JobResponse>, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    let overwrite = req.overwrite.unwrap_or(false);
+    let items: Vec<QueueItem> = {
+        let queue = state.queue.lock().await;
+        queue
+            .iter()
+            .filter(|item| item.queue_id == target.id)
+            .filter(|item| {
+                req.ids
+                    .as_ref()
+                    .map(|ids| ids.contains(&item.id))
+                    .unwrap_or(true)
+            })
+            .cloned()
+            .collect()
+    };
+
+    let (job_id, cancel) = state
+        .jobs
+        .start(
+            JobKind::Enrich,
+            None,
+            Some(target.id.clone()),
+            target.workspace.clone(),
+        )
+        .await;
+    state.jobs.update(&job_id, |job| job.total = items.len()).await;
+    tokio::spawn({
+        let state = state.clone();
+        let job_id = job_id.clone();
+        async move {
+            tokio::select! {
+                _ = run_enrich(&state, &job_id, items, overwrite) => {}
+                _ = cancel.cancelled() => {}
+            }
+            state.jobs.finish(&job_id, Ok(())).await;
+        }
+    });
+    Ok(Json(JobResponse { job_id }))
+}
+
+async fn run_enrich(state: &AppState, job_id: &str, items: Vec<QueueItem>, overwrite: bool) {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            // MusicBrainz asks anonymous clients to stay under one request per second.
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+        let enrichment = match lookup_musicbrainz(&state.client, item).await {
+            Ok(Some(enrichment)) => enrichment,
+            Ok(None) => {
+                state.jobs.update(job_id, |job| {
+                    job.processed += 1;
+                    job.skipped += 1;
+                })
+                .await;
+                continue;
+            }
+            Err(err) => {
+                error!("musicbrainz lookup failed for {}: {err}", item.id);
+                state.jobs.update(job_id, |job| {
+                    job.processed += 1;
+                    job.failed += 1;
+                })
+                .await;
+                continue;
+            }
+        };
+        // The item may have been removed from its queue during the lookup.
+        let applied = {
+            let mut queue = state.queue.lock().await;
+            match queue.iter_mut().find(|existing| existing.matches_key(&item.queue_id, &item.id)) {
+                Some(existing) => {
+                    apply_enrichment(existing, enrichment, overwrite);
+                    true
+                }
+                None => false,
+            }
+        };
+        state.jobs.update(job_id, |job| {
+            job.processed += 1;
+            match applied {
+                true => job.succeeded += 1,
+                false => job.skipped += 1,
+            }
+        })
+        .await;
+    }
+}
+
+pub async fn clear_queue(
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<ClearRequest>,
+) -> Result<Json<Vec<QueueItem>>, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    let keep: fn(&QueueItem) -> bool = match req.mode.as_str() {
+        "complete" => |item| item.state != DownloadState::Complete,
+        "failed" => |item| item.state != DownloadState::Failed,
+        "all" | "non_working" => |item| {
+            matches!(item.state, DownloadState::Working | DownloadState::Recording)
+        },
+        _ => return Err(AppError::bad_request("unknown clear mode")),
+    };
+    let mut queue = state.queue.lock().await;
+    queue.retain(|item| item.queue_id != target.id || keep(item));
+    Ok(Json(
+        queue
+            .iter()
+            .filter(|item| item.queue_id == target.id)
+            .cloned()
+            .collect(),
+    ))
+}
+
+pub async fn download_all(
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<DownloadRequest>,
+) -> Result<Json<DownloadResponse>, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    start_download(&state, &target, req, None).await.map(Json)
+}
+
+pub async fn retry_failed(
+    State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<RetryFailedRequest>,
+) -> Result<Json<DownloadResponse>, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
+    let retried: Vec<QueueItem> = {
+        let mut queue = state.queue.lock().await;
+        queue
+            .iter_mut()
+            .filter(|item| item.queue_id == target.id && item.state == DownloadState::Failed)
+            .filter(|item| item.state.can_transition_to(DownloadState::Waiting))
+            .map(|item| {
+                let failed = item.clone();
+                item.state = DownloadState::Waiting;
+                item.error = None;
+                item.error_code = None;
+                item.stage = None;
+                item.progress = None;
+                if req.clear_attempts {
+                    item.attempts.clear();
+                }
+                failed
+            })
+            .collect()
+    };
+    let failed: HashSet<String> = retried.iter().map(|item| item.id.clone()).collect();
+    let download = DownloadRequest {
+        format: req.format,
+        quality: req.quality,
+        limit_rate: req.limit_rate,
+        number_tracks: false,
+        preset: None,
+    };
+    let started = start_download(&state, &target, download, Some(&failed)).await;
+    if started.is_err() {
+        // Nothing was started, so the items go back to failed with their reasons.
+        let mut queue = state.queue.lock().await;
+        for failed in retried {
+            let item = queue.iter_mut().find(|item| item.matches_key(&failed.queue_id, &failed.id));
+            if let Some(item) = item.filter(|item| item.state == DownloadState::Waiting) {
+                *item = failed;
+            }
+        }
+    }
+    started.map(Json)
+}
+
+pub async fn start_download(
+    state: &AppState,
+    target: &NamedQueue,
+    req: DownloadRequest,
+    only: Option<&HashSet<String>>,
+) -> Result<DownloadResponse, AppError> {
+    let state = state.clone();
+    let preset = match req.preset.as_deref() {
+        Some(name) => Some(Arc::new(find_preset(&state, name).await?)),
+        None => None,
+    };
+    let (format, quality) = download_format(target, preset.as_deref(), &req)?;
+    let limit_rate = preset
+        .as_ref()
+        .and_then(|preset| preset.limit_rate.clone())
+        .or(req.limit_rate);
+    let limit_rate = match limit_rate.as_deref() {
+        Some(rate) => parse_rate_limit(rate)?,
+        None => {
+            let settings = state.settings.lock().await;
+            match settings.network.limit_rate.as_deref() {
+                Some(rate) => parse_rate_limit(rate)?,
+                None => None,
+            }
+        }
+    };
+    let mut options = DownloadOptions {
+        format,
+        quality: validate_quality(format, quality)?,
+        limit_rate,
+        track_digits: None,
+        track_number: None,
+    };
+    let dir = target
+        .output_dir
+        .clone()
+        .or_else(|| preset.as_ref().and_then(|preset| preset.output_dir.clone()))
+        .or_else(download_dir)
+        .unwrap_or_else(|| PathBuf::from("."));
+    tokio::fs::create_dir_all(&dir).await.map_err(|err| {
+        AppError::bad_request(format!("failed to create output directory: {err}"))
+    })?;
+
+    // Scheduled items keep their state while their job waits for the release, so they are
+    // told apart from new ones by the job.
+    let running: HashSet<String> = state
+        .jobs
+        .list()
+        .await
+        .into_iter()
+        .filter(|job| job.kind == JobKind::Download && job.state == JobState::Running)
+        .filter(|job| job.queue_id.as_deref() == Some(target.id.as_str()))
+        .filter_map(|job| job.target)
+        .collect();
+    let max_concurrent = state.settings.lock().await.network.max_concurrent_downloads;
+    let (ids, scheduled): (Vec<String>, Vec<String>) = {
+        let queue = state.queue.lock().await;
+        let queue: Vec<&QueueItem> = queue
+            .iter()
+            .filter(|item| item.queue_id == target.id && !running.contains(&item.id))
+            .filter(|item| only.is_none_or(|only| only.contains(&item.id)))
+            .collect();
+        let mut sizes: Vec<u64> = queue
+            .iter()
+            .filter(|item| {
+                matches!(
+                    item.state,
+                    DownloadState::Waiting
+                        | DownloadState::Complete
+                        | DownloadState::Failed
+                        | DownloadState::Scheduled
+                )
+            })
+            .map(|item| estimate_output_size(item, options))
+            .collect();
+        ensure_free_space(&dir, sizes.iter().sum())?;
+        // Staging only holds the items downloading at the same time.
+        sizes.sort_unstable_by(|a, b| b.cmp(a));
+        let staged = sizes.iter().take(max_concurrent.max(1)).sum();
+        ensure_free_space(&state.temp_dir, staged)?;
+        let ids = queue
+            .iter()
+            .filter(|item| {
+                matches!(
+                    item.state,
+                    DownloadState::Waiting | DownloadState::Complete | DownloadState::Failed
+                )
+            })
+            .map(|item| item.id.clone())
+            .collect();
+        let scheduled = queue
+            .iter()
+            .filter(|item| item.state == DownloadState::Scheduled)
+            .map(|item| item.id.clone())
+            .collect();
+        (ids, scheduled)
+    };
+    let mut track_numbers = HashMap::new();
+    if req.number_tracks || preset.as_ref().is_some_and(|preset| preset.number_tracks) {
+        let (numbers, digits) = number_batch_tracks(&state, target, &ids, &scheduled).await;
+        track_numbers = numbers;
+        options.track_digits = Some(digits);
+    }
+    let item_options = |id: &String| DownloadOptions {
+        track_number: track_numbers.get(id).copied(),
+        ..options
+    };
+
+    let started = ids.len() + scheduled.len();
+    let batch_id = uuid::Uuid::new_v4().to_string();
+    let mut batch = DownloadBatch {
+        id: batch_id.clone(),
+        dir: dir.clone(),
+        started_at: Utc::now(),
+        finished_at: None,
+        items: Vec::new(),
+        playlist: None,
+        workspace: target.workspace.clone(),
+    };
+    let mut scheduled_jobs = Vec::new();
+    for id in scheduled {
+        let (job_id, cancel) = state
+            .jobs
+            .start(
+                JobKind::Download,
+                Some(id.clone()),
+                Some(target.id.clone()),
+                target.workspace.clone(),
+            )
+            .await;
+        let item = batch_item(&state, target, &id, &job_id).await;
+        batch.items.push(item);
+        scheduled_jobs.push((item_options(&id), id, job_id, cancel));
+    }
+    let mut jobs = Vec::new();
+    for id in ids {
+        let (job_id, cancel) = state
+            .jobs
+            .start(
+                JobKind::Download,
+                Some(id.clone()),
+                Some(target.id.clone()),
+                target.workspace.clone(),
+            )
+            .await;
+        let item = batch_item(&state, target, &id, &job_id).await;
+        batch.items.push(item);
+        jobs.push((item_options(&id), id, job_id, cancel));
+    }
+    if batch.items.is_empty() {
+        batch.finished_at = Some(batch.started_at);
+    }
+    state.batches.lock().await.insert(batch_id.clone(), batch);
+
+    for (options, id, job_id, cancel) in scheduled_jobs {
+        let state = state.clone();
+        let queue_id = target.id.clone();
+        let dir = dir.clone();
+        let batch_id = batch_id.clone();
+        let preset = preset.clone();
+        tokio::spawn(async move {
+            let released = tokio::select! {
+                released = wait_for_release(&state, &queue_id, &id) => released,
+                _ = cancel.cancelled() => false,
+            };
+            let permit = match released {
+                true => acquire_download_permit(&state, &cancel).await,
+                false => None,
+            };
+            let Some(_permit) = permit else {
+                record_batch_outcome(&state, &batch_id, &id, AttemptOutcome::Skipped, None, None)
+                    .await;
+                state.jobs.finish(&job_id, Ok(())).await;
+                return;
+            };
+            let job = DownloadJob {
+                queue_id: &queue_id,
+                id: &id,
+                job_id: &job_id,
+                batch_id: &batch_id,
+                preset: preset.as_deref(),
+            };
+            run_download_job(&state, job, cancel, &dir, options).await;
+        });
+    }
+
+    let state_clone = state.clone();
+    let queue_id = target.id.clone();
+    let batch = batch_id.clone();
+
+    tokio::spawn(async move {
+        for (options, id, job_id, cancel) in jobs {
+            let Some(permit) = acquire_download_permit(&state_clone, &cancel).await else {
+                record_batch_outcome(&state_clone, &batch, &id, AttemptOutcome::Skipped, None, None)
+                    .await;
+                state_clone.jobs.finish(&job_id, Ok(())).await;
+                continue;
+            };
+            let state = state_clone.clone();
+            let queue_id = queue_id.clone();
+            let dir = dir.clone();
+            let batch_id = batch.clone();
+            let preset = preset.clone();
+            tokio::spawn(async move {
+                let _permit = permit;
+                let job = DownloadJob {
+                    queue_id: &queue_id,
+                    id: &id,
+                    job_id: &job_id,
+                    batch_id: &batch_id,
+                    preset: preset.as_deref(),
+                };
+                run_download_job(&state, job, cancel, &dir, options).await;
+            });
+        }
+    });
+
+    Ok(DownloadResponse { started, batch_id })
+}
+
+// Numbers follow queue order rather than dispatch order, so scheduled items keep their place.
+// They only apply to this batch; the items' own track numbers are left alone. Also returns the
+// zero-padded width, at least two digits.
+async fn number_batch_tracks(
+    state: &AppState,
+    target: &NamedQueue,
+    ids: &[String],
+    scheduled: &[String],
+) -> (HashMap<String, u32>, usize) {
+    let batch: HashSet<&String> = ids.iter().chain(scheduled).collect();
+    let queue = state.queue.lock().await;
+    let numbers: HashMap<String, u32> = queue
+        .iter()
+        .filter(|item| item.queue_id == target.id && batch.contains(&item.id))
+        .zip(1..)
+        .map(|(item, number)| (item.id.clone(), number))
+        .collect();
+    let digits = numbers.len().to_string().len().max(2);
+    (numbers, digits)
+}
+
+// Cancelling a download job kills its yt-dlp child, drops the staging directory and puts the
+// item back to Waiting, while jobs still waiting for a permit end up skipped.
+pub async fn cancel_all_downloads(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Json<CancelAllResponse> {
+    let running: Vec<Job> = state
+        .jobs
+        .list()
+        .await
+        .into_iter()
+        .filter(|job| job.kind == JobKind::Download && job.state == JobState::Running)
+        .collect();
+    let owned = owned_queue_ids(&state, &workspace).await;
+    let mut cancelled = 0;
+    for job in running {
+        if job
+            .queue_id
+            .as_ref()
+            .is_some_and(|queue_id| owned.contains(queue_id))
+        {
+            state.jobs.cancel(&job.id).await;
+            cancelled += 1;
+        }
+    }
+    info!("cancelled {cancelled} downloads");
+    Json(CancelAllResponse { cancelled })
+}
+
+pub async fn get_batch(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<BatchReport>, AppError> {
+    let batches = state.batches.lock().await;
+    let batch = batches
+        .get(&id)
+        .filter(|batch| workspace.owns(batch.workspace.as_deref()))
+        .ok_or_else(|| AppError::not_found("download batch not found"))?;
+    Ok(Json(batch_report(batch)))
+}
+
+pub fn batch_report(batch: &DownloadBatch) -> BatchReport {
+    let count = |outcome: AttemptOutcome| {
+        batch
+            .items
+            .iter()
+            .filter(|item| item.outcome == Some(outcome))
+            .count()
+    };
+    let elapsed = batch.finished_at.unwrap_or_else(Utc::now) - batch.started_at;
+    BatchReport {
+        id: batch.id.clone(),
+        started_at: batch.started_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        finished_at: batch
+            .finished_at
+            .map(|at| at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        summary: BatchSummary {
+            total: batch.items.len(),
+            succeeded: count(AttemptOutcome::Complete),
+            failed: count(AttemptOutcome::Failed),
+            skipped: count(AttemptOutcome::Skipped),
+            pending: batch.items.iter().filter(|item| item.outcome.is_none()).count(),
+            total_bytes: batch.items.iter().filter_map(|item| item.bytes).sum(),
+            elapsed_secs: elapsed.num_seconds().max(0) as u64,
+        },
+        items: batch.items.clone(),
+        playlist: batch.playlist.clone(),
+    }
+}
+
+pub async fn get_batch_playlist(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Query(query): Query<PlaylistQuery>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Response, AppError> {
+    let relative = state.settings.lock().await.playlist.paths == PlaylistPaths::Relative;
+    let (dir, entries) = {
+        let batches = state.batches.lock().await;
+        let batch = batches
+            .get(&id)
+            .filter(|batch| workspace.owns(batch.workspace.as_deref()))
+            .ok_or_else(|| AppError::not_found("download batch not found"))?;
+        (batch.dir.clone(), playlist_entries(batch))
+    };
+    let file_name = match query.format {
+        PlaylistFormat::M3u8 => "AudioDownloader_playlist.m3u8",
+        PlaylistFormat::Xspf => "AudioDownloader_playlist.xspf",
+    };
+    let file_path = state.temp_dir.join(format!("{}-{file_name}", uuid::Uuid::new_v4()));
+    tokio::task::spawn_blocking({
+        let file_path = file_path.clone();
+        move || {
+            let base = relative.then_some(dir.as_path());
+            match query.format {
+                PlaylistFormat::M3u8 => export_m3u8(&file_path, &entries, base),
+                PlaylistFormat::Xspf => export_xspf(&file_path, &entries, base),
+            }
+        }
+    })
+    .await
+    .map_err(|err| AppError::internal(err.to_string()))?
+    .map_err(|err| AppError::internal(err.to_string()))?;
+
+    stream_file(&file_path, file_name).await
+}
+
+fn playlist_entries(batch: &DownloadBatch) -> Vec<PlaylistEntry> {
+    batch
+        .items
+        .iter()
+        .filter(|item| item.outcome == Some(AttemptOutcome::Complete))
+        .filter_map(|item| {
+            Some(PlaylistEntry {
+                title: item.title.clone(),
+                artist: item.artist.clone(),
+                duration: item.duration,
+                path: item.path.clone()?,
+            })
+        })
+        .collect()
+}
+
+async fn write_batch_playlist(state: &AppState, batch_id: &str) {
+    let settings = state.settings.lock().await.playlist.clone();
+    if !settings.write_m3u8 {
+        return;
+    }
+    let (dir, path, entries) = {
+        let batches = state.batches.lock().await;
+        let Some(batch) = batches.get(batch_id) else {
+            return;
+        };
+        let entries = playlist_entries(batch);
+        if entries.is_empty() {
+            return;
+        }
+        let file_name = format!(
+            "AudioDownloader_{}.m3u8",
+            batch.started_at.with_timezone(&chrono::Local).format("%Y%m%d-%H%M%S")
+        );
+        (batch.dir.clone(), batch.dir.join(file_name), entries)
+    };
+    let relative = settings.paths == PlaylistPaths::Relative;
+    let result = tokio::task::spawn_blocking({
+        let path = path.clone();
+        move || export_m3u8(&path, &entries, relative.then_some(dir.as_path()))
+    })
+    .await
+    .map_err(anyhow::Error::from)
+    .and_then(|result| result);
+    match result {
+        Ok(()) => {
+            if let Some(batch) = state.batches.lock().await.get_mut(batch_id) {
+                batch.playlist = Some(path);
+            }
+        }
+        Err(err) => error!("failed to write playlist for batch {batch_id}: {err}"),
+    }
+}
+
+async fn batch_item(state: &AppState, target: &NamedQueue, id: &str, job_id: &str) -> BatchItem {
+    let (title, artist, duration) = {
+        let queue = state.queue.lock().await;
+        queue
+            .iter()
+            .find(|item| item.matches_key(&target.id, id))
+            .map(|item| (item.title.clone(), item.artist.clone(), item.duration))
+            .unwrap_or_default()
+    };
+    BatchItem {
+        id: id.to_string(),
+        title,
+        job_id: job_id.to_string(),
+        outcome: None,
+        error: None,
+        bytes: None,
+        path: None,
+        artist,
+        duration,
     }
-
-    Ok(Json(item.clone()))
 }
 
-pub async fn delete_queue(
-    AxumPath(id): AxumPath<String>,
-    State(state): State<AppState>,
-) -> Result<StatusCode, AppError> {
-    let mut queue = state.queue.lock().await;
-    let before = queue.len();
-    queue.retain(|item| item.id != id);
-    if queue.len() == before {
-        return Err(AppError::not_found("queue item not found"));
+async fn record_batch_outcome(
+    state: &AppState,
+    batch_id: &str,
+    id: &str,
+    outcome: AttemptOutcome,
+    error: Option<String>,
+    path: Option<PathBuf>,
+) {
+    let bytes = match &path {
+        Some(path) => tokio::fs::metadata(path).await.ok().map(|meta| meta.len()),
+        None => None,
+    };
+    let finished = {
+        let mut batches = state.batches.lock().await;
+        let Some(batch) = batches.get_mut(batch_id) else {
+            return;
+        };
+        if let Some(item) = batch.items.iter_mut().find(|item| item.id == id) {
+            item.outcome = Some(outcome);
+            item.error = error;
+            item.bytes = bytes;
+            item.path = path;
+        }
+        let finished = batch.items.iter().all(|item| item.outcome.is_some());
+        if finished {
+            batch.finished_at = Some(Utc::now());
+        }
+        finished
+    };
+    if finished {
+        write_batch_playlist(state, batch_id).await;
+        let report = state.batches.lock().await.get(batch_id).map(batch_report);
+        if let Some(report) = report {
+            let settings = state.settings.lock().await.clone();
+            let desktop = settings.notifications.desktop && !state.headless;
+            if desktop && settings.notifications.on_batch_complete {
+                let summary = &report.summary;
+                notify_desktop(
+                    "Downloads finished".to_string(),
+                    format!(
+                        "{} downloaded, {} failed, {} skipped",
+                        summary.succeeded, summary.failed, summary.skipped
+                    ),
+                );
+            }
+            let webhooks = &settings.webhooks;
+            let data = WebhookData::Batch(&report);
+            dispatch_webhooks(&state.client, webhooks, WebhookEvent::BatchComplete, data);
+            if settings.media_server.kind.is_some() && report.summary.succeeded > 0 {
+                let client = state.client.clone();
+                let media_server = settings.media_server.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = refresh_media_library(&client, &media_server).await {
+                        error!("media library refresh failed: {err}");
+                    }
+                });
+            }
+        }
     }
-    Ok(StatusCode::NO_CONTENT)
 }
 
-pub async fn clear_queue(
-    State(state): State<AppState>,
-    Json(req): Json<ClearRequest>,
-) -> Result<Json<Vec<QueueItem>>, AppError> {
-    let mut queue = state.queue.lock().await;
-    match req.mode.as_str() {
-        "complete" => queue.retain(|item| item.state != DownloadState::Complete),
-        "failed" => queue.retain(|item| item.state != DownloadState::Failed),
-        "all" => queue.retain(|item| item.state == DownloadState::Working),
-        "non_working" => queue.retain(|item| item.state == DownloadState::Working),
-        _ => return Err(AppError::bad_request("unknown clear mode")),
+async fn acquire_download_permit(
+    state: &AppState,
+    cancel: &CancellationToken,
+) -> Option<OwnedSemaphorePermit> {
+    tokio::select! {
+        permit = state.download_semaphore.clone().acquire_owned() => permit.ok(),
+        _ = cancel.cancelled() => None,
     }
-    Ok(Json(queue.clone()))
 }
 
-pub async fn download_all(
-    State(state): State<AppState>,
-    Json(req): Json<DownloadRequest>,
-) -> Result<Json<DownloadResponse>, AppError> {
-    let format = normalize_format(&req.format)?;
-    let dir = download_dir().unwrap_or_else(|| PathBuf::from("."));
-    tokio::fs::create_dir_all(&dir).await.map_err(|err| {
-        AppError::bad_request(format!("failed to create output directory: {err}"))
-    })?;
+#[derive(Clone, Copy)]
+struct DownloadJob<'a> {
+    queue_id: &'a str,
+    id: &'a str,
+    job_id: &'a str,
+    batch_id: &'a str,
+    preset: Option<&'a Preset>,
+}
 
-    let ids: Vec<String> = {
-        let queue = state.queue.lock().await;
-        queue
-            .iter()
-            .filter(|item| {
-                matches!(
-                    item.state,
-                    DownloadState::Waiting | DownloadState::Complete | DownloadState::Failed
-                )
-            })
-            .map(|item| item.id.clone())
-            .collect()
+async fn run_download_job(
+    state: &AppState,
+    job: DownloadJob<'_>,
+    cancel: CancellationToken,
+    dir: &Path,
+    options: DownloadOptions,
+) {
+    let (queue_id, id) = (job.queue_id, job.id);
+    state.jobs.update(job.job_id, |job| job.total = 1).await;
+    let download = handle_download_item(state.clone(), queue_id, id, dir, options, job.preset);
+    let result = tokio::select! {
+        result = download => result,
+        _ = cancel.cancelled() => {
+            state.recordings.lock().await.remove(&(queue_id.to_string(), id.to_string()));
+            let _ = tokio::fs::remove_dir_all(staging_dir(state, queue_id, id)).await;
+            // Pausing or cancelling the item already moved it out of Working.
+            let active = state.queue.lock().await.iter().any(|item| {
+                item.matches_key(queue_id, id)
+                    && matches!(item.state, DownloadState::Working | DownloadState::Recording)
+            });
+            if active {
+                update_item_state(state, queue_id, id, DownloadState::Waiting, None).await;
+            }
+            Ok(None)
+        }
     };
+    let (outcome, error, path) = match result {
+        Ok(Some(path)) => (AttemptOutcome::Complete, None, Some(path)),
+        Ok(None) => {
+            let queue = state.queue.lock().await;
+            match queue.iter().find(|item| item.matches_key(queue_id, id)) {
+                Some(item) if item.state == DownloadState::Failed => {
+                    let error = item.error.clone();
+                    let error = error.unwrap_or_else(|| "download failed".to_string());
+                    (AttemptOutcome::Failed, Some(error), None)
+                }
+                _ => (AttemptOutcome::Skipped, None, None),
+            }
+        }
+        Err(err) => {
+            error!("download failed for {id}: {err}");
+            (AttemptOutcome::Failed, Some(err.to_string()), None)
+        }
+    };
+    state
+        .jobs
+        .update(job.job_id, |job| {
+            job.processed = 1;
+            match outcome {
+                AttemptOutcome::Complete => job.succeeded = 1,
+                AttemptOutcome::Skipped => job.skipped = 1,
+                AttemptOutcome::Failed => job.failed = 1,
+            }
+        })
+        .await;
+    let event = match outcome {
+        AttemptOutcome::Complete => Some(WebhookEvent::ItemComplete),
+        AttemptOutcome::Failed => Some(WebhookEvent::ItemFailed),
+        AttemptOutcome::Skipped => None,
+    };
+    if let Some(event) = event {
+        let item = {
+            let queue = state.queue.lock().await;
+            queue
+                .iter()
+                .find(|item| item.matches_key(queue_id, id))
+                .cloned()
+        };
+        if let Some(item) = item {
+            let settings = state.settings.lock().await.clone();
+            let notify = settings.notifications.desktop
+                && !state.headless
+                && settings.notifications.on_item_failed;
+            if notify && event == WebhookEvent::ItemFailed {
+                let error = item.error.as_deref().unwrap_or("download failed");
+                notify_desktop("Download failed".to_string(), format!("{}: {error}", item.title));
+            }
+            let data = WebhookData::Item(&item);
+            dispatch_webhooks(&state.client, &settings.webhooks, event, data);
+            if let (WebhookEvent::ItemComplete, Some(path)) = (event, &path) {
+                run_post_download_hook(&settings.hooks, &item, path);
+                spawn_uploads(state, &settings, queue_id, id, path);
+            }
+        }
+    }
+    record_batch_outcome(state, job.batch_id, id, outcome, error.clone(), path).await;
+    state.jobs.finish(job.job_id, error.map_or(Ok(()), Err)).await;
+}
 
-    let started = ids.len();
-    let state_clone = state.clone();
-
+fn spawn_uploads(state: &AppState, settings: &AppSettings, queue_id: &str, id: &str, path: &Path) {
+    let upload = settings.upload.clone();
+    if upload.s3.is_none() && upload.webdav.is_none() {
+        return;
+    }
+    let state = state.clone();
+    let queue_id = queue_id.to_string();
+    let id = id.to_string();
+    let path = path.to_path_buf();
     tokio::spawn(async move {
-        for id in ids {
-            let permit = state_clone.download_semaphore.clone().acquire_owned().await;
-            if permit.is_err() {
-                break;
+        let mut remote_url = None;
+        if let Some(s3) = &upload.s3 {
+            match upload_s3(&state.client, s3, &path).await {
+                Ok(url) => remote_url = Some(url),
+                Err(err) => error!("s3 upload failed for {id}: {err}"),
+            }
+        }
+        if let Some(webdav) = &upload.webdav {
+            match upload_webdav(&state.client, webdav, &path).await {
+                Ok(url) => remote_url = remote_url.or(url),
+                Err(err) => error!("webdav upload failed for {id}: {err}"),
+            }
+        }
+        if let Some(url) = remote_url {
+            let mut queue = state.queue.lock().await;
+            if let Some(item) = queue
+                .iter_mut()
+                .find(|item| item.matches_key(&queue_id, &id))
+            {
+                item.remote_url = Some(url);
             }
-            let state = state_clone.clone();
-            let dir = dir.clone();
-            let format = format.to_string();
-            tokio::spawn(async move {
-                let _permit = permit;
-                if let Err(err) = handle_download_item(state, &id, &dir, &format).await {
-                    error!("download failed for {id}: {err}");
-                }
-            });
         }
     });
+}
+
+#[derive(Clone, Copy)]
+struct DownloadOptions {
+    format: &'static str,
+    quality: Option<u32>,
+    limit_rate: Option<u64>,
+    track_digits: Option<usize>,
+    track_number: Option<u32>,
+}
 
-    Ok(Json(DownloadResponse { started }))
+fn numbered_title(item: &QueueItem, options: DownloadOptions) -> String {
+    match (options.track_digits, options.track_number) {
+        (Some(digits), Some(number)) => format!("{number:0digits$} - {}", item.title),
+        _ => item.title.clone(),
+    }
+}
+
+async fn queue_owner(state: &AppState, queue_id: &str) -> Option<String> {
+    let queues = state.queues.lock().await;
+    queues
+        .iter()
+        .find(|queue| queue.id == queue_id)
+        .and_then(|queue| queue.workspace.clone())
+}
+
+fn staging_dir(state: &AppState, queue_id: &str, id: &str) -> PathBuf {
+    state.temp_dir.join(format!("{queue_id}-{id}-staging"))
 }
 
 async fn handle_download_item(
     state: AppState,
+    queue_id: &str,
     id: &str,
     dir: &Path,
-    format: &str,
-) -> Result<()> {
+    options: DownloadOptions,
+    preset: Option<&Preset>,
+) -> Result<Option<PathBuf>> {
+    let mut settings = state.settings.lock().await.clone();
+    if let Some(preset) = preset {
+        preset.apply(&mut settings);
+    }
+    let owner = queue_owner(&state, queue_id).await;
+    let archived = state.archive.lock().await.contains(owner.as_deref(), id);
     let item = {
         let mut queue = state.queue.lock().await;
-        let Some(item) = queue.iter_mut().find(|item| item.id == id) else {
-            return Ok(());
+        let Some(item) = queue.iter_mut().find(|item| item.matches_key(queue_id, id)) else {
+            return Ok(None);
         };
-        item.state = DownloadState::Working;
+        if !item.state.can_transition_to(DownloadState::Working) {
+            return Ok(None);
+        }
+        if settings.archive.enabled && archived && !item.force_download {
+            item.state = DownloadState::Complete;
+            item.error = None;
+            item.progress = Some(100.0);
+            return Ok(None);
+        }
+        if item.is_live {
+            item.state = DownloadState::Recording;
+            item.stage = Some(DownloadStage::Recording);
+            item.progress = None;
+        } else {
+            item.state = DownloadState::Working;
+            item.stage = Some(DownloadStage::Downloading);
+            item.progress = Some(0.0);
+        }
         item.error = None;
-        item.progress = Some(0.0);
-        item.clone()
+        item.attempts.push(DownloadAttempt {
+            started_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            finished_at: None,
+            outcome: None,
+            error: None,
+            error_code: None,
+            exit_status: None,
+        });
+        let mut item = item.clone();
+        item.track_number = options.track_number.or(item.track_number);
+        item
     };
 
-    let thumbnail_data = if let Some(url) = item.thumbnail_url.as_deref() {
-        match fetch_thumbnail(&state.client, url).await {
-            Ok(bytes) => Some(bytes),
-            Err(err) => {
-                error!("thumbnail fetch failed: {err}");
-                None
-            }
+    let thumbnail_data = match fetch_cover_art(&state.client, &settings.cover_art, &item).await {
+        Some(bytes) => Some(process_cover_art(&settings.cover_art, bytes).await),
+        None => None,
+    };
+
+    let dir = &organized_dir(dir, &item, &settings.output);
+    let staging = staging_dir(&state, queue_id, id);
+    let output_claim = OutputClaim {
+        names: state.output_names.clone(),
+        id: item.scratch_name(),
+    };
+    let prepared = match tokio::fs::create_dir_all(dir).await {
+        Ok(()) => tokio::fs::create_dir_all(&staging).await,
+        Err(err) => Err(err),
+    };
+    let result = match prepared {
+        Ok(()) if item.is_live => {
+            record_to_output(&state, &item, &settings, options, dir, &staging).await
         }
-    } else {
-        None
+        Ok(()) => download_audio(&state, &item, &settings, options, dir, &staging).await,
+        Err(err) => Err(anyhow!("failed to create download directories: {err}")),
+    };
+    let result = match result {
+        Ok(Some(path)) => {
+            finish_download(&state, &item, &settings, options, thumbnail_data, &path, dir)
+                .await
+                .map(Some)
+        }
+        other => other,
     };
+    let _ = tokio::fs::remove_dir_all(&staging).await;
+    drop(output_claim);
 
-    let result = download_audio(&state, id, &item.youtube_url, &item.title, format, dir).await;
-    match result {
-        Ok(path) => {
-            if let Err(err) = tag_audio(&path, &item.artist, thumbnail_data) {
-                error!("tagging failed for {id}: {err}");
+    let mut published = None;
+    let outcome = match result {
+        Ok(None) => {
+            update_item_state(&state, queue_id, id, DownloadState::Complete, None).await;
+            AttemptOutcome::Skipped
+        }
+        Ok(Some(path)) => {
+            let checksum = tokio::task::spawn_blocking({
+                let path = path.clone();
+                move || file_sha256(&path)
+            })
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result);
+            let sha256 = match checksum {
+                Ok(sha256) => Some(sha256),
+                Err(err) => {
+                    error!("failed to checksum {}: {err}", path.display());
+                    None
+                }
+            };
+            let file_size = tokio::fs::metadata(&path).await.ok().map(|meta| meta.len());
+            {
+                let mut queue = state.queue.lock().await;
+                if let Some(item) = queue.iter_mut().find(|item| item.matches_key(queue_id, id)) {
+                    item.sha256 = sha256.clone();
+                    item.file_path = Some(path.clone());
+                    item.file_size = file_size;
+                }
+            }
+            if settings.archive.enabled {
+                let mut archive = state.archive.lock().await;
+                archive.record(&item, &path, sha256, owner);
+                if let Err(err) = save_archive(&state.archive_path, &archive) {
+                    error!("failed to save download archive: {err}");
+                }
             }
-            update_item_state(&state, id, DownloadState::Complete, None).await;
+            update_item_state(&state, queue_id, id, DownloadState::Complete, None).await;
+            published = Some(path);
+            AttemptOutcome::Complete
         }
         Err(err) => {
-            update_item_state(&state, id, DownloadState::Failed, Some(err.to_string())).await;
+            let error = Some(err.to_string());
+            update_item_state(&state, queue_id, id, DownloadState::Failed, error).await;
+            let download_error = err.downcast_ref::<DownloadError>();
+            let code = download_error.map_or(ErrorCode::Unknown, |err| err.code);
+            let mut queue = state.queue.lock().await;
+            if let Some(item) = queue.iter_mut().find(|item| item.matches_key(queue_id, id)) {
+                item.error_code = Some(code);
+                if let Some(attempt) = item.attempts.last_mut() {
+                    attempt.error = Some(err.to_string());
+                    attempt.error_code = Some(code);
+                    attempt.exit_status = download_error.and_then(|err| err.exit_status);
+                }
+            }
+            AttemptOutcome::Failed
         }
+    };
+
+    let mut queue = state.queue.lock().await;
+    if let Some(attempt) = queue
+        .iter_mut()
+        .find(|item| item.matches_key(queue_id, id))
+        .and_then(|item| item.attempts.last_mut())
+    {
+        attempt.finished_at = Some(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true));
+        attempt.outcome = Some(outcome);
     }
-    Ok(())
+    Ok(published)
+}
+
+async fn finish_download(
+    state: &AppState,
+    item: &QueueItem,
+    settings: &AppSettings,
+    options: DownloadOptions,
+    thumbnail_data: Option<Vec<u8>>,
+    path: &Path,
+    dir: &Path,
+) -> Result<PathBuf> {
+    let (queue_id, id) = (item.queue_id.as_str(), item.id.as_str());
+    // Downloads and recordings are written straight into the item's staging directory.
+    let staging = path
+        .parent()
+        .ok_or_else(|| anyhow!("invalid audio file path"))?;
+    update_item_stage(state, queue_id, id, DownloadStage::PostProcessing).await;
+    let progress = ProgressTarget {
+        state,
+        queue_id,
+        id,
+    };
+    let quality = options.quality;
+    // The file is still published, but the item says what it is missing.
+    let mut warnings = Vec::new();
+    let outcome = match post_process_audio(path, settings, item, quality, progress).await {
+        Ok(outcome) => outcome,
+        Err(err) => {
+            error!("post-processing failed for {id}: {err}");
+            warnings.push(format!("post-processing skipped: {err}"));
+            PostProcessOutcome::default()
+        }
+    };
+    let trimmed = outcome.trimmed_seconds;
+    if let Some(warning) = verify_duration(path, item, trimmed) {
+        error!("{id}: {warning}");
+        warnings.push(warning);
+    }
+    update_item_stage(state, queue_id, id, DownloadStage::Tagging).await;
+    if let Err(err) = tag_audio(path, item, settings, thumbnail_data) {
+        error!("tagging failed for {id}: {err}");
+        warnings.push(format!("file is untagged: {err}"));
+    }
+    {
+        let mut queue = state.queue.lock().await;
+        if let Some(entry) = queue
+            .iter_mut()
+            .find(|entry| entry.matches_key(queue_id, id))
+        {
+            entry.trimmed_silence = trimmed;
+            entry.warning = (!warnings.is_empty()).then(|| warnings.join("; "));
+        }
+    }
+    if settings.cue_sheet.enabled && !item.chapters.is_empty() {
+        if let Err(err) = write_cue_sheet(path, item, outcome.leading_trimmed_seconds).await {
+            error!("cue sheet failed for {id}: {err}");
+        }
+    }
+    if settings.lyrics.write_lrc {
+        let cookies = state.cookies.lock().await.clone();
+        let lyrics = &settings.lyrics;
+        if let Err(err) =
+            write_lrc_sidecar(&state.client, lyrics, item, path, &state.temp_dir, &cookies).await
+        {
+            error!("lrc sidecar failed for {id}: {err}");
+        }
+    }
+    if settings.metadata_sidecar.write_json || settings.metadata_sidecar.write_nfo {
+        if let Err(err) = write_metadata_sidecars(path, item, &settings.metadata_sidecar).await {
+            error!("metadata sidecar failed for {id}: {err}");
+        }
+    }
+    update_item_stage(state, queue_id, id, DownloadStage::Publishing).await;
+    let overwrite = settings.output.conflict_policy == ConflictPolicy::Overwrite;
+    publish_staged_files(staging, dir, overwrite).await?;
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("invalid audio file name"))?;
+    Ok(dir.join(file_name))
+}
+
+async fn record_to_output(
+    state: &AppState,
+    item: &QueueItem,
+    settings: &AppSettings,
+    options: DownloadOptions,
+    dir: &Path,
+    staging: &Path,
+) -> Result<Option<PathBuf>> {
+    let recording = record_livestream(
+        state,
+        item,
+        &settings.livestream,
+        format_selector(item),
+        &network_args(settings, options),
+    )
+    .await?;
+    let (queue_id, id) = (item.queue_id.as_str(), item.id.as_str());
+    update_item_state(state, queue_id, id, DownloadState::Working, None).await;
+    update_item_stage(state, queue_id, id, DownloadStage::Converting).await;
+
+    // Copying keeps the stream as recorded, so the container has to fit its codec.
+    let copied = match options.format {
+        "source" => Some(copy_extension(probe_audio_codec(&recording).await.as_deref())),
+        _ => None,
+    };
+    let extension = copied.or(output_extension(options.format)).unwrap_or("m4a");
+    let codec = match options.format {
+        "alac" => Some("alac"),
+        "source" => Some("copy"),
+        _ => None,
+    };
+    let title = numbered_title(item, options);
+    let title = output_file_stem(&title, dir, Some(extension), &settings.output);
+    let policy = settings.output.conflict_policy;
+    let names = &state.output_names;
+    let owner = item.scratch_name();
+    let resolved = resolve_output_stem(names, &owner, dir, &title, Some(extension), policy);
+    let Some(stem) = resolved else {
+        let _ = tokio::fs::remove_file(&recording).await;
+        return Ok(None);
+    };
+    let path = staging.join(format!("{stem}.{extension}"));
+    let progress = ProgressTarget {
+        state,
+        queue_id,
+        id,
+    };
+    transcode_audio(&recording, &path, codec, progress).await?;
+    Ok(Some(path))
+}
+
+// Releases every name an item claimed once its download ends, including when a cancelled job
+// drops the download future halfway.
+struct OutputClaim {
+    names: Arc<std::sync::Mutex<HashMap<PathBuf, String>>>,
+    id: String,
+}
+
+impl Drop for OutputClaim {
+    fn drop(&mut self) {
+        let mut names = self.names.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        names.retain(|_, owner| *owner != self.id);
+    }
+}
+
+// Claims the name until the item finishes, so concurrent items with the same title cannot
+// pick it too. Overwriting only ever replaces files already on disk, never another download.
+fn resolve_output_stem(
+    names: &std::sync::Mutex<HashMap<PathBuf, String>>,
+    id: &str,
+    dir: &Path,
+    title: &str,
+    extension: Option<&str>,
+    policy: ConflictPolicy,
+) -> Option<String> {
+    let mut claimed = names.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let stem = {
+        let taken = |stem: &str| claimed.get(&dir.join(stem)).is_some_and(|owner| owner != id);
+        let exists = |stem: &str| match extension {
+            Some(extension) => dir.join(format!("{stem}.{extension}")).exists(),
+            None => find_downloaded_file(dir, stem, &[]).is_some(),
+        };
+        let free = |stem: &str| !taken(stem) && !exists(stem);
+        if free(title) || (policy == ConflictPolicy::Overwrite && !taken(title)) {
+            Some(title.to_string())
+        } else {
+            match policy {
+                ConflictPolicy::Skip => None,
+                ConflictPolicy::Overwrite | ConflictPolicy::Rename => (2..)
+                    .map(|n| format!("{title} ({n})"))
+                    .find(|stem| free(stem)),
+            }
+        }
+    }?;
+    claimed.insert(dir.join(&stem), id.to_string());
+    Some(stem)
 }
 
 async fn update_item_state(
     state: &AppState,
+    queue_id: &str,
     id: &str,
     new_state: DownloadState,
     error: Option<String>,
 ) {
     let mut queue = state.queue.lock().await;
-    if let Some(item) = queue.iter_mut().find(|item| item.id == id) {
+    if let Some(item) = queue.iter_mut().find(|item| item.matches_key(queue_id, id)) {
+        if !item.state.can_transition_to(new_state) {
+            warn!("ignoring invalid state change for {id}");
+            return;
+        }
         item.state = new_state;
         item.error = error;
+        item.error_code = None;
         item.progress = match new_state {
             DownloadState::Complete => Some(100.0),
             DownloadState::Working => item.progress.or(Some(0.0)),
             _ => None,
         };
+        item.stage = match new_state {
+            DownloadState::Working => item.stage,
+            DownloadState::Recording => Some(DownloadStage::Recording),
+            _ => None,
+        };
+        if new_state != DownloadState::Working {
+            item.stage_progress = None;
+        }
+    }
+}
+
+async fn update_item_stage(state: &AppState, queue_id: &str, id: &str, stage: DownloadStage) {
+    let mut queue = state.queue.lock().await;
+    if let Some(item) = queue.iter_mut().find(|item| item.matches_key(queue_id, id)) {
+        if item.stage != Some(stage) {
+            item.stage_progress = None;
+        }
+        item.stage = Some(stage);
     }
 }
 
-async fn update_item_progress(state: &AppState, id: &str, progress: f32) {
+async fn update_item_progress(
+    state: &AppState,
+    queue_id: &str,
+    id: &str,
+    progress: TransferProgress,
+) {
     let mut queue = state.queue.lock().await;
-    if let Some(item) = queue.iter_mut().find(|item| item.id == id) {
-        item.progress = Some(progress.clamp(0.0, 100.0));
+    if let Some(item) = queue.iter_mut().find(|item| item.matches_key(queue_id, id)) {
+        if let Some(percent) = progress.percent {
+            item.progress = Some(percent.clamp(0.0, 100.0));
+        }
+        if progress.downloaded_bytes.is_some() {
+            item.downloaded_bytes = progress.downloaded_bytes;
+            item.total_bytes = progress.total_bytes;
+            item.speed = progress.speed;
+            item.eta = progress.eta;
+        }
     }
 }
 
 pub async fn import_list(
     State(state): State<AppState>,
+    Query(mut query): Query<ImportQuery>,
+    Extension(workspace): Extension<Workspace>,
+    multipart: Multipart,
+) -> Result<Json<JobResponse>, AppError> {
+    let target = resolve_queue(&state, &workspace, query.queue.as_deref()).await?;
+    query.queue = Some(target.id);
+    let (file_name, file_path) = save_upload(&state, multipart).await?;
+    let job = start_import_job(&state, file_name, target.workspace, file_path, query).await;
+    Ok(Json(job))
+}
+
+pub async fn import_sheet(
+    State(state): State<AppState>,
+    Query(mut query): Query<ImportQuery>,
+    Extension(workspace): Extension<Workspace>,
+    Json(req): Json<SheetImportRequest>,
+) -> Result<Json<JobResponse>, AppError> {
+    let target = resolve_queue(&state, &workspace, query.queue.as_deref()).await?;
+    query.queue = Some(target.id);
+    let owner = target.workspace;
+    let export_url = google_sheet_csv_url(req.url.trim())
+        .ok_or_else(|| AppError::bad_request("not a Google Sheets link"))?;
+    let response = state
+        .client
+        .get(&export_url)
+        .send()
+        .await
+        .map_err(|err| AppError::bad_request(format!("failed to fetch sheet: {err}")))?;
+    let is_csv = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/csv"));
+    if !response.status().is_success() || !is_csv {
+        return Err(AppError::bad_request(
+            "failed to fetch sheet; make sure it is shared with anyone who has the link",
+        ));
+    }
+    let data = response
+        .bytes()
+        .await
+        .map_err(|err| AppError::bad_request(format!("failed to fetch sheet: {err}")))?;
+    let file_path = state
+        .temp_dir
+        .join(format!("{}-sheet.csv", uuid::Uuid::new_v4()));
+    tokio::fs::write(&file_path, data)
+        .await
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(Json(start_import_job(&state, req.url, owner, file_path, query).await))
+}
+
+async fn start_import_job(
+    state: &AppState,
+    target: String,
+    workspace: Option<String>,
+    file_path: PathBuf,
+    query: ImportQuery,
+) -> JobResponse {
+    let (job_id, cancel) = state
+        .jobs
+        .start(JobKind::Import, Some(target), None, workspace)
+        .await;
+    tokio::spawn({
+        let state = state.clone();
+        let job_id = job_id.clone();
+        async move {
+            let result = tokio::select! {
+                result = run_import(&state, &job_id, file_path.clone(), query) => result,
+                _ = cancel.cancelled() => Ok(()),
+            };
+            // The list was uploaded or fetched into the temp dir just for this job.
+            let _ = tokio::fs::remove_file(&file_path).await;
+            state.jobs.finish(&job_id, result.map_err(|err| err.to_string())).await;
+        }
+    });
+    JobResponse { job_id }
+}
+
+pub async fn preview_import(
+    State(state): State<AppState>,
+    multipart: Multipart,
+) -> Result<Json<ImportPreview>, AppError> {
+    let (_, file_path) = save_upload(&state, multipart).await?;
+    let preview = tokio::task::spawn_blocking({
+        let file_path = file_path.clone();
+        move || preview_music_list(&file_path)
+    })
+    .await
+    .map_err(|err| AppError::internal(err.to_string()))?;
+    let _ = tokio::fs::remove_file(&file_path).await;
+    preview
+        .map(Json)
+        .map_err(|err| AppError::bad_request(err.to_string()))
+}
+
+async fn save_upload(
+    state: &AppState,
     mut multipart: Multipart,
-) -> Result<Json<Vec<QueueItem>>, AppError> {
-    let mut saved_path = None;
+) -> Result<(String, PathBuf), AppError> {
+    let mut saved = None;
     while let Some(field) = multipart
         .next_field()
         .await
         .map_err(|err| AppError::bad_request(err.to_string()))?
     {
+        // The first field is the list; any later ones are read past.
+        if saved.is_some() {
+            continue;
+        }
         let file_name = field
             .file_name()
             .map(|name| name.to_string())
@@ -282,59 +3347,276 @@ pub async fn import_list(
             .bytes()
             .await
             .map_err(|err| AppError::bad_request(err.to_string()))?;
-        let file_path = state
-            .temp_dir
-            .join(format!("{}-{}", uuid::Uuid::new_v4(), file_name));
+        let file_path = upload_path(&state.temp_dir, &file_name);
         tokio::fs::write(&file_path, data)
             .await
             .map_err(|err| AppError::bad_request(err.to_string()))?;
-        saved_path = Some(file_path);
-        break;
+        saved = Some((file_name, file_path));
     }
+    saved.ok_or_else(|| AppError::bad_request("no file uploaded"))
+}
 
-    let Some(file_path) = saved_path else {
-        return Err(AppError::bad_request("no file uploaded"));
-    };
+// The client's name is only shown back; the saved file keeps nothing but its extension, which
+// picks the parser.
+fn upload_path(temp_dir: &Path, file_name: &str) -> PathBuf {
+    let extension = Path::new(file_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .filter(|extension| extension.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or("bin");
+    temp_dir.join(format!("{}.{extension}", uuid::Uuid::new_v4()))
+}
 
-    let rows = tokio::task::spawn_blocking({
-        let file_path = file_path.clone();
-        move || import_music_list(&file_path)
-    })
-    .await
-    .map_err(|err| AppError::internal(err.to_string()))?
-    .map_err(|err| AppError::bad_request(err.to_string()))?;
+pub async fn list_jobs(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Json<Vec<Job>> {
+    let mut jobs = state.jobs.list().await;
+    jobs.retain(|job| workspace.owns(job.workspace.as_deref()));
+    Json(jobs)
+}
 
-    let mut new_items = Vec::new();
-    for row in rows {
-        match build_queue_item_from_row(&row).await {
-            Ok(item) => new_items.push(item),
-            Err(err) => error!("failed to import row: {err:?}"),
-        }
+pub async fn get_job(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<Job>, AppError> {
+    state
+        .jobs
+        .get(&id)
+        .await
+        .filter(|job| workspace.owns(job.workspace.as_deref()))
+        .map(Json)
+        .ok_or_else(|| AppError::not_found("job not found"))
+}
+
+pub async fn cancel_job(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<Job>, AppError> {
+    let owned = state
+        .jobs
+        .get(&id)
+        .await
+        .is_some_and(|job| workspace.owns(job.workspace.as_deref()));
+    if !owned {
+        return Err(AppError::not_found("job not found"));
+    }
+    state
+        .jobs
+        .cancel(&id)
+        .await
+        .map(Json)
+        .ok_or_else(|| AppError::not_found("job not found"))
+}
+
+pub async fn run_import(
+    state: &AppState,
+    job_id: &str,
+    file_path: PathBuf,
+    query: ImportQuery,
+) -> Result<()> {
+    let rows = tokio::task::spawn_blocking(move || import_music_list(&file_path)).await??;
+    state.jobs.update(job_id, |job| job.total = rows.len()).await;
+    let queue_id = query.queue.clone().unwrap_or_else(default_queue_id);
+    let owner = queue_owner(state, &queue_id).await;
+    let workspace = Workspace(owner.clone());
+    let peers = owned_queue_ids(state, &workspace).await;
+    if query.mode == ImportMode::Replace {
+        state.queue.lock().await.retain(|item| {
+            item.queue_id != queue_id
+                || matches!(item.state, DownloadState::Working | DownloadState::Recording)
+        });
+    }
+
+    let settings = state.settings.lock().await.clone();
+    let mut imported: Vec<Option<QueueItem>> = vec![None; rows.len()];
+    let mut tasks = tokio::task::JoinSet::new();
+    for (index, row) in rows.into_iter().enumerate() {
+        let state = state.clone();
+        let settings = settings.clone();
+        let refresh = query.refresh;
+        tasks.spawn(async move {
+            let result = build_queue_item_from_row(&state, &row, &settings, refresh).await;
+            (index, row, result)
+        });
+    }
+
+    let mut added = HashSet::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (index, row, result) = match joined {
+            Ok(joined) => joined,
+            Err(err) => {
+                error!("import task failed: {err}");
+                state.jobs.update(job_id, |job| {
+                    job.processed += 1;
+                    job.failed += 1;
+                })
+                .await;
+                continue;
+            }
+        };
+        let mut warning = None;
+        let changed = match result {
+            Ok(mut item) => {
+                item.queue_id = queue_id.clone();
+                item.force_download = query.force;
+                let archived = {
+                    let archive = state.archive.lock().await;
+                    if settings.archive.enabled && !query.force {
+                        archive.mark_downloaded(owner.as_deref(), &mut item);
+                    }
+                    let entries = archive
+                        .entries
+                        .iter()
+                        .filter(|entry| workspace.owns(entry.workspace.as_deref()));
+                    find_archived_duplicate(entries, &item)
+                        .filter(|_| !query.allow_duplicates)
+                        .map(|entry| duplicate_message(&entry.title, &entry.artist))
+                };
+                let mut queue = state.queue.lock().await;
+                let duplicate = if query.allow_duplicates {
+                    None
+                } else {
+                    let owned = queue.iter().filter(|existing| peers.contains(&existing.queue_id));
+                    find_duplicate(owned, &item)
+                        .map(|existing| duplicate_message(&existing.title, &existing.artist))
+                        .or(archived)
+                };
+                let changed = match queue
+                    .iter_mut()
+                    .find(|existing| existing.matches_key(&queue_id, &item.id))
+                {
+                    Some(existing) if query.mode == ImportMode::Merge => {
+                        merge_imported_row(existing, &row)
+                    }
+                    Some(_) => false,
+                    None if duplicate.is_some() => {
+                        warning = duplicate.map(|message| format!("{}: {message}", item.title));
+                        false
+                    }
+                    None => {
+                        added.insert(item.id.clone());
+                        queue.push(item.clone());
+                        true
+                    }
+                };
+                imported[index] = Some(item);
+                Some(changed)
+            }
+            Err(err) => {
+                error!("failed to import row: {err:?}");
+                None
+            }
+        };
+        state.jobs.update(job_id, |job| {
+            job.processed += 1;
+            match changed {
+                Some(true) => job.succeeded += 1,
+                Some(false) => job.skipped += 1,
+                None => job.failed += 1,
+            }
+            job.warnings.extend(warning);
+        })
+        .await;
+    }
+
+    let new_items: Vec<QueueItem> = imported.into_iter().flatten().collect();
+    restore_row_order(&mut state.queue.lock().await, &queue_id, &new_items, &added);
+    Ok(())
+}
+
+fn merge_imported_row(item: &mut QueueItem, row: &MusicRow) -> bool {
+    if matches!(item.state, DownloadState::Working | DownloadState::Recording) {
+        return false;
+    }
+    let mut changed = false;
+    let title = row.title.as_deref().map(sanitize_text).unwrap_or_default();
+    if !title.is_empty() && title != item.title {
+        item.title = title;
+        item.keep_raw_title = false;
+        changed = true;
     }
-
-    let mut queue = state.queue.lock().await;
-    for item in &new_items {
-        if !queue.iter().any(|existing| existing.id == item.id) {
-            queue.push(item.clone());
-        }
+    let artist = row.artist.as_deref().map(sanitize_text).unwrap_or_default();
+    if !artist.is_empty() && artist != item.artist {
+        item.artists = parse_artists(&artist);
+        item.artist = artist;
+        changed = true;
     }
+    changed
+}
 
-    Ok(Json(new_items))
+fn restore_row_order(
+    queue: &mut [QueueItem],
+    queue_id: &str,
+    ordered: &[QueueItem],
+    added: &HashSet<String>,
+) {
+    let rank: HashMap<&str, usize> = ordered
+        .iter()
+        .enumerate()
+        .map(|(index, item)| (item.id.as_str(), index))
+        .collect();
+    let positions: Vec<usize> = queue
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.queue_id == queue_id && added.contains(&item.id))
+        .map(|(index, _)| index)
+        .collect();
+    let mut items: Vec<QueueItem> = positions.iter().map(|index| queue[*index].clone()).collect();
+    items.sort_by_key(|item| rank.get(item.id.as_str()).copied().unwrap_or(usize::MAX));
+    for (position, item) in positions.into_iter().zip(items) {
+        queue[position] = item;
+    }
 }
 
 pub async fn export_list(
     State(state): State<AppState>,
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
     Json(req): Json<ExportRequest>,
 ) -> Result<Response, AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
     let format = normalize_export_format(&req.format)?;
+    let columns = match req.columns.is_empty() {
+        true => DEFAULT_EXPORT_COLUMNS.to_vec(),
+        false => req.columns,
+    };
     let rows = {
         let queue = state.queue.lock().await;
+        let archive = state.archive.lock().await;
         queue
             .iter()
+            .filter(|item| item.queue_id == target.id)
             .map(|item| MusicRow {
                 title: Some(item.title.clone()),
                 artist: Some(item.artist.clone()),
                 youtube_url: item.youtube_url.clone(),
+                custom_tags: item.custom_tags.clone(),
+                duration: item.duration,
+                state: serde_json::to_value(item.state)
+                    .ok()
+                    .and_then(|value| value.as_str().map(str::to_string)),
+                error: item.error.clone(),
+                album: item.album.clone(),
+                disc_number: item.disc_number,
+                disc_total: item.disc_total,
+                compilation: Some(item.compilation),
+                file_path: item.file_path.clone().or_else(|| {
+                    archive
+                        .entries
+                        .iter()
+                        .find(|entry| entry.matches_key(target.workspace.as_deref(), &item.id))
+                        .map(|entry| entry.path.clone())
+                }),
+                sha256: item.sha256.clone().or_else(|| {
+                    archive
+                        .entries
+                        .iter()
+                        .find(|entry| entry.matches_key(target.workspace.as_deref(), &item.id))
+                        .and_then(|entry| entry.sha256.clone())
+                }),
             })
             .collect::<Vec<_>>()
     };
@@ -345,7 +3627,7 @@ pub async fn export_list(
     tokio::task::spawn_blocking({
         let file_path = file_path.clone();
         let rows = rows.clone();
-        move || export_music_list(&file_path, &rows)
+        move || export_music_list(&file_path, &rows, &columns)
     })
     .await
     .map_err(|err| AppError::internal(err.to_string()))?
@@ -369,120 +3651,604 @@ pub async fn sample_file(State(state): State<AppState>) -> Result<Response, AppE
 pub async fn ensure_preview(
     AxumPath(id): AxumPath<String>,
     State(state): State<AppState>,
-) -> Result<Json<PreviewResponse>, AppError> {
+    Query(scope): Query<QueueQuery>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<(StatusCode, Json<PreviewResponse>), AppError> {
+    let target = resolve_queue(&state, &workspace, scope.queue.as_deref()).await?;
     let item = {
         let queue = state.queue.lock().await;
-        queue.iter().find(|item| item.id == id).cloned()
+        queue.iter().find(|item| item.matches_key(&target.id, &id)).cloned()
     };
     let Some(item) = item else {
         return Err(AppError::not_found("queue item not found"));
     };
 
-    let existing = find_preview_file(&state.preview_dir, &item.id);
-    let path = if let Some(path) = existing {
-        path
-    } else {
-        download_preview(&item.youtube_url, &item.id, &state.preview_dir).await?
-    };
+    // Holding the lock across the lookup dedupes concurrent requests for the same item,
+    // and keeps a half-written preview from being reported as ready.
+    let mut pending = state.pending_previews.lock().await;
+    if let Some(job_id) = pending.get(&item.id) {
+        return Ok(preview_pending(job_id.clone()));
+    }
+    if let Some(path) = find_preview_file(&state.preview_dir, &item.id) {
+        drop(pending);
+        tokio::task::spawn_blocking({
+            let path = path.clone();
+            move || touch_preview(&path)
+        })
+        .await
+        .map_err(|err| AppError::internal(err.to_string()))?;
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| AppError::internal("invalid preview file name"))?;
+        return Ok((
+            StatusCode::OK,
+            Json(PreviewResponse {
+                url: Some(format!("/preview/{file_name}")),
+                job_id: None,
+            }),
+        ));
+    }
 
-    let file_name = path
-        .file_name()
-        .and_then(|name| name.to_str())
-        .ok_or_else(|| AppError::internal("invalid preview file name"))?;
+    let clip_seconds = state.settings.lock().await.preview_cache.clip_seconds;
+    let clip = clip_seconds
+        .filter(|seconds| *seconds > 0)
+        .map(|seconds| (item.start.unwrap_or(0.0).max(0.0), seconds));
+    let (job_id, cancel) = state
+        .jobs
+        .start(
+            JobKind::Preview,
+            Some(item.id.clone()),
+            Some(target.id.clone()),
+            target.workspace.clone(),
+        )
+        .await;
+    pending.insert(item.id.clone(), job_id.clone());
+    drop(pending);
+    tokio::spawn({
+        let state = state.clone();
+        let job_id = job_id.clone();
+        async move {
+            let cookies = state.cookies.lock().await.clone();
+            let preview =
+                download_preview(&item.youtube_url, &item.id, &state.preview_dir, clip, &cookies);
+            let result = tokio::select! {
+                result = preview => result,
+                _ = cancel.cancelled() => Err(AppError::conflict("preview cancelled")),
+            };
+            state.pending_previews.lock().await.remove(&item.id);
+            let outcome = result.as_ref().map(|_| ()).map_err(|err| err.to_string());
+            state.jobs.finish(&job_id, outcome).await;
+            if result.is_ok() {
+                run_preview_eviction(state).await;
+            }
+        }
+    });
+    Ok(preview_pending(job_id))
+}
 
-    Ok(Json(PreviewResponse {
-        url: format!("/preview/{file_name}"),
+pub async fn preview_waveform(
+    AxumPath(id): AxumPath<String>,
+    Query(query): Query<WaveformQuery>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<WaveformResponse>, AppError> {
+    if !owns_item(&state, &workspace, &id).await {
+        return Err(AppError::not_found("preview not generated"));
+    }
+    if state.pending_previews.lock().await.contains_key(&id) {
+        return Err(AppError::conflict("preview is still downloading"));
+    }
+    let path = find_preview_file(&state.preview_dir, &id)
+        .ok_or_else(|| AppError::not_found("preview not generated"))?;
+    let points = query
+        .points
+        .unwrap_or(DEFAULT_WAVEFORM_POINTS)
+        .clamp(1, MAX_WAVEFORM_POINTS);
+    let waveform = waveform_peaks(&path, points)
+        .await
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(Json(WaveformResponse {
+        duration: waveform.duration,
+        peaks: waveform.peaks,
     }))
 }
 
-async fn build_queue_item_from_row(row: &MusicRow) -> Result<QueueItem, AppError> {
-    let info = fetch_video_info(&row.youtube_url).await?;
-    let title = row.title.clone().unwrap_or_else(|| info.title.clone());
-    let artist = row.artist.clone().unwrap_or_else(|| info.artist.clone());
+fn preview_pending(job_id: String) -> (StatusCode, Json<PreviewResponse>) {
+    (
+        StatusCode::ACCEPTED,
+        Json(PreviewResponse {
+            url: None,
+            job_id: Some(job_id),
+        }),
+    )
+}
+
+const DEFAULT_WAVEFORM_POINTS: usize = 800;
+const MAX_WAVEFORM_POINTS: usize = 8_000;
+const PREVIEW_EVICTION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+pub async fn clear_previews(
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Json<EvictionReport>, AppError> {
+    workspace.require_admin()?;
+    let dir = state.preview_dir.clone();
+    let report = tokio::task::spawn_blocking(move || clear_preview_cache(&dir))
+        .await
+        .map_err(|err| AppError::internal(err.to_string()))?;
+    Ok(Json(report))
+}
+
+pub async fn evict_previews(state: AppState) {
+    loop {
+        run_preview_eviction(state.clone()).await;
+        tokio::time::sleep(PREVIEW_EVICTION_INTERVAL).await;
+    }
+}
+
+async fn run_preview_eviction(state: AppState) {
+    let settings = state.settings.lock().await.preview_cache.clone();
+    if settings.max_bytes.is_none() && settings.max_age_secs.is_none() {
+        return;
+    }
+    let dir = state.preview_dir.clone();
+    match tokio::task::spawn_blocking(move || evict_preview_cache(&dir, &settings)).await {
+        Ok(report) if report.removed > 0 => {
+            info!("evicted {} previews ({} bytes)", report.removed, report.freed_bytes);
+        }
+        Ok(_) => {}
+        Err(err) => error!("preview eviction failed: {err}"),
+    }
+}
+
+const THUMBNAIL_SIZES: [u32; 6] = [96, 192, 320, 480, 720, 1280];
+
+pub async fn thumbnail(
+    AxumPath(id): AxumPath<String>,
+    Query(query): Query<ThumbnailQuery>,
+    State(state): State<AppState>,
+    Extension(workspace): Extension<Workspace>,
+) -> Result<Response, AppError> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(AppError::bad_request("invalid thumbnail id"));
+    }
+    if !owns_item(&state, &workspace, &id).await {
+        return Err(AppError::not_found("queue item not found"));
+    }
+    let cache_dir = state.preview_dir.join("thumbnails");
+    let original_path = cache_dir.join(format!("{id}.img"));
+
+    let original = match tokio::fs::read(&original_path).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            let item = {
+                let queue = state.queue.lock().await;
+                queue.iter().find(|item| item.id == id).cloned()
+            };
+            let Some(item) = item else {
+                return Err(AppError::not_found("queue item not found"));
+            };
+            let settings = CoverArtSettings {
+                providers: vec![CoverArtProvider::Thumbnail],
+                ..CoverArtSettings::default()
+            };
+            let bytes = fetch_cover_art(&state.client, &settings, &item)
+                .await
+                .ok_or_else(|| AppError::not_found("thumbnail unavailable"))?;
+            tokio::fs::write(&original_path, &bytes)
+                .await
+                .map_err(|err| AppError::internal(err.to_string()))?;
+            bytes
+        }
+    };
+
+    let bytes = match query.size {
+        None => original,
+        Some(requested) => {
+            let size = THUMBNAIL_SIZES
+                .iter()
+                .copied()
+                .find(|size| *size >= requested)
+                .unwrap_or(THUMBNAIL_SIZES[THUMBNAIL_SIZES.len() - 1]);
+            let variant_path = cache_dir.join(format!("{id}-{size}.jpg"));
+            match tokio::fs::read(&variant_path).await {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    let resized = resize_image(&original, size)
+                        .await
+                        .map_err(|err| AppError::internal(err.to_string()))?;
+                    tokio::fs::write(&variant_path, &resized)
+                        .await
+                        .map_err(|err| AppError::internal(err.to_string()))?;
+                    resized
+                }
+            }
+        }
+    };
+
+    let mime = match detect_mime(&bytes) {
+        MimeType::Png => "image/png",
+        _ => "image/jpeg",
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(mime));
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=86400"),
+    );
+    Ok((headers, bytes).into_response())
+}
+
+async fn build_queue_item_from_row(
+    state: &AppState,
+    row: &MusicRow,
+    settings: &AppSettings,
+    refresh: bool,
+) -> Result<QueueItem, AppError> {
+    let info = probe_video_info(state, &row.youtube_url, refresh).await?;
+    Ok(build_queue_item(settings, info, &row.youtube_url, Some(row)))
+}
+
+fn build_queue_item(
+    settings: &AppSettings,
+    info: VideoInfo,
+    youtube_url: &str,
+    row: Option<&MusicRow>,
+) -> QueueItem {
+    let row_title = row.and_then(|row| row.title.clone());
+    let row_artist = row.and_then(|row| row.artist.clone());
+    let (derived_title, derived_artist, split_confidence) = derive_title_artist(settings, &info);
+    let split_confidence = split_confidence.filter(|_| row_title.is_none() && row_artist.is_none());
+
+    let title = sanitize_text(
+        &row_title.unwrap_or_else(|| clean_title(&settings.title_cleanup, &derived_title)),
+    );
+    let mut artists = parse_artists(&row_artist.unwrap_or(derived_artist));
+    for featured in parse_featured_from_title(&title) {
+        if !artists.iter().any(|artist| artist.eq_ignore_ascii_case(&featured)) {
+            artists.push(featured);
+        }
+    }
+    let artist = sanitize_text(&join_artists(&settings.artists, &artists));
+    let genre = derive_genre(&settings.genre, &info.categories, &info.tags);
+    let date = info
+        .release_date
+        .as_deref()
+        .or(info.upload_date.as_deref())
+        .and_then(format_yt_dlp_date);
+    let year_from_upload = info.release_year.is_none() && info.release_date.is_none();
+    let year = info.release_year.or_else(|| date.as_deref().and_then(date_year));
+    let date = date.filter(|value| date_year(value) == year);
+    let is_upcoming = info.live_status.as_deref() == Some("is_upcoming");
+    let estimated_size = info
+        .audio_formats
+        .iter()
+        .find(|format| format.audio_only)
+        .and_then(|format| {
+            format.filesize.or_else(|| {
+                let bitrate = format.bitrate?;
+                Some((bitrate * 125.0) as u64 * info.duration?)
+            })
+        });
+    let mut audio_languages: Vec<String> = Vec::new();
+    for language in info.audio_formats.iter().filter_map(|format| format.language.clone()) {
+        if !audio_languages.contains(&language) {
+            audio_languages.push(language);
+        }
+    }
 
-    Ok(QueueItem {
+    QueueItem {
         id: info.id,
-        youtube_url: row.youtube_url.clone(),
-        title: sanitize_text(&title),
-        artist: sanitize_text(&artist),
+        queue_id: default_queue_id(),
+        youtube_url: youtube_url.to_string(),
+        title: if title.is_empty() { "Unknown".to_string() } else { title },
+        raw_title: info.title,
+        keep_raw_title: false,
+        artist: if artist.is_empty() { "Unknown".to_string() } else { artist },
+        artists,
+        split_confidence,
+        album: None,
+        year,
+        year_from_upload: year_from_upload && year.is_some(),
+        date,
+        track_number: None,
+        disc_number: row.and_then(|row| row.disc_number),
+        disc_total: row.and_then(|row| row.disc_total),
+        compilation: row.and_then(|row| row.compilation).unwrap_or(false),
+        genre,
+        custom_tags: row.map(|row| row.custom_tags.clone()).unwrap_or_default(),
+        musicbrainz_recording_id: None,
+        musicbrainz_release_id: None,
         thumbnail_url: info.thumbnail_url,
+        thumbnail_candidates: info.thumbnail_candidates,
         duration: info.duration,
-        state: DownloadState::Waiting,
+        estimated_size,
+        state: if is_upcoming {
+            DownloadState::Scheduled
+        } else {
+            DownloadState::Waiting
+        },
+        stage: None,
+        stage_progress: None,
         progress: None,
+        downloaded_bytes: None,
+        total_bytes: None,
+        speed: None,
+        eta: None,
         error: None,
-    })
+        error_code: None,
+        attempts: Vec::new(),
+        trimmed_silence: None,
+        fade_in: None,
+        fade_out: None,
+        start: None,
+        end: None,
+        chapters: info.chapters,
+        format_id: None,
+        audio_languages,
+        audio_language: None,
+        is_live: info.live_status.as_deref() == Some("is_live"),
+        recorded_bytes: None,
+        recorded_seconds: None,
+        scheduled_at: info.release_timestamp.filter(|_| is_upcoming),
+        remote_url: None,
+        uploader: info.uploader,
+        channel_url: info.channel_url,
+        upload_date: info.upload_date.clone(),
+        description: info.description,
+        sha256: None,
+        file_path: None,
+        file_size: None,
+        warning: None,
+        force_download: false,
+    }
+}
+
+fn derive_title_artist(settings: &AppSettings, info: &VideoInfo) -> (String, String, Option<f32>) {
+    match configured_split(settings, &info.title, &info.artist) {
+        Some(parsed) => (parsed.title, parsed.artist, Some(parsed.confidence)),
+        None => (info.title.clone(), info.artist.clone(), None),
+    }
+}
+
+// `uploader` falls back to "Unknown" like `VideoInfo::artist` so edits split the same way.
+fn configured_split(settings: &AppSettings, title: &str, uploader: &str) -> Option<ArtistTitle> {
+    let split = &settings.artist_title_split;
+    if !split.enabled {
+        return None;
+    }
+    split_artist_title(title, uploader).filter(|parsed| parsed.confidence >= split.min_confidence)
 }
 
 async fn download_audio(
     state: &AppState,
-    id: &str,
-    url: &str,
-    title: &str,
-    format: &str,
+    item: &QueueItem,
+    settings: &AppSettings,
+    options: DownloadOptions,
     dir: &Path,
-) -> Result<PathBuf> {
-    let clean_title = sanitize_text(title);
-    let output_template = dir.join(format!("{clean_title}.%(ext)s"));
+    staging: &Path,
+) -> Result<Option<PathBuf>> {
+    let format = options.format;
+    let policy = settings.output.conflict_policy;
+    let extension = output_extension(format);
+    let stem = output_file_stem(&numbered_title(item, options), dir, extension, &settings.output);
+    let names = &state.output_names;
+    let owner = item.scratch_name();
+    let Some(clean_title) = resolve_output_stem(names, &owner, dir, &stem, extension, policy)
+    else {
+        return Ok(None);
+    };
+    let output_template = staging.join(format!("{clean_title}.%(ext)s"));
     let output_template = output_template
         .to_str()
         .ok_or_else(|| anyhow!("invalid output path"))?
         .to_string();
 
     let mut cmd = Command::new("yt-dlp");
-    cmd.arg("-x")
-        .arg("--audio-format")
-        .arg(format)
-        .arg("--audio-quality")
-        .arg("0")
-        .arg("--no-playlist")
+    cmd.arg("-x");
+    cmd.args(network_args(settings, options));
+    if let Some(selector) = format_selector(item) {
+        cmd.arg("-f").arg(selector);
+    }
+    if let Some(audio_format) = yt_dlp_audio_format(format) {
+        cmd.arg("--audio-format").arg(audio_format);
+    }
+    match options.quality {
+        Some(level) if format == "flac" => {
+            cmd.arg("--audio-quality")
+                .arg("0")
+                .arg("--postprocessor-args")
+                .arg(format!("ExtractAudio:-compression_level {level}"));
+        }
+        Some(bitrate) => {
+            cmd.arg("--audio-quality").arg(format!("{bitrate}K"));
+        }
+        None => {
+            cmd.arg("--audio-quality").arg("0");
+        }
+    }
+    cmd.arg("--no-playlist")
         .arg("--progress")
         .arg("--newline")
+        .arg("--progress-template")
+        .arg(YT_DLP_PROGRESS_TEMPLATE)
         .arg("-o")
         .arg(output_template)
-        .arg(url)
+        .arg(&item.youtube_url)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
-    apply_yt_dlp_common_args(&mut cmd);
-    let mut child = cmd.spawn().context("yt-dlp execution failed")?;
+    if let Some(section) = download_section(item) {
+        cmd.arg("--download-sections")
+            .arg(section)
+            .arg("--force-keyframes-at-cuts");
+    }
+    apply_yt_dlp_common_args(&mut cmd, &*state.cookies.lock().await);
+    let mut child = cmd
+        .kill_on_drop(true)
+        .spawn()
+        .context("yt-dlp execution failed")?;
 
     let mut progress_tasks = Vec::new();
     if let Some(stdout) = child.stdout.take() {
         let state = state.clone();
-        let id = id.to_string();
+        let (queue_id, id) = (item.queue_id.clone(), item.id.clone());
         progress_tasks.push(tokio::spawn(async move {
-            consume_progress(stdout, state, id).await;
+            consume_progress(stdout, state, queue_id, id).await
         }));
     }
     if let Some(stderr) = child.stderr.take() {
         let state = state.clone();
-        let id = id.to_string();
+        let (queue_id, id) = (item.queue_id.clone(), item.id.clone());
         progress_tasks.push(tokio::spawn(async move {
-            consume_progress(stderr, state, id).await;
+            consume_progress(stderr, state, queue_id, id).await
         }));
     }
 
     let status = child.wait().await.context("yt-dlp execution failed")?;
+    let mut output = Vec::new();
     for task in progress_tasks {
-        let _ = task.await;
+        output.extend(task.await.unwrap_or_default());
     }
     if !status.success() {
-        return Err(anyhow!("yt-dlp download failed"));
+        return Err(DownloadError::from_yt_dlp_output(&output, status.code()).into());
     }
 
-    let path = dir.join(format!("{clean_title}.{format}"));
-    if path.exists() {
-        return Ok(path);
-    }
+    let extensions: Vec<&str> = format_extension(format).into_iter().collect();
+    let expected = extensions
+        .first()
+        .map(|extension| staging.join(format!("{clean_title}.{extension}")))
+        .filter(|path| path.exists());
+    let path = match expected {
+        Some(path) => path,
+        None => find_downloaded_file(staging, &clean_title, &extensions)
+            .ok_or_else(|| anyhow!("downloaded file not found"))?,
+    };
 
-    find_downloaded_file(dir, &clean_title).ok_or_else(|| anyhow!("downloaded file not found"))
+    if format == "aiff" {
+        let progress = ProgressTarget {
+            state,
+            queue_id: &item.queue_id,
+            id: &item.id,
+        };
+        return convert_audio(&path, "aiff", progress).await.map(Some);
+    }
+    Ok(Some(path))
 }
 
-async fn consume_progress<R: AsyncRead + Unpin>(reader: R, state: AppState, id: String) {
+async fn consume_progress<R: AsyncRead + Unpin>(
+    reader: R,
+    state: AppState,
+    queue_id: String,
+    id: String,
+) -> Vec<String> {
+    let mut output = Vec::new();
     let mut lines = BufReader::new(reader).lines();
     while let Ok(Some(line)) = lines.next_line().await {
         if let Some(progress) = parse_yt_dlp_progress(&line) {
-            update_item_progress(&state, &id, progress).await;
+            update_item_progress(&state, &queue_id, &id, progress).await;
+        } else if let Some(stage) = parse_yt_dlp_stage(&line) {
+            update_item_stage(&state, &queue_id, &id, stage).await;
+        } else if line.starts_with("ERROR:") || line.starts_with("WARNING:") {
+            output.push(line);
+        }
+    }
+    output
+}
+
+fn download_section(item: &QueueItem) -> Option<String> {
+    if item.start.is_none() && item.end.is_none() {
+        return None;
+    }
+    let start = item.start.unwrap_or(0.0);
+    let end = item
+        .end
+        .map(|end| end.to_string())
+        .unwrap_or_else(|| "inf".to_string());
+    Some(format!("*{start}-{end}"))
+}
+
+fn validate_clip(item: &QueueItem, start: Option<f64>, end: Option<f64>) -> Result<(), AppError> {
+    for value in [start, end].into_iter().flatten() {
+        if !value.is_finite() || value < 0.0 {
+            return Err(AppError::bad_request("clip times must be non-negative seconds"));
+        }
+        if let Some(duration) = item.duration {
+            if value > duration as f64 {
+                return Err(AppError::bad_request("clip time exceeds video duration"));
+            }
+        }
+    }
+    if let (Some(start), Some(end)) = (start, end) {
+        if end <= start {
+            return Err(AppError::bad_request("clip end must be after clip start"));
+        }
+    }
+    Ok(())
+}
+
+fn validate_fade(seconds: f32) -> Result<Option<f32>, AppError> {
+    if !seconds.is_finite() || !(0.0..=60.0).contains(&seconds) {
+        return Err(AppError::bad_request("fade must be between 0 and 60 seconds"));
+    }
+    Ok(Some(seconds).filter(|value| *value > 0.0))
+}
+
+fn format_selector(item: &QueueItem) -> Option<String> {
+    if let Some(format_id) = item.format_id.as_ref() {
+        return Some(format_id.clone());
+    }
+    let language = item.audio_language.as_deref()?;
+    Some(format!("bestaudio[language={language}]/best[language={language}]/bestaudio"))
+}
+
+fn validate_format_id(format_id: &str) -> Result<Option<String>, AppError> {
+    let format_id = format_id.trim();
+    if format_id.is_empty() {
+        return Ok(None);
+    }
+    if format_id.chars().any(char::is_whitespace) {
+        return Err(AppError::bad_request("format id must not contain whitespace"));
+    }
+    Ok(Some(format_id.to_string()))
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_string())
+    }
+}
+
+// An explicit preset or request picks format and quality together; the queue's defaults only
+// apply when neither names a format.
+fn download_format(
+    target: &NamedQueue,
+    preset: Option<&Preset>,
+    req: &DownloadRequest,
+) -> Result<(&'static str, Option<u32>), AppError> {
+    match preset {
+        Some(preset) => {
+            let format = normalize_format(&preset.format)?;
+            let format_differs =
+                !req.format.is_empty() && normalize_format(&req.format)? != format;
+            let quality_differs =
+                req.quality.is_some_and(|quality| preset.quality != Some(quality));
+            if format_differs || quality_differs {
+                return Err(AppError::conflict(format!(
+                    "format and quality conflict with preset {}",
+                    preset.name
+                )));
+            }
+            Ok((format, preset.quality))
         }
+        None if !req.format.is_empty() => Ok((normalize_format(&req.format)?, req.quality)),
+        None => match target.format.as_deref() {
+            Some(format) => Ok((normalize_format(format)?, target.quality)),
+            None => Err(AppError::bad_request("format is required")),
+        },
     }
 }
 
@@ -492,10 +4258,142 @@ fn normalize_format(format: &str) -> Result<&'static str, AppError> {
         "mp3" => Ok("mp3"),
         "m4a" => Ok("m4a"),
         "wav" => Ok("wav"),
+        "opus" => Ok("opus"),
+        "vorbis" | "ogg" => Ok("vorbis"),
+        "alac" => Ok("alac"),
+        "aiff" | "aif" => Ok("aiff"),
+        "source" | "original" | "best" => Ok("source"),
         _ => Err(AppError::bad_request("unsupported format")),
     }
 }
 
+fn validate_quality(format: &str, quality: Option<u32>) -> Result<Option<u32>, AppError> {
+    let Some(quality) = quality else {
+        return Ok(None);
+    };
+    let (range, unit) = match format {
+        "flac" => (0..=12, "compression level"),
+        "mp3" => (32..=320, "kbps"),
+        "opus" => (6..=510, "kbps"),
+        "m4a" | "vorbis" => (32..=512, "kbps"),
+        _ => {
+            return Err(AppError::bad_request(format!(
+                "quality is not supported for {format}"
+            )))
+        }
+    };
+    if !range.contains(&quality) {
+        return Err(AppError::bad_request(format!(
+            "{format} quality must be a {unit} between {} and {}",
+            range.start(),
+            range.end()
+        )));
+    }
+    Ok(Some(quality))
+}
+
+fn network_args(settings: &AppSettings, options: DownloadOptions) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(limit) = options.limit_rate {
+        args.extend(["--limit-rate".to_string(), limit.to_string()]);
+    }
+    if settings.network.concurrent_fragments > 1 {
+        args.extend([
+            "--concurrent-fragments".to_string(),
+            settings.network.concurrent_fragments.to_string(),
+        ]);
+    }
+    args
+}
+
+fn parse_rate_limit(rate: &str) -> Result<Option<u64>, AppError> {
+    let rate = rate.trim();
+    if rate.is_empty() {
+        return Ok(None);
+    }
+    let (number, multiplier) = match rate.to_ascii_uppercase().chars().last() {
+        Some('K') => (&rate[..rate.len() - 1], 1_024.0),
+        Some('M') => (&rate[..rate.len() - 1], 1_024.0 * 1_024.0),
+        Some('G') => (&rate[..rate.len() - 1], 1_024.0 * 1_024.0 * 1_024.0),
+        _ => (rate, 1.0),
+    };
+    match number.trim().parse::<f64>() {
+        Ok(value) if value > 0.0 => Ok(Some((value * multiplier) as u64)),
+        _ => Err(AppError::bad_request(
+            "rate limit must be a positive byte rate such as 500K or 2M",
+        )),
+    }
+}
+
+fn estimate_output_size(item: &QueueItem, options: DownloadOptions) -> u64 {
+    let end = item.end.or(item.duration.map(|value| value as f64)).unwrap_or(0.0);
+    let seconds = (end - item.start.unwrap_or(0.0)).max(0.0);
+    let bytes_per_second = match options.format {
+        "wav" | "aiff" => 176_400.0,
+        "flac" | "alac" => 176_400.0 * 0.6,
+        "source" => return item.estimated_size.unwrap_or(0),
+        "mp3" => f64::from(options.quality.unwrap_or(320)) * 125.0,
+        _ => f64::from(options.quality.unwrap_or(256)) * 125.0,
+    };
+    (seconds * bytes_per_second) as u64
+}
+
+fn ensure_free_space(dir: &Path, required: u64) -> Result<(), AppError> {
+    let available = match fs4::available_space(dir) {
+        Ok(available) => available,
+        Err(err) => {
+            error!("failed to query free space for {}: {err}", dir.display());
+            return Ok(());
+        }
+    };
+    // Leave headroom for staging copies and estimation error.
+    let required = required + required / 10;
+    if available < required {
+        return Err(AppError::insufficient_storage(format!(
+            "not enough free space in {}: about {} MB needed, {} MB available",
+            dir.display(),
+            required / 1_000_000,
+            available / 1_000_000
+        )));
+    }
+    Ok(())
+}
+
+fn yt_dlp_audio_format(format: &str) -> Option<&str> {
+    match format {
+        "source" => None,
+        // yt-dlp cannot extract straight to AIFF, so it is converted from WAV afterwards.
+        "aiff" => Some("wav"),
+        other => Some(other),
+    }
+}
+
+fn output_extension(format: &str) -> Option<&str> {
+    match format {
+        "aiff" => Some("aiff"),
+        other => format_extension(other),
+    }
+}
+
+fn copy_extension(codec: Option<&str>) -> &'static str {
+    match codec {
+        Some("aac" | "alac") => "m4a",
+        Some("mp3") => "mp3",
+        Some("opus") => "opus",
+        Some("vorbis") => "ogg",
+        Some("flac") => "flac",
+        _ => "mka",
+    }
+}
+
+fn format_extension(format: &str) -> Option<&str> {
+    match format {
+        "vorbis" => Some("ogg"),
+        "alac" => Some("m4a"),
+        other => yt_dlp_audio_format(other),
+    }
+}
+
 fn normalize_export_format(format: &str) -> Result<&'static str, AppError> {
     match format.to_lowercase().as_str() {
         "xlsx" => Ok("xlsx"),
@@ -525,3 +4423,466 @@ async fn stream_file(path: &Path, download_name: &str) -> Result<Response, AppEr
 
     Ok((headers, body).into_response())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::JobRegistry;
+    use crate::queues::ensure_workspace_queues;
+    use crate::settings::UserSettings;
+
+    const ALICE: &str = "alice";
+    const BOB: &str = "bob";
+
+    // Every file the state would write goes to a fresh directory under the system temp dir.
+    fn test_state() -> AppState {
+        let root = std::env::temp_dir().join(format!("audiodl-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut settings = AppSettings::default();
+        settings.server.users = [ALICE, BOB]
+            .into_iter()
+            .map(|name| UserSettings {
+                name: name.to_string(),
+                token: format!("{name}-token-0123456789"),
+                output_dir: Some(root.join(name)),
+            })
+            .collect();
+        let mut queues = vec![default_queue()];
+        ensure_workspace_queues(&mut queues, &settings.server.users);
+        AppState {
+            queue: Arc::default(),
+            preview_dir: root.join("preview"),
+            temp_dir: root.join("temp"),
+            download_semaphore: Arc::new(tokio::sync::Semaphore::new(1)),
+            metadata_semaphore: Arc::new(tokio::sync::Semaphore::new(1)),
+            client: reqwest::Client::new(),
+            project_root: root.clone(),
+            settings: Arc::new(tokio::sync::Mutex::new(settings)),
+            settings_path: root.join("settings.json"),
+            recordings: Arc::default(),
+            archive: Arc::default(),
+            archive_path: root.join("archive.json"),
+            cookies_path: root.join("cookies.txt"),
+            cookies: Arc::default(),
+            metadata_cache_dir: root.join("metadata_cache"),
+            jobs: JobRegistry::load(&root.join("jobs.json")).unwrap(),
+            batches: Arc::default(),
+            pending_previews: Arc::default(),
+            output_names: Arc::default(),
+            queues: Arc::new(tokio::sync::Mutex::new(queues)),
+            queues_path: root.join("queues.json"),
+            subscriptions: Arc::default(),
+            subscriptions_path: root.join("subscriptions.json"),
+            schedules: Arc::default(),
+            schedules_path: root.join("schedules.json"),
+            presets: Arc::default(),
+            presets_path: root.join("presets.json"),
+            headless: true,
+            clipboard: Arc::default(),
+        }
+    }
+
+    fn user(name: &str) -> Extension<Workspace> {
+        Extension(Workspace(Some(name.to_string())))
+    }
+
+    fn admin() -> Extension<Workspace> {
+        Extension(Workspace::default())
+    }
+
+    fn status<T>(result: Result<T, AppError>) -> StatusCode {
+        match result {
+            Ok(_) => StatusCode::OK,
+            Err(err) => err.into_response().status(),
+        }
+    }
+
+    fn queued(queue_id: &str, id: &str, title: &str, artist: &str) -> QueueItem {
+        let info = VideoInfo {
+            id: id.to_string(),
+            title: title.to_string(),
+            artist: artist.to_string(),
+            uploader: Some(artist.to_string()),
+            channel_url: None,
+            description: None,
+            thumbnail_url: None,
+            thumbnail_candidates: Vec::new(),
+            duration: Some(200),
+            categories: Vec::new(),
+            tags: Vec::new(),
+            upload_date: None,
+            release_date: None,
+            release_year: None,
+            chapters: Vec::new(),
+            audio_formats: Vec::new(),
+            live_status: None,
+            release_timestamp: None,
+        };
+        let url = format!("https://www.youtube.com/watch?v={id}");
+        let mut item = build_queue_item(&AppSettings::default(), info, &url, None);
+        item.queue_id = queue_id.to_string();
+        item
+    }
+
+    fn queue_request(output_dir: Option<&str>) -> Json<CreateQueueRequest> {
+        Json(CreateQueueRequest {
+            name: "Mixes".to_string(),
+            output_dir: output_dir.map(PathBuf::from),
+            format: None,
+            quality: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn only_the_admin_sets_a_queue_output_dir() {
+        let state = test_state();
+        let created = create_queue(State(state.clone()), user(ALICE), queue_request(Some("/etc")));
+        assert_eq!(status(created.await), StatusCode::FORBIDDEN);
+        let created = create_queue(State(state.clone()), user(ALICE), queue_request(None)).await;
+        let Json(created) = created.ok().unwrap();
+        assert_eq!(created.workspace.as_deref(), Some(ALICE));
+        assert_eq!(created.output_dir, Some(state.project_root.join(ALICE)));
+
+        // Queues made before the user's directory was known pick it up on the next start.
+        let mut queues = vec![NamedQueue {
+            output_dir: None,
+            ..created
+        }];
+        ensure_workspace_queues(&mut queues, &state.settings.lock().await.server.users);
+        assert!(queues.iter().all(|queue| queue.output_dir.is_some()));
+        let created = create_queue(State(state), admin(), queue_request(Some("/srv/music"))).await;
+        assert_eq!(created.unwrap().output_dir, Some(PathBuf::from("/srv/music")));
+    }
+
+    #[tokio::test]
+    async fn schedules_without_a_format_follow_their_queue() {
+        let state = test_state();
+        let mut req = queue_request(None);
+        req.format = Some("opus".to_string());
+        let Json(queue) = create_queue(State(state.clone()), admin(), req).await.ok().unwrap();
+        let schedule = |queue: Option<&str>, quality| {
+            Json(CreateScheduleRequest {
+                name: None,
+                cron: "0 2 * * *".to_string(),
+                queue: queue.map(str::to_string),
+                format: None,
+                quality,
+            })
+        };
+
+        let req = schedule(Some(&queue.id), None);
+        let created = create_schedule(State(state.clone()), admin(), req).await;
+        assert_eq!(created.ok().unwrap().format, None);
+        let req = schedule(Some(&queue.id), Some(5));
+        let created = create_schedule(State(state.clone()), admin(), req);
+        assert_eq!(status(created.await), StatusCode::BAD_REQUEST);
+        let created = create_schedule(State(state), admin(), schedule(None, None)).await;
+        assert_eq!(created.ok().unwrap().format.as_deref(), Some("flac"));
+    }
+
+    fn download_request(
+        format: &str,
+        quality: Option<u32>,
+        preset: Option<&str>,
+    ) -> DownloadRequest {
+        DownloadRequest {
+            format: format.to_string(),
+            quality,
+            limit_rate: None,
+            number_tracks: false,
+            preset: preset.map(str::to_string),
+        }
+    }
+
+    fn car_preset() -> Preset {
+        let preset = serde_json::json!({ "name": "Car", "format": "flac", "quality": 5 });
+        serde_json::from_value(preset).unwrap()
+    }
+
+    #[test]
+    fn presets_and_requests_outrank_queue_formats() {
+        let mut queue = default_queue();
+        queue.format = Some("opus".to_string());
+        queue.quality = Some(160);
+        let preset = car_preset();
+
+        let picked = |req| download_format(&queue, None, &req).ok();
+        assert_eq!(picked(download_request("mp3", None, None)), Some(("mp3", None)));
+        assert_eq!(picked(download_request("", Some(2), None)), Some(("opus", Some(160))));
+
+        let picked = |req| download_format(&queue, Some(&preset), &req).ok();
+        assert_eq!(picked(download_request("", None, None)), Some(("flac", Some(5))));
+        assert_eq!(picked(download_request("FLAC", Some(5), None)), Some(("flac", Some(5))));
+        assert_eq!(picked(download_request("mp3", None, None)), None);
+        assert_eq!(picked(download_request("", Some(320), None)), None);
+    }
+
+    #[tokio::test]
+    async fn downloads_use_their_queue_settings() {
+        let state = test_state();
+        let mut target = default_queue();
+        target.output_dir = Some(state.project_root.join("mixes"));
+        target.format = Some("opus".to_string());
+
+        let started = start_download(&state, &target, download_request("", None, None), None);
+        assert_eq!(status(started.await), StatusCode::OK);
+        assert!(state.project_root.join("mixes").is_dir());
+        target.format = None;
+        let started = start_download(&state, &target, download_request("", None, None), None);
+        assert_eq!(status(started.await), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn downloads_reject_a_request_that_contradicts_its_preset() {
+        let state = test_state();
+        state.presets.lock().await.push(car_preset());
+        let mut target = default_queue();
+        target.output_dir = Some(state.project_root.join("out"));
+
+        let req = download_request("mp3", None, Some("car"));
+        let started = start_download(&state, &target, req, None).await;
+        assert_eq!(status(started), StatusCode::CONFLICT);
+        let req = download_request("", None, Some("missing"));
+        let started = start_download(&state, &target, req, None).await;
+        assert_eq!(status(started), StatusCode::NOT_FOUND);
+        let req = download_request("", None, None);
+        let started = start_download(&state, &target, req, None).await;
+        assert_eq!(status(started), StatusCode::BAD_REQUEST);
+        let req = download_request("", None, Some("car"));
+        let started = start_download(&state, &target, req, None).await;
+        assert_eq!(status(started), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn item_updates_stay_in_their_queue() {
+        let state = test_state();
+        let (alice, bob) = (workspace_queue_id(ALICE), workspace_queue_id(BOB));
+        let items = [queued(&alice, "vid", "Song", "Band"), queued(&bob, "vid", "Song", "Band")];
+        state.queue.lock().await.extend(items);
+        update_item_state(&state, &bob, "vid", DownloadState::Working, None).await;
+        let queue = state.queue.lock().await;
+        assert!(queue[0].state == DownloadState::Waiting);
+        assert!(queue[1].state == DownloadState::Working);
+        assert_ne!(queue[0].scratch_name(), queue[1].scratch_name());
+    }
+
+    #[tokio::test]
+    async fn cached_media_stays_in_its_workspace() {
+        let state = test_state();
+        let item = queued(&workspace_queue_id(ALICE), "vid", "Song", "Band");
+        state.queue.lock().await.push(item);
+        assert!(owns_item(&state, &user(ALICE).0, "vid").await);
+        assert!(owns_item(&state, &admin().0, "vid").await);
+        assert!(!owns_item(&state, &user(BOB).0, "vid").await);
+
+        let query = Query(ThumbnailQuery { size: None });
+        let id = AxumPath("vid".to_string());
+        let response = thumbnail(id, query, State(state.clone()), user(BOB));
+        assert_eq!(status(response.await), StatusCode::NOT_FOUND);
+        let clipboard = list_clipboard(State(state.clone()), user(ALICE)).await;
+        assert_eq!(status(clipboard), StatusCode::FORBIDDEN);
+        assert_eq!(status(clear_previews(State(state), user(ALICE)).await), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn import_jobs_remove_their_list_when_they_end() {
+        let state = test_state();
+        std::fs::create_dir_all(&state.temp_dir).unwrap();
+        let path = state.temp_dir.join("list.xlsx");
+        std::fs::write(&path, b"not a spreadsheet").unwrap();
+        let query = serde_json::from_value(serde_json::json!({})).unwrap();
+        let job = start_import_job(&state, "list.xlsx".to_string(), None, path.clone(), query);
+        let job_id = job.await.job_id;
+        while state.jobs.get(&job_id).await.unwrap().state == JobState::Running {
+            tokio::task::yield_now().await;
+        }
+        assert!(state.jobs.get(&job_id).await.unwrap().state == JobState::Failed);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn feed_episodes_carry_the_token_and_public_url() {
+        let state = test_state();
+        let item = queued(&workspace_queue_id(ALICE), "vid", "Song", "Band");
+        let path = state.project_root.join("Song.mp3");
+        std::fs::write(&path, b"audio").unwrap();
+        let owner = Some(ALICE.to_string());
+        state.archive.lock().await.record(&item, &path, None, owner);
+        let public_url = Some("https://music.example.com/".to_string());
+        state.settings.lock().await.server.public_url = public_url;
+
+        let query = Query(FeedQuery {
+            token: Some("alice+token/0123456789".to_string()),
+        });
+        let feed = podcast_feed_xml(State(state), query, user(ALICE), HeaderMap::new()).await;
+        let body = axum::body::to_bytes(feed.ok().unwrap().into_body(), usize::MAX).await;
+        let body = String::from_utf8(body.unwrap().to_vec()).unwrap();
+        let enclosure = "https://music.example.com/api/library/vid?token=alice%2Btoken%2F";
+        assert!(body.contains(enclosure));
+    }
+
+    #[test]
+    fn uploads_stay_in_the_temp_dir() {
+        let temp_dir = Path::new("/tmp/audiodl");
+        for name in ["..\\..\\x.csv", "../../x.csv", "/etc/x.csv", "x.csv"] {
+            let path = upload_path(temp_dir, name);
+            assert_eq!(path.parent(), Some(temp_dir));
+            assert_eq!(path.extension().and_then(|ext| ext.to_str()), Some("csv"));
+        }
+        let path = upload_path(temp_dir, "list.x/../y");
+        assert_eq!(path.extension().and_then(|ext| ext.to_str()), Some("bin"));
+    }
+
+    #[tokio::test]
+    async fn concurrency_limits_follow_the_settings() {
+        let semaphore = Arc::new(tokio::sync::Semaphore::new(3));
+        let running = semaphore.clone().acquire_many_owned(2).await.unwrap();
+        resize_semaphore(&semaphore, 3, 1);
+        assert_eq!(semaphore.available_permits(), 0);
+        drop(running);
+        while semaphore.available_permits() != 1 {
+            tokio::task::yield_now().await;
+        }
+        resize_semaphore(&semaphore, 1, 4);
+        assert_eq!(semaphore.available_permits(), 4);
+
+        let state = test_state();
+        let mut settings = state.settings.lock().await.clone();
+        settings.network.max_concurrent_downloads = 0;
+        let applied = apply_settings(&state, settings).await;
+        assert_eq!(status(applied), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn rejected_retries_keep_their_failure() {
+        let state = test_state();
+        let mut item = queued(DEFAULT_QUEUE_ID, "vid", "Song", "Band");
+        item.state = DownloadState::Failed;
+        item.error = Some("HTTP Error 403".to_string());
+        state.queue.lock().await.push(item);
+
+        let req = Json(RetryFailedRequest {
+            format: "wma".to_string(),
+            quality: None,
+            limit_rate: None,
+            clear_attempts: true,
+        });
+        let scope = Query(QueueQuery { queue: None });
+        let retried = retry_failed(State(state.clone()), scope, admin(), req).await;
+        assert_eq!(status(retried), StatusCode::BAD_REQUEST);
+        let queue = state.queue.lock().await;
+        assert!(queue[0].state == DownloadState::Failed);
+        assert_eq!(queue[0].error.as_deref(), Some("HTTP Error 403"));
+    }
+
+    #[tokio::test]
+    async fn untagged_files_are_published_with_a_warning() {
+        let state = test_state();
+        let item = queued(DEFAULT_QUEUE_ID, "vid", "Song", "Band");
+        state.queue.lock().await.push(item.clone());
+        let staging = staging_dir(&state, DEFAULT_QUEUE_ID, "vid");
+        let dir = state.project_root.join("out");
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = staging.join("Song.mp3");
+        std::fs::write(&path, b"not audio").unwrap();
+
+        let settings = AppSettings::default();
+        let options = DownloadOptions {
+            format: "mp3",
+            quality: None,
+            limit_rate: None,
+            track_digits: None,
+            track_number: None,
+        };
+        let finished = finish_download(&state, &item, &settings, options, None, &path, &dir).await;
+        assert_eq!(finished.unwrap(), dir.join("Song.mp3"));
+        let warning = state.queue.lock().await[0].warning.clone().unwrap_or_default();
+        assert!(warning.contains("untagged"), "{warning}");
+    }
+
+    #[tokio::test]
+    async fn archive_entries_belong_to_their_workspace() {
+        let state = test_state();
+        let item = queued(&workspace_queue_id(ALICE), "vid", "Song", "Band");
+        let path = state.project_root.join("Song.mp3");
+        std::fs::write(&path, b"audio").unwrap();
+        state
+            .archive
+            .lock()
+            .await
+            .record(&item, &path, None, Some(ALICE.to_string()));
+
+        let mut copy = queued(&workspace_queue_id(BOB), "vid", "Song", "Band");
+        assert!(!state
+            .archive
+            .lock()
+            .await
+            .mark_downloaded(Some(BOB), &mut copy));
+        assert!(copy.file_path.is_none());
+        let path = |id: &str| AxumPath(id.to_string());
+        let deleted = delete_archive_entry(path("vid"), State(state.clone()), user(BOB)).await;
+        assert_eq!(status(deleted), StatusCode::NOT_FOUND);
+        let deleted = delete_archive_entry(path("vid"), State(state.clone()), user(ALICE)).await;
+        assert_eq!(status(deleted), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_download_releases_its_output_name() {
+        let names = Arc::new(std::sync::Mutex::new(HashMap::new()));
+        let dir = Path::new("/nonexistent-output-dir");
+        let policy = ConflictPolicy::Rename;
+        let claim = |id| resolve_output_stem(&names, id, dir, "Song", Some("mp3"), policy);
+
+        let download = {
+            let names = names.clone();
+            async move {
+                let _claim = OutputClaim {
+                    names: names.clone(),
+                    id: "a".to_string(),
+                };
+                resolve_output_stem(&names, "a", dir, "Song", Some("mp3"), policy);
+                std::future::pending::<()>().await;
+            }
+        };
+        let mut download = Box::pin(download);
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        tokio::select! {
+            biased;
+            _ = &mut download => unreachable!(),
+            _ = cancel.cancelled() => {}
+        }
+        assert_eq!(claim("b").as_deref(), Some("Song (2)"));
+
+        // Cancelling drops the download future, as `run_download_job` does.
+        drop(download);
+        assert_eq!(claim("c").as_deref(), Some("Song"));
+    }
+
+    #[test]
+    fn imported_rows_only_reorder_their_queue() {
+        let alice = workspace_queue_id(ALICE);
+        let bob = workspace_queue_id(BOB);
+        let mut queue = vec![
+            queued(&bob, "b", "B", "Band"),
+            queued(&alice, "b", "B", "Band"),
+            queued(&bob, "a", "A", "Band"),
+            queued(&alice, "a", "A", "Band"),
+        ];
+        let ordered = vec![queued(&alice, "a", "A", "Band"), queued(&alice, "b", "B", "Band")];
+        let added = HashSet::from(["a".to_string(), "b".to_string()]);
+        restore_row_order(&mut queue, &alice, &ordered, &added);
+        let keys: Vec<(&str, &str)> = queue
+            .iter()
+            .map(|item| (item.queue_id.as_str(), item.id.as_str()))
+            .collect();
+        let expected = [
+            (bob.as_str(), "b"),
+            (alice.as_str(), "a"),
+            (bob.as_str(), "a"),
+            (alice.as_str(), "b"),
+        ];
+        assert_eq!(keys, expected);
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use crate::settings::HookSettings;
+use crate::types::QueueItem;
+
+// The hook runs through the platform shell with the output file as its first argument
+// and the item metadata in AD_* environment variables.
+pub fn run_post_download_hook(settings: &HookSettings, item: &QueueItem, path: &Path) {
+    let Some(hook) = settings
+        .post_download
+        .as_deref()
+        .map(str::trim)
+        .filter(|hook| !hook.is_empty())
+    else {
+        return;
+    };
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(hook).arg(path);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(hook).arg("sh").arg(path);
+        command
+    };
+    let optional = |value: Option<String>| value.unwrap_or_default();
+    command
+        .env("AD_FILE", path)
+        .env("AD_ID", &item.id)
+        .env("AD_URL", &item.youtube_url)
+        .env("AD_TITLE", &item.title)
+        .env("AD_ARTIST", &item.artist)
+        .env("AD_ALBUM", optional(item.album.clone()))
+        .env("AD_GENRE", optional(item.genre.clone()))
+        .env("AD_YEAR", optional(item.year.map(|year| year.to_string())))
+        .env(
+            "AD_DURATION",
+            optional(item.duration.map(|secs| secs.to_string())),
+        )
+        .env("AD_QUEUE", &item.queue_id)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let timeout = Duration::from_secs(settings.timeout_secs.max(1));
+    let id = item.id.clone();
+    tokio::spawn(async move {
+        let output = match command.spawn() {
+            Ok(child) => tokio::time::timeout(timeout, child.wait_with_output()).await,
+            Err(err) => {
+                warn!("failed to start post-download hook for {id}: {err}");
+                return;
+            }
+        };
+        match output {
+            Ok(Ok(output)) if output.status.success() => {
+                info!("post-download hook finished for {id}");
+            }
+            Ok(Ok(output)) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                warn!(
+                    "post-download hook for {id} exited with {}: {}",
+                    output.status,
+                    stderr.trim()
+                );
+            }
+            Ok(Err(err)) => warn!("post-download hook for {id} failed: {err}"),
+            Err(_) => warn!("post-download hook for {id} timed out after {timeout:?}"),
+        }
+    });
+}
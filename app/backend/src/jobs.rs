@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::error;
+
+const MAX_FINISHED_JOBS: usize = 200;
+const PERSIST_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Import,
+    Download,
+    Preview,
+    Enrich,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Complete,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub kind: JobKind,
+    pub state: JobState,
+    pub target: Option<String>,
+    // Queue holding the target item; one video may sit in several queues.
+    #[serde(default)]
+    pub queue_id: Option<String>,
+    // Owner of the queue the job works on; `None` is visible to the admin token only.
+    #[serde(default)]
+    pub workspace: Option<String>,
+    pub total: usize,
+    pub processed: usize,
+    pub succeeded: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+}
+
+struct JobEntry {
+    job: Job,
+    cancel: CancellationToken,
+}
+
+#[derive(Clone)]
+pub struct JobRegistry {
+    entries: Arc<Mutex<HashMap<String, JobEntry>>>,
+    path: PathBuf,
+    // Set while a save is scheduled; the writer lock keeps saves in snapshot order.
+    save_pending: Arc<AtomicBool>,
+    writer: Arc<Mutex<()>>,
+}
+
+impl JobRegistry {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut jobs: Vec<Job> = if path.exists() {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("failed to parse {}", path.display()))?
+        } else {
+            Vec::new()
+        };
+        for job in jobs.iter_mut().filter(|job| job.state == JobState::Running) {
+            job.state = JobState::Failed;
+            job.error = Some("interrupted by restart".to_string());
+            job.finished_at = Some(now());
+        }
+        let entries = jobs
+            .into_iter()
+            .map(|job| {
+                let entry = JobEntry {
+                    job,
+                    cancel: CancellationToken::new(),
+                };
+                (entry.job.id.clone(), entry)
+            })
+            .collect();
+        Ok(Self {
+            entries: Arc::new(Mutex::new(entries)),
+            path: path.to_path_buf(),
+            save_pending: Arc::new(AtomicBool::new(false)),
+            writer: Arc::new(Mutex::new(())),
+        })
+    }
+
+    pub async fn start(
+        &self,
+        kind: JobKind,
+        target: Option<String>,
+        queue_id: Option<String>,
+        workspace: Option<String>,
+    ) -> (String, CancellationToken) {
+        let id = uuid::Uuid::new_v4().to_string();
+        let cancel = CancellationToken::new();
+        let job = Job {
+            id: id.clone(),
+            kind,
+            state: JobState::Running,
+            target,
+            queue_id,
+            workspace,
+            total: 0,
+            processed: 0,
+            succeeded: 0,
+            skipped: 0,
+            failed: 0,
+            error: None,
+            warnings: Vec::new(),
+            created_at: now(),
+            finished_at: None,
+        };
+        let mut entries = self.entries.lock().await;
+        entries.insert(
+            id.clone(),
+            JobEntry {
+                job,
+                cancel: cancel.clone(),
+            },
+        );
+        drop(entries);
+        self.persist();
+        (id, cancel)
+    }
+
+    pub async fn update(&self, id: &str, update: impl FnOnce(&mut Job)) {
+        if let Some(entry) = self.entries.lock().await.get_mut(id) {
+            update(&mut entry.job);
+        }
+    }
+
+    pub async fn finish(&self, id: &str, result: Result<(), String>) {
+        let mut entries = self.entries.lock().await;
+        let Some(entry) = entries.get_mut(id) else {
+            return;
+        };
+        entry.job.state = match result {
+            _ if entry.cancel.is_cancelled() => JobState::Cancelled,
+            Ok(()) => JobState::Complete,
+            Err(err) => {
+                entry.job.error = Some(err);
+                JobState::Failed
+            }
+        };
+        entry.job.finished_at = Some(now());
+        prune_finished(&mut entries);
+        drop(entries);
+        self.persist();
+    }
+
+    pub async fn cancel(&self, id: &str) -> Option<Job> {
+        let entries = self.entries.lock().await;
+        let entry = entries.get(id)?;
+        if entry.job.state == JobState::Running {
+            entry.cancel.cancel();
+        }
+        Some(entry.job.clone())
+    }
+
+    pub async fn get(&self, id: &str) -> Option<Job> {
+        self.entries
+            .lock()
+            .await
+            .get(id)
+            .map(|entry| entry.job.clone())
+    }
+
+    pub async fn list(&self) -> Vec<Job> {
+        let mut jobs: Vec<Job> = self
+            .entries
+            .lock()
+            .await
+            .values()
+            .map(|entry| entry.job.clone())
+            .collect();
+        jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        jobs
+    }
+
+    // Bursts of job updates collapse into one write that runs off the runtime threads.
+    fn persist(&self) {
+        if self.save_pending.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let registry = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(PERSIST_DELAY).await;
+            registry.flush().await;
+        });
+    }
+
+    pub async fn flush(&self) {
+        let _writer = self.writer.lock().await;
+        self.save_pending.store(false, Ordering::Release);
+        let content = {
+            let entries = self.entries.lock().await;
+            let jobs: Vec<&Job> = entries.values().map(|entry| &entry.job).collect();
+            serde_json::to_vec_pretty(&jobs)
+        };
+        let path = self.path.clone();
+        let result = match content {
+            Ok(content) => tokio::task::spawn_blocking(move || write_atomic(&path, &content))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|result| result),
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = result {
+            error!("failed to save jobs: {err}");
+        }
+    }
+}
+
+fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, content)
+        .with_context(|| format!("failed to write {}", temp_path.display()))?;
+    fs::rename(&temp_path, path).with_context(|| format!("failed to replace {}", path.display()))
+}
+
+fn prune_finished(entries: &mut HashMap<String, JobEntry>) {
+    let mut finished: Vec<(String, String)> = entries
+        .values()
+        .filter(|entry| entry.job.state != JobState::Running)
+        .map(|entry| (entry.job.created_at.clone(), entry.job.id.clone()))
+        .collect();
+    if finished.len() <= MAX_FINISHED_JOBS {
+        return;
+    }
+    finished.sort();
+    let excess = finished.len() - MAX_FINISHED_JOBS;
+    for (_, id) in finished.into_iter().take(excess) {
+        entries.remove(&id);
+    }
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
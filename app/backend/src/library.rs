@@ -0,0 +1,103 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::MediaServerSettings;
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaServerKind {
+    Plex,
+    Jellyfin,
+    Navidrome,
+}
+
+#[derive(Deserialize)]
+struct SubsonicEnvelope {
+    #[serde(rename = "subsonic-response")]
+    response: SubsonicResponse,
+}
+
+#[derive(Deserialize)]
+struct SubsonicResponse {
+    status: String,
+    error: Option<SubsonicError>,
+}
+
+#[derive(Deserialize)]
+struct SubsonicError {
+    message: Option<String>,
+}
+
+pub fn validate_media_server(settings: &MediaServerSettings) -> Result<(), String> {
+    let Some(kind) = settings.kind else {
+        return Ok(());
+    };
+    let url = reqwest::Url::parse(&settings.base_url)
+        .map_err(|err| format!("invalid media server url {}: {err}", settings.base_url))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("media server url must use http or https".to_string());
+    }
+    if settings.token.trim().is_empty() {
+        return Err("media server token is required".to_string());
+    }
+    if kind == MediaServerKind::Navidrome && settings.username.is_none() {
+        return Err("navidrome requires a username".to_string());
+    }
+    Ok(())
+}
+
+pub async fn refresh_media_library(
+    client: &reqwest::Client,
+    settings: &MediaServerSettings,
+) -> Result<()> {
+    let Some(kind) = settings.kind else {
+        return Ok(());
+    };
+    let base = settings.base_url.trim_end_matches('/');
+    let token = settings.token.trim();
+    let request = match kind {
+        MediaServerKind::Plex => {
+            let section = settings.library_id.as_deref().unwrap_or("all");
+            client
+                .get(format!("{base}/library/sections/{section}/refresh"))
+                .header("X-Plex-Token", token)
+        }
+        MediaServerKind::Jellyfin => client
+            .post(format!("{base}/Library/Refresh"))
+            .header("X-Emby-Token", token),
+        MediaServerKind::Navidrome => {
+            let username = settings.username.as_deref().unwrap_or_default();
+            // Subsonic accepts the hex-encoded password, which avoids needing an MD5 salt.
+            let password: String = token.bytes().map(|byte| format!("{byte:02x}")).collect();
+            client.get(format!("{base}/rest/startScan")).query(&[
+                ("u", username),
+                ("p", &format!("enc:{password}")),
+                ("v", "1.16.1"),
+                ("c", "AudioDownloader"),
+                ("f", "json"),
+            ])
+        }
+    };
+    let response = request
+        .send()
+        .await
+        .context("failed to reach media server")?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("media server responded with {status}");
+    }
+    if kind == MediaServerKind::Navidrome {
+        let envelope: SubsonicEnvelope = response
+            .json()
+            .await
+            .context("invalid navidrome response")?;
+        if envelope.response.status != "ok" {
+            let message = envelope.response.error.and_then(|error| error.message);
+            return Err(anyhow!(
+                "navidrome scan failed: {}",
+                message.unwrap_or_else(|| "unknown error".to_string())
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use tokio::process::{Child, Command};
+use tokio::sync::Notify;
+
+use crate::media::{apply_yt_dlp_common_args, probe_video_info};
+use crate::settings::LivestreamSettings;
+use crate::types::{AppState, DownloadState, QueueItem};
+
+const RELEASE_POLL_SECS: u64 = 60;
+const STOP_GRACE: Duration = Duration::from_secs(15);
+
+pub async fn record_livestream(
+    state: &AppState,
+    item: &QueueItem,
+    settings: &LivestreamSettings,
+    selector: Option<String>,
+    extra_args: &[String],
+) -> Result<PathBuf> {
+    let prefix = format!("{}-live", item.scratch_name());
+    let output_template = state.temp_dir.join(format!("{prefix}.%(ext)s"));
+    let output_template = output_template
+        .to_str()
+        .ok_or_else(|| anyhow!("invalid recording output path"))?
+        .to_string();
+
+    let mut cmd = Command::new("yt-dlp");
+    cmd.arg("-f")
+        .arg(selector.unwrap_or_else(|| "bestaudio/best".to_string()))
+        .arg("--no-playlist")
+        .arg("--no-part")
+        .arg("--hls-use-mpegts")
+        .arg("-o")
+        .arg(output_template)
+        .arg(&item.youtube_url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if settings.live_from_start {
+        cmd.arg("--live-from-start");
+    }
+    cmd.args(extra_args);
+    apply_yt_dlp_common_args(&mut cmd, &*state.cookies.lock().await);
+    // Its own process group, so a console break reaches yt-dlp alone.
+    #[cfg(windows)]
+    cmd.creation_flags(windows_sys::Win32::System::Threading::CREATE_NEW_PROCESS_GROUP);
+    let mut child = cmd
+        .kill_on_drop(true)
+        .spawn()
+        .context("yt-dlp execution failed")?;
+
+    let key = (item.queue_id.clone(), item.id.clone());
+    let stop = Arc::new(Notify::new());
+    state
+        .recordings
+        .lock()
+        .await
+        .insert(key.clone(), stop.clone());
+
+    let started = Instant::now();
+    let limit = settings.max_duration_secs.map(Duration::from_secs);
+    let mut ticker =
+        tokio::time::interval(Duration::from_secs(settings.report_interval_secs.max(1)));
+    let result = loop {
+        tokio::select! {
+            status = child.wait() => break status.map(|_| ()).context("yt-dlp execution failed"),
+            _ = stop.notified() => break stop_child(&mut child).await,
+            _ = ticker.tick() => {
+                report_recording(state, item, &prefix, started.elapsed()).await;
+                if limit.is_some_and(|limit| started.elapsed() >= limit) {
+                    break stop_child(&mut child).await;
+                }
+            }
+        }
+    };
+    state.recordings.lock().await.remove(&key);
+    result?;
+
+    report_recording(state, item, &prefix, started.elapsed()).await;
+    find_recording_file(&state.temp_dir, &prefix)
+        .ok_or_else(|| anyhow!("livestream recording produced no file"))
+}
+
+// An interrupt lets yt-dlp finish writing the recording; it is only killed if it hangs.
+async fn stop_child(child: &mut Child) -> Result<()> {
+    interrupt(child);
+    match tokio::time::timeout(STOP_GRACE, child.wait()).await {
+        Ok(status) => status.map(|_| ()).context("yt-dlp execution failed"),
+        Err(_) => child.kill().await.context("failed to stop yt-dlp"),
+    }
+}
+
+#[cfg(unix)]
+fn interrupt(child: &Child) {
+    if let Some(pid) = child.id() {
+        // SAFETY: the pid belongs to a child that has not been reaped yet.
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGINT);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn interrupt(child: &Child) {
+    use windows_sys::Win32::System::Console::{GenerateConsoleCtrlEvent, CTRL_BREAK_EVENT};
+    if let Some(pid) = child.id() {
+        // SAFETY: the child was started in its own process group, whose id is its pid.
+        unsafe {
+            GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pid);
+        }
+    }
+}
+
+async fn report_recording(state: &AppState, item: &QueueItem, prefix: &str, elapsed: Duration) {
+    let bytes = recording_size(&state.temp_dir, prefix);
+    let mut queue = state.queue.lock().await;
+    if let Some(item) = queue
+        .iter_mut()
+        .find(|entry| entry.matches_key(&item.queue_id, &item.id))
+    {
+        item.recorded_bytes = Some(bytes);
+        item.recorded_seconds = Some(elapsed.as_secs());
+    }
+}
+
+fn recording_size(dir: &Path, prefix: &str) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(prefix))
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn find_recording_file(dir: &Path, prefix: &str) -> Option<PathBuf> {
+    let entries = std::fs::read_dir(dir).ok()?;
+    entries
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(&format!("{prefix}."))
+        })
+        .filter_map(|entry| Some((entry.metadata().ok()?.len(), entry.path())))
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, path)| path)
+}
+
+pub async fn wait_for_release(state: &AppState, queue_id: &str, id: &str) -> bool {
+    loop {
+        let (url, scheduled_at) = {
+            let queue = state.queue.lock().await;
+            match queue.iter().find(|item| item.matches_key(queue_id, id)) {
+                Some(item) if item.state == DownloadState::Scheduled => {
+                    (item.youtube_url.clone(), item.scheduled_at)
+                }
+                _ => return false,
+            }
+        };
+
+        let now = chrono::Utc::now().timestamp();
+        let remaining = scheduled_at.map_or(0, |at| at - now).max(0) as u64;
+        if remaining > 0 {
+            tokio::time::sleep(Duration::from_secs(remaining.min(RELEASE_POLL_SECS))).await;
+            continue;
+        }
+
+        let info = match probe_video_info(state, &url, true).await {
+            Ok(info) => info,
+            Err(err) => {
+                tracing::error!("release check failed for {id}: {err:?}");
+                tokio::time::sleep(Duration::from_secs(RELEASE_POLL_SECS)).await;
+                continue;
+            }
+        };
+        let mut queue = state.queue.lock().await;
+        let Some(item) = queue.iter_mut().find(|item| item.matches_key(queue_id, id)) else {
+            return false;
+        };
+        if item.state != DownloadState::Scheduled {
+            return false;
+        }
+        match info.live_status.as_deref() {
+            Some("is_upcoming") => {
+                item.scheduled_at = info.release_timestamp;
+                drop(queue);
+                tokio::time::sleep(Duration::from_secs(RELEASE_POLL_SECS)).await;
+            }
+            live_status => {
+                item.state = DownloadState::Waiting;
+                item.scheduled_at = None;
+                item.is_live = live_status == Some("is_live");
+                item.duration = info.duration.or(item.duration);
+                return true;
+            }
+        }
+    }
+}
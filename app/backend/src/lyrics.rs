@@ -0,0 +1,230 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::cookies::CookieSource;
+use crate::media::apply_yt_dlp_common_args;
+use crate::settings::LyricsSettings;
+use crate::types::QueueItem;
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LyricsProvider {
+    Lrclib,
+    Captions,
+}
+
+#[derive(Deserialize)]
+struct LrclibResponse {
+    #[serde(rename = "syncedLyrics")]
+    synced_lyrics: Option<String>,
+}
+
+pub async fn write_lrc_sidecar(
+    client: &reqwest::Client,
+    settings: &LyricsSettings,
+    item: &QueueItem,
+    audio_path: &Path,
+    temp_dir: &Path,
+    cookies: &CookieSource,
+) -> Result<Option<PathBuf>> {
+    for provider in &settings.providers {
+        let lyrics = match provider {
+            LyricsProvider::Lrclib => fetch_lrclib(client, item).await,
+            LyricsProvider::Captions => fetch_captions_lrc(settings, item, temp_dir, cookies).await,
+        };
+        match lyrics {
+            Ok(Some(content)) => {
+                let path = audio_path.with_extension("lrc");
+                tokio::fs::write(&path, content)
+                    .await
+                    .with_context(|| format!("failed to write {}", path.display()))?;
+                return Ok(Some(path));
+            }
+            Ok(None) => {}
+            Err(err) => tracing::error!("lyrics provider failed for {}: {err}", item.id),
+        }
+    }
+    Ok(None)
+}
+
+async fn fetch_lrclib(client: &reqwest::Client, item: &QueueItem) -> Result<Option<String>> {
+    let artist = item.artists.first().unwrap_or(&item.artist);
+    let mut query = vec![
+        ("artist_name", artist.clone()),
+        ("track_name", item.title.clone()),
+    ];
+    if let Some(album) = item.album.as_ref() {
+        query.push(("album_name", album.clone()));
+    }
+    if let Some(duration) = item.duration {
+        query.push(("duration", duration.to_string()));
+    }
+
+    let response = client
+        .get("https://lrclib.net/api/get")
+        .query(&query)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+    let body: LrclibResponse = response.json().await?;
+    Ok(body
+        .synced_lyrics
+        .filter(|lyrics| !lyrics.trim().is_empty()))
+}
+
+async fn fetch_captions_lrc(
+    settings: &LyricsSettings,
+    item: &QueueItem,
+    temp_dir: &Path,
+    cookies: &CookieSource,
+) -> Result<Option<String>> {
+    let prefix = format!("{}-captions", item.id);
+    let output_template = temp_dir.join(format!("{prefix}.%(ext)s"));
+    let output_template = output_template
+        .to_str()
+        .ok_or_else(|| anyhow!("invalid captions output path"))?
+        .to_string();
+
+    let mut cmd = Command::new("yt-dlp");
+    cmd.arg("--skip-download")
+        .arg("--write-subs")
+        .arg("--sub-format")
+        .arg("vtt")
+        .arg("--sub-langs")
+        .arg(settings.languages.join(","))
+        .arg("--no-playlist")
+        .arg("-o")
+        .arg(output_template)
+        .arg(&item.youtube_url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    if settings.include_auto_captions {
+        cmd.arg("--write-auto-subs");
+    }
+    apply_yt_dlp_common_args(&mut cmd, cookies);
+    let status = cmd.status().await.context("yt-dlp execution failed")?;
+
+    // One file is written per matching language; prefer them in the configured order and
+    // remove every one of them, even when yt-dlp failed halfway.
+    let captions = caption_files(temp_dir, &prefix);
+    let preferred = settings
+        .languages
+        .iter()
+        .find_map(|language| {
+            let name = format!("{prefix}.{language}.vtt");
+            captions
+                .iter()
+                .find(|path| path.file_name().is_some_and(|file_name| *file_name == *name))
+        })
+        .or(captions.first());
+    let content = match preferred {
+        Some(path) if status.success() => tokio::fs::read_to_string(path).await.ok(),
+        _ => None,
+    };
+    for path in &captions {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+    let Some(content) = content else {
+        return Ok(None);
+    };
+    let lrc = vtt_to_lrc(&content);
+    Ok(Some(lrc).filter(|lrc| !lrc.is_empty()))
+}
+
+fn caption_files(dir: &Path, prefix: &str) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.starts_with(&format!("{prefix}.")) && name.ends_with(".vtt")
+                })
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+pub fn vtt_to_lrc(content: &str) -> String {
+    let mut lines = Vec::new();
+    let mut last_text = String::new();
+    let mut current_start: Option<f64> = None;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if let Some((start, _)) = line.split_once("-->") {
+            current_start = parse_vtt_timestamp(start.trim());
+            continue;
+        }
+        if line.is_empty() {
+            current_start = None;
+            continue;
+        }
+        let Some(start) = current_start else {
+            continue;
+        };
+
+        let text = strip_vtt_markup(line);
+        if text.is_empty() || text == last_text {
+            continue;
+        }
+        lines.push(format!("[{}]{text}", format_lrc_timestamp(start)));
+        last_text = text;
+    }
+
+    lines.join("\n")
+}
+
+fn parse_vtt_timestamp(raw: &str) -> Option<f64> {
+    let raw = raw.split_whitespace().next()?;
+    let parts: Vec<&str> = raw.split(':').collect();
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [hours, minutes, seconds] => (hours.parse::<f64>().ok()?, *minutes, *seconds),
+        [minutes, seconds] => (0.0, *minutes, *seconds),
+        _ => return None,
+    };
+    let minutes = minutes.parse::<f64>().ok()?;
+    let seconds = seconds.replace(',', ".").parse::<f64>().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+fn format_lrc_timestamp(seconds: f64) -> String {
+    let centis = (seconds * 100.0).round() as u64;
+    format!(
+        "{:02}:{:02}.{:02}",
+        centis / 6000,
+        (centis / 100) % 60,
+        centis % 100
+    )
+}
+
+fn strip_vtt_markup(line: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in line.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .trim()
+        .to_string()
+}
@@ -1,64 +1,336 @@
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::process::ExitCode;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use axum::http::HeaderValue;
 use axum::routing::{delete, get, post};
 use axum::Router;
-use tower_http::cors::{Any, CorsLayer};
+use axum_server::tls_rustls::RustlsConfig;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 use tower_http::services::ServeDir;
-use tracing::info;
+use tracing::{error, info, warn};
 
+mod access;
+mod analysis;
+mod archive;
+mod assets;
+mod cache;
+mod cli;
+mod clipboard;
+mod cookies;
+mod cover;
+mod discovery;
+mod duplicates;
+mod enrich;
 mod errors;
+mod feeds;
+mod files;
 mod handlers;
+mod hooks;
+mod jobs;
+mod library;
+mod live;
+mod lyrics;
 mod media;
+mod metadata;
+mod notifications;
 mod port;
+mod postprocess;
+mod presets;
+mod queues;
+mod scheduler;
+mod session;
+mod settings;
+mod sidecar;
+mod subscriptions;
 mod types;
+mod upload;
+mod webhooks;
 
 use types::AppState;
 
+const DEV_ORIGINS: [&str; 2] = ["http://localhost:5173", "http://127.0.0.1:5173"];
+
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     tracing_subscriber::fmt().with_env_filter("info").init();
 
+    let command = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("{err}\n\n{}", cli::USAGE);
+            return Ok(ExitCode::from(2));
+        }
+    };
+
     let project_root = resolve_project_root();
-    let preview_dir = project_root.join("app").join("preview_cache");
-    let temp_dir = project_root.join("app").join("tmp");
+    let state_path = |base: fn() -> Option<PathBuf>, name| {
+        settings::state_path(&project_root, base(), name)
+    };
+    let settings_path = state_path(dirs::config_dir, "settings.json");
+    let settings = settings::load_settings(&settings_path)?;
+    let server = settings.server.clone();
+    let preview_dir = settings.storage.resolve_preview_dir();
+    let temp_dir = settings.storage.resolve_temp_dir();
+    info!("preview cache: {}, temp: {}", preview_dir.display(), temp_dir.display());
     tokio::fs::create_dir_all(&preview_dir).await?;
     tokio::fs::create_dir_all(&temp_dir).await?;
+    tokio::fs::create_dir_all(preview_dir.join("thumbnails")).await?;
+    let metadata_cache_dir = state_path(dirs::cache_dir, "metadata_cache");
+    tokio::fs::create_dir_all(&metadata_cache_dir).await?;
+    let archive_path = state_path(dirs::data_dir, "archive.json");
+    let archive = archive::load_archive(&archive_path)?;
+    let cookies_path = state_path(dirs::data_dir, "cookies.txt");
+    let cookie_source = cookies::CookieSource {
+        file: cookies_path.exists().then(|| cookies_path.clone()),
+        browser: cookies::browser_spec(&settings.cookies),
+    };
+    let jobs_path = state_path(dirs::data_dir, "jobs.json");
+    let queue_path = state_path(dirs::data_dir, "queue.json");
+    // The command line runs on its own queue and never touches the saved session.
+    let queue = match command {
+        cli::Command::Serve { .. } => session::load_queue(&queue_path)?,
+        cli::Command::Download(_) => Vec::new(),
+    };
+    let queues_path = state_path(dirs::data_dir, "queues.json");
+    let mut queues = queues::load_queues(&queues_path)?;
+    queues::ensure_workspace_queues(&mut queues, &settings.server.users);
+    let subscriptions_path = state_path(dirs::data_dir, "subscriptions.json");
+    let subscriptions = subscriptions::load_subscriptions(&subscriptions_path)?;
+    let schedules_path = state_path(dirs::data_dir, "schedules.json");
+    let schedules = scheduler::load_schedules(&schedules_path)?;
+    let presets_path = state_path(dirs::data_dir, "presets.json");
+    let state_files = [
+        &settings_path,
+        &archive_path,
+        &cookies_path,
+        &jobs_path,
+        &queue_path,
+        &queues_path,
+        &subscriptions_path,
+        &schedules_path,
+        &presets_path,
+    ];
+    for dir in state_files.into_iter().filter_map(|path| path.parent()) {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let jobs = jobs::JobRegistry::load(&jobs_path)?;
+    let presets = presets::load_presets(&presets_path)?;
+    let headless = match command {
+        cli::Command::Serve { headless } => headless || settings.headless || !has_display(),
+        cli::Command::Download(_) => true,
+    };
+    if headless && matches!(command, cli::Command::Serve { .. }) {
+        info!("running headless: desktop-only endpoints are disabled");
+    }
 
     let state = AppState {
-        queue: std::sync::Arc::new(tokio::sync::Mutex::new(Vec::new())),
+        queue: std::sync::Arc::new(tokio::sync::Mutex::new(queue)),
         preview_dir: preview_dir.clone(),
         temp_dir: temp_dir.clone(),
-        download_semaphore: std::sync::Arc::new(tokio::sync::Semaphore::new(6)),
+        download_semaphore: std::sync::Arc::new(tokio::sync::Semaphore::new(
+            settings.network.max_concurrent_downloads.max(1),
+        )),
+        metadata_semaphore: std::sync::Arc::new(tokio::sync::Semaphore::new(
+            settings.network.max_concurrent_probes.max(1),
+        )),
         client: reqwest::Client::new(),
         project_root,
+        settings: std::sync::Arc::new(tokio::sync::Mutex::new(settings)),
+        settings_path,
+        recordings: std::sync::Arc::new(tokio::sync::Mutex::new(
+            std::collections::HashMap::new(),
+        )),
+        archive: std::sync::Arc::new(tokio::sync::Mutex::new(archive)),
+        archive_path,
+        cookies_path,
+        cookies: std::sync::Arc::new(tokio::sync::Mutex::new(cookie_source)),
+        metadata_cache_dir,
+        jobs,
+        batches: std::sync::Arc::new(tokio::sync::Mutex::new(
+            std::collections::HashMap::new(),
+        )),
+        pending_previews: std::sync::Arc::new(tokio::sync::Mutex::new(
+            std::collections::HashMap::new(),
+        )),
+        output_names: std::sync::Arc::new(std::sync::Mutex::new(
+            std::collections::HashMap::new(),
+        )),
+        queues: std::sync::Arc::new(tokio::sync::Mutex::new(queues)),
+        queues_path,
+        subscriptions: std::sync::Arc::new(tokio::sync::Mutex::new(subscriptions)),
+        subscriptions_path,
+        schedules: std::sync::Arc::new(tokio::sync::Mutex::new(schedules)),
+        schedules_path,
+        presets: std::sync::Arc::new(tokio::sync::Mutex::new(presets)),
+        presets_path,
+        headless,
+        clipboard: std::sync::Arc::new(tokio::sync::Mutex::new(Vec::new())),
     };
 
-    let cors = CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any);
+    if let cli::Command::Download(args) = command {
+        let jobs = state.jobs.clone();
+        let code = cli::run_download(state, args).await.unwrap_or_else(|err| {
+            eprintln!("error: {err:#}");
+            ExitCode::FAILURE
+        });
+        jobs.flush().await;
+        return Ok(code);
+    }
 
+    tokio::spawn(session::autosave_queue(state.clone(), queue_path));
+    tokio::spawn(handlers::poll_subscriptions(state.clone()));
+    tokio::spawn(handlers::run_schedules(state.clone()));
+    tokio::spawn(handlers::evict_previews(state.clone()));
+    if !state.headless {
+        tokio::spawn(handlers::watch_clipboard(state.clone()));
+    }
+
+    access::validate_server(&server).map_err(anyhow::Error::msg)?;
+    let policy = std::sync::Arc::new(access::AccessPolicy::from_settings(&server));
+    // The bundled frontend is same-origin; only the Vite dev server needs cross-origin access.
+    let dev_origins = DEV_ORIGINS.map(HeaderValue::from_static);
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::list(dev_origins))
+        .allow_methods(Any)
+        .allow_headers(Any);
+
+    let preview_owner =
+        axum::middleware::from_fn_with_state(state.clone(), handlers::require_preview_owner);
     let app = Router::new()
         .route("/api/version", get(handlers::version_info))
+        .route("/api/settings", get(handlers::get_settings).post(handlers::update_settings))
+        .route("/api/media-server/refresh", post(handlers::refresh_media_server))
+        .route("/api/session", get(handlers::get_session).post(handlers::restore_session))
+        .route(
+            "/api/cookies",
+            get(handlers::get_cookies)
+                .post(handlers::upload_cookies)
+                .delete(handlers::delete_cookies),
+        )
+        .route("/api/cookies/browsers", get(handlers::list_browsers))
+        .route(
+            "/api/cookies/browser",
+            post(handlers::select_browser).delete(handlers::clear_browser),
+        )
         .route("/api/default-dir", get(handlers::default_dir))
         .route("/api/select-dir", get(handlers::select_dir))
+        .route("/api/files/:id/reveal", post(handlers::reveal_file))
+        .route("/api/files/:id/spectrogram", get(handlers::file_spectrogram))
+        .route("/api/files/:id/analysis", get(handlers::file_analysis))
+        .route("/api/fs/list", get(handlers::list_fs))
+        .route("/api/queues", get(handlers::list_queues).post(handlers::create_queue))
+        .route("/api/queues/:id", delete(handlers::delete_named_queue))
         .route("/api/queue", get(handlers::list_queue))
+        .route(
+            "/api/subscriptions",
+            get(handlers::list_subscriptions).post(handlers::create_subscription),
+        )
+        .route("/api/subscriptions/:id", delete(handlers::delete_subscription))
+        .route("/api/subscriptions/:id/check", post(handlers::check_subscription))
+        .route("/api/subscriptions/:id/sync", post(handlers::sync_playlist_mirror))
+        .route("/api/schedules", get(handlers::list_schedules).post(handlers::create_schedule))
+        .route("/api/schedules/:id", delete(handlers::delete_schedule))
+        .route("/api/schedules/:id/last-run", get(handlers::get_schedule_run))
+        .route("/api/presets", get(handlers::list_presets).post(handlers::create_preset))
+        .route(
+            "/api/presets/:name",
+            get(handlers::get_preset)
+                .put(handlers::update_preset)
+                .delete(handlers::delete_preset),
+        )
         .route("/api/queue/add", post(handlers::add_queue))
+        .route("/api/clipboard", get(handlers::list_clipboard))
+        .route(
+            "/api/clipboard/:id",
+            post(handlers::accept_clipboard).delete(handlers::dismiss_clipboard),
+        )
         .route("/api/queue/update", post(handlers::update_queue))
+        .route("/api/queue/update-batch", post(handlers::update_queue_batch))
         .route("/api/queue/clear", post(handlers::clear_queue))
+        .route("/api/queue/retry-failed", post(handlers::retry_failed))
+        .route("/api/queue/enrich", post(handlers::enrich_queue))
         .route("/api/queue/:id", delete(handlers::delete_queue))
+        .route("/api/queue/:id/enrich", post(handlers::enrich_item))
+        .route("/api/queue/:id/attempts", get(handlers::list_attempts))
+        .route("/api/queue/:id/stop", post(handlers::stop_recording))
+        .route("/api/queue/:id/pause", post(handlers::pause_item))
+        .route("/api/queue/:id/resume", post(handlers::resume_item))
+        .route("/api/queue/:id/cancel", post(handlers::cancel_item))
         .route("/api/download", post(handlers::download_all))
+        .route("/api/downloads/cancel-all", post(handlers::cancel_all_downloads))
+        .route("/api/downloads/:batch", get(handlers::get_batch))
+        .route("/api/downloads/:batch/playlist", get(handlers::get_batch_playlist))
+        .route("/api/formats", get(handlers::list_formats))
+        .route("/api/archive", get(handlers::list_archive).delete(handlers::clear_archive))
+        .route("/api/archive/prune", post(handlers::prune_archive))
+        .route("/api/archive/verify", post(handlers::verify_archive))
+        .route("/api/archive/:id", delete(handlers::delete_archive_entry))
+        .route("/api/podcast.xml", get(handlers::podcast_feed_xml))
+        .route("/api/library/:id", get(handlers::library_file))
         .route("/api/import", post(handlers::import_list))
+        .route("/api/import/preview", post(handlers::preview_import))
+        .route("/api/import/sheet", post(handlers::import_sheet))
+        .route("/api/jobs", get(handlers::list_jobs))
+        .route("/api/jobs/:id", get(handlers::get_job))
+        .route("/api/jobs/:id/cancel", post(handlers::cancel_job))
         .route("/api/export", post(handlers::export_list))
         .route("/api/sample", get(handlers::sample_file))
+        .route("/api/preview/cache", delete(handlers::clear_previews))
         .route("/api/preview/:id", get(handlers::ensure_preview))
-        .nest_service("/preview", ServeDir::new(preview_dir))
+        .route("/api/preview/:id/waveform", get(handlers::preview_waveform))
+        .route("/api/thumbnail/:id", get(handlers::thumbnail))
+        .nest(
+            "/preview",
+            Router::new().fallback_service(ServeDir::new(preview_dir)).layer(preview_owner),
+        )
+        .fallback(assets::serve_frontend)
+        .layer(axum::middleware::from_fn_with_state(policy, access::require_access))
         .layer(cors)
         .with_state(state);
 
-    let address = "127.0.0.1:47815";
-    info!("listening on http://{address}");
-    let listener = tokio::net::TcpListener::bind(address).await?;
-    axum::serve(listener, app).await?;
-    Ok(())
+    let address: SocketAddr = server
+        .address
+        .parse()
+        .with_context(|| format!("invalid server address {}", server.address))?;
+    if server.remote && server.tls.is_none() {
+        warn!("remote mode without tls: the access token is sent in clear text");
+    }
+    let _discovery = match server.mdns {
+        true => discovery::advertise(address).unwrap_or_else(|err| {
+            error!("mdns advertisement failed: {err:#}");
+            None
+        }),
+        false => None,
+    };
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    match server.tls.filter(|_| server.remote) {
+        Some(tls) => {
+            let _ = rustls::crypto::ring::default_provider().install_default();
+            let config = RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path)
+                .await
+                .context("failed to load tls certificate")?;
+            info!("listening on https://{address}");
+            axum_server::bind_rustls(address, config).serve(service).await?;
+        }
+        None => {
+            info!("listening on http://{address}");
+            let listener = tokio::net::TcpListener::bind(address).await?;
+            axum::serve(listener, service).await?;
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// rfd and notify-send need an X11 or Wayland session on Linux; elsewhere a desktop is assumed.
+fn has_display() -> bool {
+    if !cfg!(target_os = "linux") {
+        return true;
+    }
+    ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|name| std::env::var_os(name).is_some_and(|value| !value.is_empty()))
 }
 
 fn resolve_project_root() -> PathBuf {
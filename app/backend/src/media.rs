@@ -1,18 +1,42 @@
 use std::env;
+use std::collections::HashMap;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::{anyhow, Result};
-use lofty::{AudioFile, ItemKey, MimeType, Picture, PictureType, Tag, TagType, TaggedFileExt};
-use sanitize_filename::sanitize;
+use chrono::{SecondsFormat, Utc};
+use deunicode::deunicode_with_tofu;
+use lofty::{
+    Accessor, AudioFile, ItemKey, ItemValue, MimeType, Picture, PictureType, Tag, TagItem,
+    TagType, TaggedFileExt,
+};
+use sanitize_filename::{sanitize, sanitize_with_options, Options};
 use tokio::process::Command;
 
+use crate::cache::{load_cached_info, store_cached_info, video_id_from_url};
+use crate::cookies::CookieSource;
 use crate::errors::AppError;
-use crate::types::{VideoInfo, YtDlpInfo};
+use crate::metadata::join_artists;
+use crate::settings::{AppSettings, OutputSettings};
+use crate::types::{
+    AppState, AudioFormat, DownloadStage, QueueItem, VideoInfo, YtDlpFormat, YtDlpInfo,
+    YtDlpThumb,
+};
 
-pub fn apply_yt_dlp_common_args(cmd: &mut Command) {
+pub fn apply_yt_dlp_common_args(cmd: &mut Command, cookies: &CookieSource) {
     cmd.arg("--extractor-args")
         .arg("youtube:player_client=default");
 
+    if let Some(file) = &cookies.file {
+        cmd.arg("--cookies").arg(file);
+        return;
+    }
+    if let Some(browser) = &cookies.browser {
+        cmd.arg("--cookies-from-browser").arg(browser);
+        return;
+    }
+
     if let Ok(cookies) = env::var("YTDLP_COOKIES") {
         let trimmed = cookies.trim();
         if !trimmed.is_empty() {
@@ -29,10 +53,48 @@ pub fn apply_yt_dlp_common_args(cmd: &mut Command) {
     }
 }
 
-pub async fn fetch_video_info(url: &str) -> Result<VideoInfo, AppError> {
+pub async fn probe_video_info(
+    state: &AppState,
+    url: &str,
+    refresh: bool,
+) -> Result<VideoInfo, AppError> {
+    let (jitter_ms, cache) = {
+        let settings = state.settings.lock().await;
+        (settings.network.probe_jitter_ms, settings.metadata_cache.clone())
+    };
+    let cache_id = video_id_from_url(url).filter(|_| cache.enabled && !refresh);
+    if let Some(id) = cache_id {
+        if let Some(info) = load_cached_info(&state.metadata_cache_dir, &id, cache.ttl_secs).await {
+            return Ok(info);
+        }
+    }
+
+    let _permit = state
+        .metadata_semaphore
+        .acquire()
+        .await
+        .map_err(|_| AppError::internal("metadata probe queue closed"))?;
+    if jitter_ms > 0 {
+        let delay = (uuid::Uuid::new_v4().as_u128() % (u128::from(jitter_ms) + 1)) as u64;
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+    }
+    let cookies = state.cookies.lock().await.clone();
+    let info = fetch_video_info(url, &cookies).await?;
+    let is_live = matches!(info.live_status.as_deref(), Some("is_live" | "is_upcoming"));
+    if cache.enabled && !is_live {
+        store_cached_info(&state.metadata_cache_dir, &info).await;
+    }
+    Ok(info)
+}
+
+pub async fn fetch_video_info(url: &str, cookies: &CookieSource) -> Result<VideoInfo, AppError> {
     let mut cmd = Command::new("yt-dlp");
-    cmd.arg("-J").arg("--no-playlist").arg(url);
-    apply_yt_dlp_common_args(&mut cmd);
+    cmd.arg("-J")
+        .arg("--no-playlist")
+        .arg("--ignore-no-formats-error")
+        .arg(url);
+    apply_yt_dlp_common_args(&mut cmd, cookies);
+    cmd.kill_on_drop(true);
     let output = cmd.output().await
         .map_err(|err| AppError::bad_request(format!("yt-dlp not available: {err}")))?;
 
@@ -47,27 +109,103 @@ pub async fn fetch_video_info(url: &str) -> Result<VideoInfo, AppError> {
         .map_err(|err| AppError::internal(err.to_string()))?;
 
     let title = info.title.unwrap_or_else(|| "Unknown".to_string());
-    let artist = info
-        .uploader
-        .or(info.channel)
-        .unwrap_or_else(|| "Unknown".to_string());
-    let thumbnail_url = info.thumbnail.or_else(|| {
-        info.thumbnails
-            .and_then(|mut thumbs| thumbs.pop())
-            .and_then(|thumb| thumb.url)
-    });
+    let uploader = info.uploader.or(info.channel);
+    let artist = uploader.clone().unwrap_or_else(|| "Unknown".to_string());
+    let thumbnail_candidates =
+        rank_thumbnails(info.thumbnail, info.thumbnails.unwrap_or_default());
+    let thumbnail_url = thumbnail_candidates.first().cloned();
     let duration = info.duration.map(|value| value.round() as u64);
 
     Ok(VideoInfo {
         id: info.id,
         title,
         artist,
+        uploader,
+        channel_url: info.channel_url,
+        description: info.description.filter(|description| !description.trim().is_empty()),
         thumbnail_url,
+        thumbnail_candidates,
         duration,
+        categories: info.categories.unwrap_or_default(),
+        tags: info.tags.unwrap_or_default(),
+        upload_date: info.upload_date,
+        release_date: info.release_date,
+        release_year: info.release_year,
+        chapters: info.chapters.unwrap_or_default(),
+        audio_formats: audio_formats(info.formats.unwrap_or_default()),
+        live_status: info
+            .live_status
+            .or_else(|| info.is_live.filter(|live| *live).map(|_| "is_live".to_string())),
+        release_timestamp: info.release_timestamp,
     })
 }
 
-pub async fn download_preview(url: &str, id: &str, dir: &Path) -> Result<PathBuf, AppError> {
+fn audio_formats(formats: Vec<YtDlpFormat>) -> Vec<AudioFormat> {
+    let mut audio: Vec<AudioFormat> = formats
+        .into_iter()
+        .filter_map(|format| {
+            let codec = format.acodec.filter(|codec| codec != "none")?;
+            Some(AudioFormat {
+                format_id: format.format_id,
+                ext: format.ext,
+                codec,
+                bitrate: format.abr,
+                sample_rate: format.asr,
+                channels: format.audio_channels,
+                filesize: format.filesize.or(format.filesize_approx),
+                language: format.language,
+                note: format.format_note,
+                audio_only: format.vcodec.as_deref().is_none_or(|codec| codec == "none"),
+            })
+        })
+        .collect();
+    audio.sort_by(|a, b| {
+        b.audio_only
+            .cmp(&a.audio_only)
+            .then(b.bitrate.unwrap_or(0.0).total_cmp(&a.bitrate.unwrap_or(0.0)))
+    });
+    audio
+}
+
+pub async fn list_playlist_ids(url: &str, cookies: &CookieSource) -> Result<Vec<String>, AppError> {
+    let mut cmd = Command::new("yt-dlp");
+    cmd.arg("--flat-playlist")
+        .arg("--print")
+        .arg("id")
+        .arg(url);
+    apply_yt_dlp_common_args(&mut cmd, cookies);
+    cmd.kill_on_drop(true);
+    let output = cmd.output().await
+        .map_err(|err| AppError::bad_request(format!("yt-dlp not available: {err}")))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::bad_request(format!(
+            "yt-dlp failed: {stderr}"
+        )));
+    }
+
+    // Channel pages list their tabs as entries; only keep ids shaped like video ids.
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|id| {
+            id.len() == 11
+                && id
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+        })
+        .map(str::to_string)
+        .collect())
+}
+
+pub async fn download_preview(
+    url: &str,
+    id: &str,
+    dir: &Path,
+    clip: Option<(f64, u32)>,
+    cookies: &CookieSource,
+) -> Result<PathBuf, AppError> {
     let output_template = dir.join(format!("{id}.%(ext)s"));
     let output_template = output_template
         .to_str()
@@ -79,9 +217,15 @@ pub async fn download_preview(url: &str, id: &str, dir: &Path) -> Result<PathBuf
         .arg("bestaudio")
         .arg("--no-playlist")
         .arg("-o")
-        .arg(output_template)
-        .arg(url);
-    apply_yt_dlp_common_args(&mut cmd);
+        .arg(output_template);
+    if let Some((start, seconds)) = clip {
+        // Only the requested range is fetched, so a preview no longer waits on the whole track.
+        cmd.arg("--download-sections")
+            .arg(format!("*{start}-{}", start + f64::from(seconds)));
+    }
+    cmd.arg(url);
+    apply_yt_dlp_common_args(&mut cmd, cookies);
+    cmd.kill_on_drop(true);
     let status = cmd.status().await
         .map_err(|err| AppError::bad_request(format!("yt-dlp not available: {err}")))?;
 
@@ -92,22 +236,55 @@ pub async fn download_preview(url: &str, id: &str, dir: &Path) -> Result<PathBuf
     find_preview_file(dir, id).ok_or_else(|| AppError::internal("preview file missing"))
 }
 
+fn rank_thumbnails(primary: Option<String>, thumbs: Vec<YtDlpThumb>) -> Vec<String> {
+    let mut ranked: Vec<(bool, i32, u64, String)> = thumbs
+        .into_iter()
+        .filter_map(|thumb| {
+            let url = thumb.url?;
+            // Prefer static JPEG/PNG variants; webp covers are poorly supported by taggers.
+            if url.contains(".webp") || url.contains("_webp") {
+                return None;
+            }
+            let is_maxres = url.contains("maxresdefault")
+                || thumb.id.as_deref() == Some("maxresdefault");
+            let area = u64::from(thumb.width.unwrap_or(0)) * u64::from(thumb.height.unwrap_or(0));
+            Some((is_maxres, thumb.preference.unwrap_or(i32::MIN), area, url))
+        })
+        .collect();
+    ranked.sort_by_key(|(is_maxres, preference, area, _)| {
+        std::cmp::Reverse((*is_maxres, *preference, *area))
+    });
+
+    let mut candidates: Vec<String> = ranked.into_iter().map(|(_, _, _, url)| url).collect();
+    if let Some(primary) = primary {
+        if !candidates.contains(&primary) {
+            candidates.push(primary);
+        }
+    }
+    candidates
+}
+
 pub async fn fetch_thumbnail(client: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
-    let response = client.get(url).send().await?;
+    let response = client.get(url).send().await?.error_for_status()?;
     let data = response.bytes().await?;
     Ok(data.to_vec())
 }
 
-pub fn tag_audio(path: &Path, artist: &str, thumbnail: Option<Vec<u8>>) -> Result<()> {
+pub fn tag_audio(
+    path: &Path,
+    item: &QueueItem,
+    settings: &AppSettings,
+    thumbnail: Option<Vec<u8>>,
+) -> Result<()> {
+    let mut tagged_file = lofty::read_from_path(path)?;
     let tag_type = match path.extension().and_then(|ext| ext.to_str()).unwrap_or("") {
         "mp3" => TagType::Id3v2,
         "m4a" | "mp4" => TagType::Mp4Ilst,
-        "flac" => TagType::VorbisComments,
-        "wav" => TagType::Id3v2,
-        _ => TagType::Id3v2,
+        "flac" | "opus" | "ogg" => TagType::VorbisComments,
+        "wav" | "aiff" | "aif" => TagType::Id3v2,
+        _ => tagged_file.primary_tag_type(),
     };
 
-    let mut tagged_file = lofty::read_from_path(path)?;
     if tagged_file.primary_tag().is_none() {
         tagged_file.insert_tag(Tag::new(tag_type));
     }
@@ -115,8 +292,62 @@ pub fn tag_audio(path: &Path, artist: &str, thumbnail: Option<Vec<u8>>) -> Resul
         .primary_tag_mut()
         .ok_or_else(|| anyhow!("unable to access tag"))?;
 
-    tag.insert_text(ItemKey::TrackArtist, artist.to_string());
-    tag.insert_text(ItemKey::AlbumArtist, artist.to_string());
+    tag.set_title(item.title.clone());
+    let artists = if item.artists.is_empty() {
+        vec![item.artist.clone()]
+    } else {
+        item.artists.clone()
+    };
+    if settings.artists.multi_value_tags && tag_type == TagType::VorbisComments {
+        tag.remove_key(&ItemKey::TrackArtist);
+        for artist in &artists {
+            tag.push(TagItem::new(ItemKey::TrackArtist, ItemValue::Text(artist.clone())));
+        }
+    } else {
+        tag.insert_text(ItemKey::TrackArtist, join_artists(&settings.artists, &artists));
+    }
+    tag.insert_text(ItemKey::AlbumArtist, artists[0].clone());
+    if let Some(album) = item.album.as_deref() {
+        tag.set_album(album.to_string());
+    }
+    if let Some(date) = item.date.as_deref() {
+        tag.insert_text(ItemKey::RecordingDate, date.to_string());
+    } else if let Some(year) = item.year {
+        tag.set_year(year);
+    }
+    if let Some(track_number) = item.track_number {
+        tag.set_track(track_number);
+    }
+    if let Some(disc_number) = item.disc_number {
+        tag.set_disk(disc_number);
+    }
+    if let Some(disc_total) = item.disc_total {
+        tag.set_disk_total(disc_total);
+    }
+    if item.compilation {
+        tag.insert_text(ItemKey::FlagCompilation, "1".to_string());
+    } else {
+        tag.remove_key(&ItemKey::FlagCompilation);
+    }
+    if let Some(genre) = item.genre.as_deref() {
+        tag.set_genre(genre.to_string());
+    }
+    if let Some(duration) = clip_duration(item) {
+        tag.insert_text(ItemKey::Length, (duration * 1000.0).round().to_string());
+    }
+    if let Some(recording_id) = item.musicbrainz_recording_id.as_deref() {
+        tag.insert_text(ItemKey::MusicBrainzRecordingId, recording_id.to_string());
+    }
+    if let Some(release_id) = item.musicbrainz_release_id.as_deref() {
+        tag.insert_text(ItemKey::MusicBrainzReleaseId, release_id.to_string());
+    }
+    if settings.tagging.write_provenance {
+        write_provenance_tags(tag, tag_type, item);
+    }
+    for (key, value) in &item.custom_tags {
+        let item_key = custom_tag_item_key(tag_type, key);
+        tag.insert_unchecked(TagItem::new(item_key, ItemValue::Text(value.clone())));
+    }
 
     if let Some(bytes) = thumbnail {
         let mime = detect_mime(&bytes);
@@ -128,6 +359,88 @@ pub fn tag_audio(path: &Path, artist: &str, thumbnail: Option<Vec<u8>>) -> Resul
     Ok(())
 }
 
+pub fn clip_duration(item: &QueueItem) -> Option<f64> {
+    let end = item.end.or(item.duration.map(|duration| duration as f64))?;
+    Some((end - item.start.unwrap_or(0.0)).max(0.0))
+}
+
+fn write_provenance_tags(tag: &mut Tag, tag_type: TagType, item: &QueueItem) {
+    let downloaded_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    tag.insert_text(
+        ItemKey::Comment,
+        format!(
+            "Source: {} (video id {}), downloaded {downloaded_at}",
+            item.youtube_url, item.id
+        ),
+    );
+    if tag_type == TagType::Id3v2 {
+        tag.insert(TagItem::new(
+            ItemKey::AudioSourceUrl,
+            ItemValue::Locator(item.youtube_url.clone()),
+        ));
+    }
+    let provenance = [
+        ("SOURCE_URL", item.youtube_url.clone()),
+        ("YOUTUBE_ID", item.id.clone()),
+        ("DOWNLOAD_DATE", downloaded_at),
+    ];
+    for (key, value) in provenance {
+        let item_key = custom_tag_item_key(tag_type, key);
+        tag.insert_unchecked(TagItem::new(item_key, ItemValue::Text(value)));
+    }
+}
+
+fn custom_tag_item_key(tag_type: TagType, key: &str) -> ItemKey {
+    let native = ItemKey::from_key(tag_type, key);
+    if !matches!(native, ItemKey::Unknown(_)) {
+        return native;
+    }
+    match key {
+        "COMPOSER" => ItemKey::Composer,
+        "ISRC" => ItemKey::Isrc,
+        "LABEL" => ItemKey::Label,
+        "PUBLISHER" => ItemKey::Publisher,
+        "LYRICIST" => ItemKey::Lyricist,
+        "CONDUCTOR" => ItemKey::Conductor,
+        "ARRANGER" => ItemKey::Arranger,
+        "PRODUCER" => ItemKey::Producer,
+        "REMIXER" => ItemKey::Remixer,
+        "COPYRIGHT" => ItemKey::CopyrightMessage,
+        "BPM" => ItemKey::Bpm,
+        "MOOD" => ItemKey::Mood,
+        "BARCODE" => ItemKey::Barcode,
+        "CATALOGNUMBER" => ItemKey::CatalogNumber,
+        "LANGUAGE" => ItemKey::Language,
+        _ if tag_type == TagType::Mp4Ilst && !key.starts_with("----:") => {
+            ItemKey::Unknown(format!("----:com.apple.iTunes:{key}"))
+        }
+        _ => native,
+    }
+}
+
+pub fn normalize_tag_key(key: &str) -> Option<String> {
+    let trimmed = key.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_uppercase())
+    }
+}
+
+pub fn normalize_custom_tags(tags: HashMap<String, String>) -> HashMap<String, String> {
+    tags.into_iter()
+        .filter_map(|(key, value)| {
+            let key = normalize_tag_key(&key)?;
+            let value = value.trim();
+            if value.is_empty() {
+                None
+            } else {
+                Some((key, value.to_string()))
+            }
+        })
+        .collect()
+}
+
 pub fn detect_mime(bytes: &[u8]) -> MimeType {
     if bytes.starts_with(&[0x89, 0x50, 0x4E, 0x47]) {
         MimeType::Png
@@ -138,16 +451,93 @@ pub fn detect_mime(bytes: &[u8]) -> MimeType {
     }
 }
 
-pub fn find_downloaded_file(dir: &Path, title: &str) -> Option<PathBuf> {
+pub fn find_downloaded_file(dir: &Path, title: &str, extensions: &[&str]) -> Option<PathBuf> {
     let entries = std::fs::read_dir(dir).ok()?;
+    let mut fallback = None;
     for entry in entries.flatten() {
         let path = entry.path();
-        let file_name = path.file_name()?.to_str()?;
-        if file_name.starts_with(&format!("{title}.")) {
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some(extension) = file_name.strip_prefix(&format!("{title}.")) else {
+            continue;
+        };
+        if extensions.contains(&extension) {
             return Some(path);
         }
+        if fallback.is_none() && is_audio_extension(extension) {
+            fallback = Some(path);
+        }
     }
-    None
+    fallback
+}
+
+fn is_audio_extension(extension: &str) -> bool {
+    matches!(
+        extension,
+        "flac" | "mp3" | "m4a" | "mp4" | "aac" | "wav" | "aiff" | "opus" | "ogg" | "webm"
+    )
+}
+
+// Renames replace whatever is at the target, so unless overwriting, files are hard-linked into
+// place instead, which fails if another process took the name in the meantime.
+pub async fn publish_staged_files(staging: &Path, dir: &Path, overwrite: bool) -> Result<()> {
+    let mut entries = tokio::fs::read_dir(staging).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let source = entry.path();
+        let target = dir.join(entry.file_name());
+        if !overwrite {
+            match link_into_place(&source, &target).await {
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                    return Err(anyhow!("{} already exists", target.display()));
+                }
+                Ok(()) => continue,
+                Err(_) => {}
+            }
+        } else if tokio::fs::rename(&source, &target).await.is_ok() {
+            continue;
+        }
+        // Different volume: copy next to the target first so the final step stays atomic.
+        let partial = dir.join(format!(".{}.partial", entry.file_name().to_string_lossy()));
+        tokio::fs::copy(&source, &partial).await?;
+        let placed = if overwrite {
+            tokio::fs::rename(&partial, &target).await
+        } else {
+            match link_into_place(&partial, &target).await {
+                Err(err) if err.kind() != ErrorKind::AlreadyExists => {
+                    copy_new(&partial, &target).await
+                }
+                linked => linked,
+            }
+        };
+        let _ = tokio::fs::remove_file(&partial).await;
+        if let Err(err) = placed {
+            return Err(anyhow!("failed to move {} into place: {err}", target.display()));
+        }
+        let _ = tokio::fs::remove_file(&source).await;
+    }
+    Ok(())
+}
+
+async fn link_into_place(source: &Path, target: &Path) -> std::io::Result<()> {
+    tokio::fs::hard_link(source, target).await?;
+    tokio::fs::remove_file(source).await
+}
+
+// For file systems without hard links; `create_new` still refuses an existing target.
+async fn copy_new(source: &Path, target: &Path) -> std::io::Result<()> {
+    let mut reader = tokio::fs::File::open(source).await?;
+    let mut writer = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(target)
+        .await?;
+    if let Err(err) = tokio::io::copy(&mut reader, &mut writer).await {
+        drop(writer);
+        let _ = tokio::fs::remove_file(target).await;
+        return Err(err);
+    }
+    writer.sync_all().await
 }
 
 pub fn find_preview_file(dir: &Path, id: &str) -> Option<PathBuf> {
@@ -170,7 +560,152 @@ pub fn sanitize_text(input: &str) -> String {
     sanitize(filtered)
 }
 
-pub fn parse_yt_dlp_progress(line: &str) -> Option<f32> {
+const MAX_NAME_BYTES: usize = 255;
+const WINDOWS_MAX_PATH: usize = 259;
+// Room for the " (n)" suffix the rename conflict policy appends.
+const CONFLICT_SUFFIX_LEN: usize = 6;
+// Used when yt-dlp picks the extension, long enough for ".webm" and ".opus".
+const UNKNOWN_EXTENSION_LEN: usize = 5;
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+pub fn validate_output(output: &OutputSettings) -> Result<(), String> {
+    if let Some(replacement) = &output.space_replacement {
+        if sanitize_text(replacement) != *replacement || replacement.contains(char::is_whitespace) {
+            return Err("space_replacement must be usable in a file name".to_string());
+        }
+    }
+    let max = MAX_NAME_BYTES - UNKNOWN_EXTENSION_LEN - CONFLICT_SUFFIX_LEN;
+    if let Some(length) = output.max_filename_length {
+        if !(8..=max).contains(&length) {
+            return Err(format!("max_filename_length must be between 8 and {max}"));
+        }
+    }
+    Ok(())
+}
+
+// Turns a title into a file stem that every target filesystem accepts and that, together with
+// `dir` and the extension, stays within the name and path limits. The standard library maps
+// long paths to their verbatim form itself, so `long_paths` only has to lift the budget.
+pub fn output_file_stem(
+    title: &str,
+    dir: &Path,
+    extension: Option<&str>,
+    output: &OutputSettings,
+) -> String {
+    let windows = output.windows_safe();
+    let options = Options {
+        windows: false,
+        truncate: false,
+        replacement: "",
+    };
+    let mut stem = sanitize_with_options(sanitize_text(title), options);
+    if output.ascii_filenames {
+        // Transliteration can produce separators again, e.g. "／" becomes "/".
+        stem = sanitize_text(&deunicode_with_tofu(&stem, "")).replace("  ", " ");
+    }
+    if output.lowercase_filenames {
+        stem = stem.to_lowercase();
+    }
+    if let Some(replacement) = &output.space_replacement {
+        stem = stem.split_whitespace().collect::<Vec<_>>().join(replacement);
+    }
+    let extension_len = extension.map_or(UNKNOWN_EXTENSION_LEN, |extension| extension.len() + 1);
+    let mut budget = MAX_NAME_BYTES - extension_len - CONFLICT_SUFFIX_LEN;
+    if let Some(max) = output.max_filename_length {
+        budget = budget.min(max);
+    }
+    if windows && !output.long_paths {
+        let dir_len = dir.to_string_lossy().chars().count() + 1;
+        let reserved = dir_len + extension_len + CONFLICT_SUFFIX_LEN;
+        budget = budget.min(WINDOWS_MAX_PATH.saturating_sub(reserved));
+    }
+    if stem.len() > budget {
+        let cut = (0..=budget).rev().find(|&index| stem.is_char_boundary(index)).unwrap_or(0);
+        stem.truncate(cut);
+    }
+    if windows {
+        // Windows drops trailing dots and spaces, so the written name would differ from ours.
+        stem.truncate(stem.trim_end_matches(['.', ' ']).len());
+        let base = stem.split('.').next().unwrap_or("").trim_end();
+        if WINDOWS_RESERVED_NAMES.iter().any(|name| name.eq_ignore_ascii_case(base)) {
+            stem.insert(0, '_');
+        }
+    }
+    let stem = stem.trim();
+    if stem.is_empty() {
+        return "untitled".to_string();
+    }
+    stem.to_string()
+}
+
+// The artist folder follows the album artist tag so collaborations sit next to the main artist;
+// items without an album go straight into the artist folder.
+pub fn organized_dir(dir: &Path, item: &QueueItem, output: &OutputSettings) -> PathBuf {
+    if !output.organize_folders {
+        return dir.to_path_buf();
+    }
+    let artist = item.artists.first().unwrap_or(&item.artist).trim();
+    let artist = if artist.is_empty() { "Unknown Artist" } else { artist };
+    let artist_dir = dir.join(output_file_stem(artist, dir, None, output));
+    match item.album.as_deref().map(str::trim).filter(|album| !album.is_empty()) {
+        Some(album) => {
+            let album = output_file_stem(album, &artist_dir, None, output);
+            artist_dir.join(album)
+        }
+        None => artist_dir,
+    }
+}
+
+pub const YT_DLP_PROGRESS_TEMPLATE: &str = "download:[progress] %(progress.downloaded_bytes)s \
+     %(progress.total_bytes)s %(progress.total_bytes_estimate)s %(progress.speed)s %(progress.eta)s";
+
+#[derive(Default)]
+pub struct TransferProgress {
+    pub percent: Option<f32>,
+    pub downloaded_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+    pub speed: Option<f64>,
+    pub eta: Option<u64>,
+}
+
+pub fn parse_yt_dlp_stage(line: &str) -> Option<DownloadStage> {
+    let tag = line.trim().strip_prefix('[')?.split(']').next()?;
+    match tag {
+        "download" => Some(DownloadStage::Downloading),
+        "ExtractAudio" | "ffmpeg" | "Merger" | "VideoConvertor" => Some(DownloadStage::Converting),
+        tag if tag.starts_with("Fixup") => Some(DownloadStage::Converting),
+        "Metadata" | "EmbedThumbnail" => Some(DownloadStage::Tagging),
+        _ => None,
+    }
+}
+
+pub fn parse_yt_dlp_progress(line: &str) -> Option<TransferProgress> {
+    if let Some(rest) = line.trim().strip_prefix("[progress]") {
+        let fields: Vec<Option<f64>> = rest
+            .split_whitespace()
+            .map(|field| field.parse::<f64>().ok())
+            .collect();
+        let field = |index: usize| fields.get(index).copied().flatten();
+        let downloaded_bytes = field(0).map(|value| value as u64);
+        let total_bytes = field(1).or(field(2)).map(|value| value as u64);
+        let percent = match (downloaded_bytes, total_bytes) {
+            (Some(downloaded), Some(total)) if total > 0 => {
+                Some(downloaded as f32 / total as f32 * 100.0)
+            }
+            _ => None,
+        };
+        return Some(TransferProgress {
+            percent,
+            downloaded_bytes,
+            total_bytes,
+            speed: field(3),
+            eta: field(4).map(|value| value as u64),
+        });
+    }
+
     let percent_index = line.rfind('%')?;
     let bytes = line.as_bytes();
     let mut start = percent_index;
@@ -183,5 +718,84 @@ pub fn parse_yt_dlp_progress(line: &str) -> Option<f32> {
         }
     }
     let value = line[start..percent_index].trim();
-    value.parse::<f32>().ok()
+    let percent = value.parse::<f32>().ok()?;
+    Some(TransferProgress {
+        percent: Some(percent),
+        ..TransferProgress::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_progress_template() {
+        let progress = parse_yt_dlp_progress("[progress] 250 1000 NA 52.5 3").unwrap();
+        assert_eq!(progress.downloaded_bytes, Some(250));
+        assert_eq!(progress.total_bytes, Some(1000));
+        assert_eq!(progress.percent, Some(25.0));
+        assert_eq!(progress.speed, Some(52.5));
+        assert_eq!(progress.eta, Some(3));
+    }
+
+    #[test]
+    fn falls_back_to_the_estimate_and_plain_percentages() {
+        let progress = parse_yt_dlp_progress("[progress] 10 NA 40 NA NA").unwrap();
+        assert_eq!(progress.total_bytes, Some(40));
+        assert_eq!(progress.percent, Some(25.0));
+        assert_eq!(progress.speed, None);
+        let progress = parse_yt_dlp_progress("[download]  42.3% of 3.10MiB").unwrap();
+        assert_eq!(progress.percent, Some(42.3));
+        assert!(parse_yt_dlp_progress("[download] Destination: a.webm").is_none());
+    }
+
+    #[test]
+    fn maps_log_prefixes_to_stages() {
+        let stage = |line| parse_yt_dlp_stage(line).map(|stage| stage as u8);
+        assert_eq!(stage("[download] 1%"), Some(DownloadStage::Downloading as u8));
+        assert_eq!(stage("[ExtractAudio] Destination"), Some(DownloadStage::Converting as u8));
+        assert_eq!(stage("[FixupM4a] Correcting"), Some(DownloadStage::Converting as u8));
+        assert_eq!(stage("[Metadata] Adding"), Some(DownloadStage::Tagging as u8));
+        assert_eq!(stage("[youtube] abc: Downloading"), None);
+        assert_eq!(stage("no prefix"), None);
+    }
+
+    fn output(windows: bool) -> OutputSettings {
+        OutputSettings {
+            windows_safe_names: Some(windows),
+            ..OutputSettings::default()
+        }
+    }
+
+    #[test]
+    fn strips_separators_and_falls_back_to_untitled() {
+        let dir = Path::new("/music");
+        assert_eq!(output_file_stem("AC/DC: Back", dir, Some("mp3"), &output(false)), "ACDC Back");
+        assert_eq!(output_file_stem("///", dir, Some("mp3"), &output(false)), "untitled");
+    }
+
+    #[test]
+    fn applies_windows_rules() {
+        let dir = Path::new("C:/music");
+        assert_eq!(output_file_stem("Song...", dir, Some("mp3"), &output(true)), "Song");
+        assert_eq!(output_file_stem("con", dir, Some("mp3"), &output(true)), "_con");
+        assert_eq!(output_file_stem("con", dir, Some("mp3"), &output(false)), "con");
+    }
+
+    #[test]
+    fn applies_filename_options_and_length_limits() {
+        let settings = OutputSettings {
+            ascii_filenames: true,
+            lowercase_filenames: true,
+            space_replacement: Some("_".to_string()),
+            max_filename_length: Some(8),
+            ..output(false)
+        };
+        assert_eq!(output_file_stem("Café Del Mar", Path::new("/"), None, &settings), "cafe_del");
+        let long = "é".repeat(200);
+        let stem = output_file_stem(&long, Path::new("/"), Some("flac"), &output(false));
+        assert!(stem.len() <= MAX_NAME_BYTES - 5 - CONFLICT_SUFFIX_LEN);
+        assert!(stem.chars().all(|ch| ch == 'é'));
+    }
 }
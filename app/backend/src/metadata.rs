@@ -0,0 +1,284 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::settings::{ArtistJoinSettings, GenreSettings, TitleCleanupSettings};
+
+static FEATURING_SEPARATOR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\s+(?:feat\.?|ft\.?|featuring)\s+").expect("valid featuring separator regex")
+});
+static ARTIST_LIST: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\s*(?:,|;|\s&\s|\sx\s)\s*").expect("valid artist list regex")
+});
+static FEATURING_IN_TITLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)[\(\[]\s*(?:feat\.?|ft\.?|featuring)\s+([^\)\]]+)[\)\]]")
+        .expect("valid featuring regex")
+});
+static BRACKETED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\s*[\(\[\{【]([^\)\]\}】]*)[\)\]\}】]").expect("valid bracket regex")
+});
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TitleRule {
+    Remove { pattern: String },
+    Replace { pattern: String, replacement: String },
+    StripBracketed { keywords: Vec<String> },
+    StripEmoji,
+    Trim,
+}
+
+pub struct ArtistTitle {
+    pub artist: String,
+    pub title: String,
+    pub confidence: f32,
+}
+
+const ARTIST_TITLE_SEPARATORS: [&str; 4] = [" - ", " – ", " — ", " -- "];
+
+pub fn split_artist_title(video_title: &str, uploader: &str) -> Option<ArtistTitle> {
+    let uploader = uploader.trim();
+    let topic_artist = uploader.strip_suffix(" - Topic").map(str::trim);
+
+    let found = ARTIST_TITLE_SEPARATORS
+        .iter()
+        .filter_map(|separator| video_title.find(separator).map(|index| (index, *separator)))
+        .min_by_key(|(index, _)| *index);
+    let Some((index, separator)) = found else {
+        return topic_artist.map(|artist| ArtistTitle {
+            artist: artist.to_string(),
+            title: video_title.trim().to_string(),
+            confidence: 0.9,
+        });
+    };
+
+    let artist = video_title[..index].trim();
+    let title = video_title[index + separator.len()..].trim();
+    if artist.is_empty() || title.is_empty() || artist.chars().count() > 60 {
+        return None;
+    }
+
+    let mut confidence: f32 = 0.6;
+    let separator_count = ARTIST_TITLE_SEPARATORS
+        .iter()
+        .map(|separator| video_title.matches(separator).count())
+        .sum::<usize>();
+    if separator_count > 1 {
+        confidence -= 0.3;
+    }
+    let artist_lower = artist.to_lowercase();
+    let uploader_lower = topic_artist.unwrap_or(uploader).to_lowercase();
+    if !uploader_lower.is_empty()
+        && (uploader_lower.contains(&artist_lower) || artist_lower.contains(&uploader_lower))
+    {
+        confidence += 0.3;
+    }
+    if uploader_lower.ends_with("vevo") {
+        confidence += 0.2;
+    }
+
+    Some(ArtistTitle {
+        artist: artist.to_string(),
+        title: title.to_string(),
+        confidence: confidence.clamp(0.0, 1.0),
+    })
+}
+
+pub fn parse_artists(artist: &str) -> Vec<String> {
+    let mut parts = FEATURING_SEPARATOR.splitn(artist, 2);
+    let primary = parts.next().unwrap_or_default();
+    let mut candidates: Vec<&str> = primary.split(';').collect();
+    if let Some(featured) = parts.next() {
+        candidates.extend(split_artist_list(featured));
+    }
+    dedupe_artists(candidates)
+}
+
+fn split_artist_list(text: &str) -> Vec<&str> {
+    ARTIST_LIST.split(text).collect()
+}
+
+fn dedupe_artists<'a>(candidates: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut artists: Vec<String> = Vec::new();
+    for part in candidates {
+        let part = part.trim();
+        if !part.is_empty() && !artists.iter().any(|existing| existing.eq_ignore_ascii_case(part)) {
+            artists.push(part.to_string());
+        }
+    }
+    artists
+}
+
+pub fn parse_featured_from_title(title: &str) -> Vec<String> {
+    let featured: Vec<String> = FEATURING_IN_TITLE
+        .captures_iter(title)
+        .map(|caps| caps[1].to_string())
+        .collect();
+    dedupe_artists(featured.iter().flat_map(|text| split_artist_list(text)))
+}
+
+pub fn join_artists(settings: &ArtistJoinSettings, artists: &[String]) -> String {
+    match artists {
+        [] => String::new(),
+        [primary] => primary.clone(),
+        [primary, featured @ ..] => format!(
+            "{primary}{}{}",
+            settings.featuring_separator,
+            featured.join(&settings.separator)
+        ),
+    }
+}
+
+pub fn derive_genre(
+    settings: &GenreSettings,
+    categories: &[String],
+    tags: &[String],
+) -> Option<String> {
+    if !settings.enabled {
+        return None;
+    }
+
+    let mapped = tags.iter().chain(categories).find_map(|value| {
+        let normalized = value.trim().to_lowercase();
+        settings
+            .keywords
+            .iter()
+            .find(|(keyword, _)| keyword.to_lowercase() == normalized)
+            .map(|(_, genre)| genre.clone())
+    });
+    if mapped.is_some() {
+        return mapped;
+    }
+
+    if settings.use_category_fallback {
+        return categories
+            .iter()
+            .map(|category| category.trim())
+            .find(|category| !category.is_empty() && !category.eq_ignore_ascii_case("music"))
+            .map(|category| category.to_string());
+    }
+    None
+}
+
+pub fn format_yt_dlp_date(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    if raw.len() != 8 || !raw.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!("{}-{}-{}", &raw[..4], &raw[4..6], &raw[6..]))
+}
+
+pub fn normalize_tag_date(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    let parts: Vec<&str> = raw.split('-').collect();
+    let valid = match parts.as_slice() {
+        [year] => year.len() == 4,
+        [year, month] => year.len() == 4 && month.len() == 2,
+        [year, month, day] => year.len() == 4 && month.len() == 2 && day.len() == 2,
+        _ => false,
+    };
+    if valid && parts.iter().all(|part| part.chars().all(|c| c.is_ascii_digit())) {
+        Some(raw.to_string())
+    } else {
+        format_yt_dlp_date(raw)
+    }
+}
+
+pub fn date_year(date: &str) -> Option<u32> {
+    date.get(..4)?.parse().ok()
+}
+
+pub fn validate_title_rules(rules: &[TitleRule]) -> Result<(), String> {
+    for rule in rules {
+        if let TitleRule::Remove { pattern } | TitleRule::Replace { pattern, .. } = rule {
+            Regex::new(pattern).map_err(|err| format!("invalid title rule pattern: {err}"))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn clean_title(settings: &TitleCleanupSettings, title: &str) -> String {
+    if !settings.enabled {
+        return title.to_string();
+    }
+
+    let mut current = title.to_string();
+    for rule in &settings.rules {
+        current = apply_title_rule(rule, &current);
+    }
+
+    if current.trim().is_empty() {
+        title.to_string()
+    } else {
+        current
+    }
+}
+
+pub fn apply_title_rule(rule: &TitleRule, title: &str) -> String {
+    match rule {
+        TitleRule::Remove { pattern } => match Regex::new(pattern) {
+            Ok(regex) => regex.replace_all(title, "").into_owned(),
+            Err(err) => {
+                error!("skipping title rule: {err}");
+                title.to_string()
+            }
+        },
+        TitleRule::Replace {
+            pattern,
+            replacement,
+        } => match Regex::new(pattern) {
+            Ok(regex) => regex.replace_all(title, replacement.as_str()).into_owned(),
+            Err(err) => {
+                error!("skipping title rule: {err}");
+                title.to_string()
+            }
+        },
+        TitleRule::StripBracketed { keywords } => strip_bracketed(title, keywords),
+        TitleRule::StripEmoji => title.chars().filter(|c| !is_emoji(*c)).collect(),
+        TitleRule::Trim => trim_title(title),
+    }
+}
+
+fn strip_bracketed(title: &str, keywords: &[String]) -> String {
+    BRACKETED
+        .replace_all(title, |caps: &regex::Captures| {
+            let inner = caps[1].to_lowercase();
+            if keywords.is_empty() || matches_keyword(&inner, keywords) {
+                String::new()
+            } else {
+                caps[0].to_string()
+            }
+        })
+        .into_owned()
+}
+
+fn matches_keyword(inner: &str, keywords: &[String]) -> bool {
+    let words: Vec<&str> = inner
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    keywords.iter().any(|keyword| {
+        let keyword = keyword.trim().to_lowercase();
+        if keyword.contains(char::is_whitespace) {
+            inner.contains(&keyword)
+        } else {
+            words.iter().any(|word| *word == keyword)
+        }
+    })
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0xFE0F | 0x200D
+    )
+}
+
+fn trim_title(title: &str) -> String {
+    let collapsed = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    collapsed
+        .trim_matches(|c: char| c.is_whitespace() || matches!(c, '-' | '–' | '—' | '|'))
+        .to_string()
+}
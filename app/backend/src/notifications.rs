@@ -0,0 +1,18 @@
+use notify_rust::Notification;
+use tracing::warn;
+
+const APP_NAME: &str = "AudioDownloader";
+
+// Showing a toast can block on the platform notification service, so it runs off the runtime.
+pub fn notify_desktop(title: String, body: String) {
+    tokio::task::spawn_blocking(move || {
+        let shown = Notification::new()
+            .appname(APP_NAME)
+            .summary(&title)
+            .body(&body)
+            .show();
+        if let Err(err) = shown {
+            warn!("failed to show desktop notification: {err}");
+        }
+    });
+}
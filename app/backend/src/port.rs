@@ -1,16 +1,102 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::{Path, PathBuf};
 use std::{fs, str};
 
 use anyhow::{anyhow, Context, Result};
 use calamine::{open_workbook_auto, Data, Reader};
 use rust_xlsxwriter::{Workbook, XlsxError};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Clone, Debug)]
+use crate::cache::video_id_from_url;
+use crate::media::normalize_tag_key;
+
+#[derive(Clone, Debug, Default)]
 pub struct MusicRow {
     pub title: Option<String>,
     pub artist: Option<String>,
     pub youtube_url: String,
+    pub custom_tags: HashMap<String, String>,
+    pub duration: Option<u64>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub album: Option<String>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub compilation: Option<bool>,
+    pub file_path: Option<PathBuf>,
+    pub sha256: Option<String>,
+}
+
+#[derive(Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportColumn {
+    Title,
+    Artist,
+    Url,
+    Duration,
+    State,
+    Error,
+    Album,
+    Disc,
+    DiscTotal,
+    Compilation,
+    FilePath,
+    Sha256,
+    Tags,
+}
+
+pub const DEFAULT_EXPORT_COLUMNS: [ExportColumn; 4] = [
+    ExportColumn::Title,
+    ExportColumn::Artist,
+    ExportColumn::Url,
+    ExportColumn::Tags,
+];
+
+pub struct PlaylistEntry {
+    pub title: String,
+    pub artist: String,
+    pub duration: Option<u64>,
+    pub path: PathBuf,
+}
+
+pub struct PodcastEntry {
+    pub id: String,
+    pub title: String,
+    pub artist: String,
+    pub url: String,
+    pub length: u64,
+    pub mime: String,
+    pub published: String,
+}
+
+#[derive(Serialize)]
+pub struct ImportPreview {
+    pub has_header: bool,
+    pub headers: Vec<String>,
+    pub mapping: ColumnMapping,
+    pub warnings: Vec<String>,
+    pub rows: Vec<PreviewRow>,
+}
+
+#[derive(Serialize)]
+pub struct ColumnMapping {
+    pub title: usize,
+    pub artist: usize,
+    pub url: usize,
+    pub custom_tags: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize)]
+pub struct PreviewRow {
+    pub line: usize,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub youtube_url: Option<String>,
+    pub custom_tags: HashMap<String, String>,
+    pub problems: Vec<String>,
+    // Worth a second look but still imported, e.g. links yt-dlp may handle outside YouTube.
+    pub warnings: Vec<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -259,151 +345,371 @@ fn read_package_version_from_str(content: &str) -> Option<String> {
 
 
 pub fn import_music_list(path: &Path) -> Result<Vec<MusicRow>> {
-    match path.extension().and_then(|ext| ext.to_str()).unwrap_or("") {
-        "csv" => import_csv(path),
-        "xlsx" => import_xlsx(path),
-        other => Err(anyhow!("unsupported import format: {other}")),
+    let table = read_table(path)?;
+    let (header_map, body) = split_header(&table);
+    Ok(body
+        .iter()
+        .filter_map(|values| row_from_strings(values, &header_map))
+        .collect())
+}
+
+pub fn preview_music_list(path: &Path) -> Result<ImportPreview> {
+    let table = read_table(path)?;
+    let (header_map, body) = split_header(&table);
+    let headers = if header_map.has_header {
+        table[0].clone()
+    } else {
+        Vec::new()
+    };
+
+    let mut warnings = Vec::new();
+    if header_map.has_header && !headers.iter().any(|value| value.to_lowercase().contains("url")) {
+        warnings.push(format!(
+            "no URL column detected; using column {}",
+            header_map.url + 1
+        ));
     }
+
+    let first_line = if header_map.has_header { 2 } else { 1 };
+    let mut seen = HashMap::new();
+    let mut rows = Vec::new();
+    for (offset, values) in body.iter().enumerate() {
+        if values.iter().all(|value| value.trim().is_empty()) {
+            continue;
+        }
+        let line = first_line + offset;
+        let mut problems = Vec::new();
+        let mut row_warnings = Vec::new();
+        let row = row_from_strings(values, &header_map);
+        match row.as_ref() {
+            None => problems.push("missing YouTube URL".to_string()),
+            Some(row) => {
+                if video_id_from_url(&row.youtube_url).is_none() {
+                    row_warnings.push("URL is not a recognised YouTube video link".to_string());
+                }
+                if let Some(previous) = seen.insert(row.youtube_url.clone(), line) {
+                    problems.push(format!("duplicate of line {previous}"));
+                }
+            }
+        }
+        rows.push(PreviewRow {
+            line,
+            title: row.as_ref().and_then(|row| row.title.clone()),
+            artist: row.as_ref().and_then(|row| row.artist.clone()),
+            youtube_url: row.as_ref().map(|row| row.youtube_url.clone()),
+            custom_tags: row.map(|row| row.custom_tags).unwrap_or_default(),
+            problems,
+            warnings: row_warnings,
+        });
+    }
+
+    Ok(ImportPreview {
+        has_header: header_map.has_header,
+        headers,
+        mapping: ColumnMapping {
+            title: header_map.title,
+            artist: header_map.artist,
+            url: header_map.url,
+            custom_tags: header_map
+                .custom_tags
+                .iter()
+                .map(|(index, key)| (key.clone(), *index))
+                .collect(),
+        },
+        warnings,
+        rows,
+    })
+}
+
+pub fn google_sheet_csv_url(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    if url.host_str()? != "docs.google.com" {
+        return None;
+    }
+    let mut segments = url.path_segments()?;
+    if segments.next()? != "spreadsheets" || segments.next()? != "d" {
+        return None;
+    }
+    let id = segments.next().filter(|id| !id.is_empty())?;
+    let gid = url
+        .query_pairs()
+        .find(|(key, _)| key == "gid")
+        .map(|(_, value)| value.into_owned())
+        .or_else(|| {
+            url.fragment()?
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("gid="))
+                .map(|gid| gid.to_string())
+        });
+    let mut export = format!("https://docs.google.com/spreadsheets/d/{id}/export?format=csv");
+    if let Some(gid) = gid.filter(|gid| gid.chars().all(|ch| ch.is_ascii_digit())) {
+        export.push_str(&format!("&gid={gid}"));
+    }
+    Some(export)
 }
 
-pub fn export_music_list(path: &Path, rows: &[MusicRow]) -> Result<()> {
+pub fn export_music_list(path: &Path, rows: &[MusicRow], columns: &[ExportColumn]) -> Result<()> {
     match path.extension().and_then(|ext| ext.to_str()).unwrap_or("") {
-        "csv" => export_csv(path, rows),
-        "xlsx" => export_xlsx(path, rows),
+        "csv" => export_csv(path, rows, columns),
+        "xlsx" => export_xlsx(path, rows, columns),
         other => Err(anyhow!("unsupported export format: {other}")),
     }
 }
 
+pub fn export_m3u8(path: &Path, entries: &[PlaylistEntry], base: Option<&Path>) -> Result<()> {
+    let mut content = String::from("#EXTM3U\n");
+    for entry in entries {
+        let duration = entry.duration.map_or(-1, |duration| duration as i64);
+        let label = match entry.artist.is_empty() {
+            true => entry.title.clone(),
+            false => format!("{} - {}", entry.artist, entry.title),
+        };
+        let location = base
+            .and_then(|base| entry.path.strip_prefix(base).ok())
+            .unwrap_or(&entry.path);
+        content.push_str(&format!("#EXTINF:{duration},{label}\n{}\n", location.display()));
+    }
+    fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub fn export_xspf(path: &Path, entries: &[PlaylistEntry], base: Option<&Path>) -> Result<()> {
+    let mut content = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  <trackList>\n",
+    );
+    for entry in entries {
+        content.push_str("    <track>\n");
+        let location = match base.and_then(|base| entry.path.strip_prefix(base).ok()) {
+            Some(relative) => encode_uri_path(relative),
+            None => reqwest::Url::from_file_path(&entry.path)
+                .map(|url| url.to_string())
+                .unwrap_or_else(|_| encode_uri_path(&entry.path)),
+        };
+        content.push_str(&format!("      <location>{}</location>\n", escape_xml(&location)));
+        content.push_str(&format!("      <title>{}</title>\n", escape_xml(&entry.title)));
+        if !entry.artist.is_empty() {
+            content.push_str(&format!("      <creator>{}</creator>\n", escape_xml(&entry.artist)));
+        }
+        if let Some(duration) = entry.duration {
+            content.push_str(&format!("      <duration>{}</duration>\n", duration * 1000));
+        }
+        content.push_str("    </track>\n");
+    }
+    content.push_str("  </trackList>\n</playlist>\n");
+    fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub fn podcast_feed(title: &str, link: &str, entries: &[PodcastEntry]) -> String {
+    let mut content = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\" xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\">\n\
+         <channel>\n  <title>{}</title>\n  <link>{}</link>\n  <description>{}</description>\n",
+        escape_xml(title),
+        escape_xml(link),
+        escape_xml(title),
+    );
+    for entry in entries {
+        content.push_str("  <item>\n");
+        content.push_str(&format!("    <title>{}</title>\n", escape_xml(&entry.title)));
+        content.push_str(&format!(
+            "    <itunes:author>{}</itunes:author>\n",
+            escape_xml(&entry.artist)
+        ));
+        content.push_str(&format!(
+            "    <guid isPermaLink=\"false\">{}</guid>\n",
+            escape_xml(&entry.id)
+        ));
+        content.push_str(&format!(
+            "    <enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>\n",
+            escape_xml(&entry.url),
+            entry.length,
+            escape_xml(&entry.mime)
+        ));
+        content.push_str(&format!("    <pubDate>{}</pubDate>\n", entry.published));
+        content.push_str("  </item>\n");
+    }
+    content.push_str("</channel>\n</rss>\n");
+    content
+}
+
 pub fn create_sample_xlsx(dir: &Path) -> Result<PathBuf> {
     let file_path = dir.join(format!("Sample-{}.xlsx", Uuid::new_v4()));
     let rows = vec![MusicRow {
         title: Some("Example Title".to_string()),
         artist: Some("Example Artist".to_string()),
         youtube_url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+        ..MusicRow::default()
     }];
-    export_xlsx(&file_path, &rows)?;
+    export_xlsx(&file_path, &rows, &DEFAULT_EXPORT_COLUMNS)?;
     Ok(file_path)
 }
 
-fn import_csv(path: &Path) -> Result<Vec<MusicRow>> {
+fn read_table(path: &Path) -> Result<Vec<Vec<String>>> {
+    match path.extension().and_then(|ext| ext.to_str()).unwrap_or("") {
+        "csv" => read_csv_table(path),
+        "xlsx" | "ods" => read_workbook_table(path),
+        other => Err(anyhow!("unsupported import format: {other}")),
+    }
+}
+
+fn read_csv_table(path: &Path) -> Result<Vec<Vec<String>>> {
     let mut reader = csv::ReaderBuilder::new()
         .has_headers(false)
         .flexible(true)
         .from_path(path)
         .with_context(|| format!("failed to open csv: {}", path.display()))?;
 
-    let mut records = reader.records();
-    let Some(first) = records.next().transpose()? else {
-        return Ok(Vec::new());
-    };
-
-    let (header_map, mut rows) = if looks_like_header(&first) {
-        (HeaderMap::from_header(&first), Vec::new())
-    } else {
-        (HeaderMap::default(), Vec::new())
-    };
-
-    if !header_map.has_header {
-        if let Some(row) = row_from_record(&first, &header_map) {
-            rows.push(row);
-        }
-    }
-
-    for record in records {
+    let mut table = Vec::new();
+    for record in reader.records() {
         let record = record?;
-        if let Some(row) = row_from_record(&record, &header_map) {
-            rows.push(row);
-        }
+        table.push(record.iter().map(|value| value.to_string()).collect());
     }
-
-    Ok(rows)
+    Ok(table)
 }
 
-fn import_xlsx(path: &Path) -> Result<Vec<MusicRow>> {
+fn read_workbook_table(path: &Path) -> Result<Vec<Vec<String>>> {
     let mut workbook = open_workbook_auto(path)
-        .with_context(|| format!("failed to open xlsx: {}", path.display()))?;
+        .with_context(|| format!("failed to open workbook: {}", path.display()))?;
     let sheet_name = workbook
         .sheet_names()
         .get(0)
         .cloned()
-        .ok_or_else(|| anyhow!("xlsx contains no sheets"))?;
+        .ok_or_else(|| anyhow!("workbook contains no sheets"))?;
 
     let range = workbook
         .worksheet_range(&sheet_name)
         .with_context(|| format!("failed to read sheet: {sheet_name}"))?;
 
-    let mut rows_iter = range.rows();
-    let Some(first) = rows_iter.next() else {
-        return Ok(Vec::new());
-    };
-
-    let first_strings: Vec<String> = first.iter().map(cell_to_string).collect();
-    let (header_map, mut rows) = if looks_like_header_strings(&first_strings) {
-        (HeaderMap::from_strings(&first_strings), Vec::new())
-    } else {
-        (HeaderMap::default(), Vec::new())
-    };
-
-    if !header_map.has_header {
-        if let Some(row) = row_from_cells(first, &header_map) {
-            rows.push(row);
-        }
-    }
+    Ok(range
+        .rows()
+        .map(|row| row.iter().map(cell_to_string).collect())
+        .collect())
+}
 
-    for row in rows_iter {
-        if let Some(row) = row_from_cells(row, &header_map) {
-            rows.push(row);
+fn split_header(table: &[Vec<String>]) -> (HeaderMap, &[Vec<String>]) {
+    match table.first() {
+        Some(first) if looks_like_header_strings(first) => {
+            (HeaderMap::from_strings(first), &table[1..])
         }
+        _ => (HeaderMap::default(), table),
     }
-
-    Ok(rows)
 }
 
-fn export_csv(path: &Path, rows: &[MusicRow]) -> Result<()> {
+fn export_csv(path: &Path, rows: &[MusicRow], columns: &[ExportColumn]) -> Result<()> {
     let mut writer = csv::WriterBuilder::new()
         .from_path(path)
         .with_context(|| format!("failed to create csv: {}", path.display()))?;
 
-    writer.write_record(["Title", "Artist", "YouTube URL"])?;
+    let tag_keys = collect_custom_tag_keys(rows);
+    writer.write_record(export_header(columns, &tag_keys))?;
     for row in rows {
-        writer.write_record([
-            row.title.clone().unwrap_or_default(),
-            row.artist.clone().unwrap_or_default(),
-            row.youtube_url.clone(),
-        ])?;
+        writer.write_record(export_record(row, columns, &tag_keys))?;
     }
     writer.flush()?;
     Ok(())
 }
 
-fn export_xlsx(path: &Path, rows: &[MusicRow]) -> Result<()> {
+fn export_xlsx(path: &Path, rows: &[MusicRow], columns: &[ExportColumn]) -> Result<()> {
     let mut workbook = Workbook::new();
     let worksheet = workbook.add_worksheet();
 
-    worksheet.write_string(0, 0, "Title")?;
-    worksheet.write_string(0, 1, "Artist")?;
-    worksheet.write_string(0, 2, "YouTube URL")?;
+    let tag_keys = collect_custom_tag_keys(rows);
+    for (col, value) in export_header(columns, &tag_keys).iter().enumerate() {
+        worksheet.write_string(0, col as u16, value)?;
+    }
 
     for (index, row) in rows.iter().enumerate() {
         let row_index = (index + 1) as u32;
-        worksheet.write_string(row_index, 0, row.title.as_deref().unwrap_or(""))?;
-        worksheet.write_string(row_index, 1, row.artist.as_deref().unwrap_or(""))?;
-        worksheet.write_string(row_index, 2, &row.youtube_url)?;
+        for (col, value) in export_record(row, columns, &tag_keys).iter().enumerate() {
+            if !value.is_empty() {
+                worksheet.write_string(row_index, col as u16, value)?;
+            }
+        }
     }
 
     workbook.save(path).map_err(map_xlsx_error)?;
     Ok(())
 }
 
+fn export_header(columns: &[ExportColumn], tag_keys: &[String]) -> Vec<String> {
+    let mut header = Vec::new();
+    for column in columns {
+        let label = match column {
+            ExportColumn::Title => "Title",
+            ExportColumn::Artist => "Artist",
+            ExportColumn::Url => "YouTube URL",
+            ExportColumn::Duration => "Duration",
+            ExportColumn::State => "State",
+            ExportColumn::Error => "Error",
+            ExportColumn::Album => "Album",
+            ExportColumn::Disc => "Disc",
+            ExportColumn::DiscTotal => "Total Discs",
+            ExportColumn::Compilation => "Compilation",
+            ExportColumn::FilePath => "File Path",
+            ExportColumn::Sha256 => "SHA-256",
+            ExportColumn::Tags => {
+                header.extend(tag_keys.iter().map(|key| format!("{CUSTOM_TAG_PREFIX}{key}")));
+                continue;
+            }
+        };
+        header.push(label.to_string());
+    }
+    header
+}
+
+fn export_record(row: &MusicRow, columns: &[ExportColumn], tag_keys: &[String]) -> Vec<String> {
+    let mut record = Vec::new();
+    for column in columns {
+        let value = match column {
+            ExportColumn::Title => row.title.clone(),
+            ExportColumn::Artist => row.artist.clone(),
+            ExportColumn::Url => Some(row.youtube_url.clone()),
+            ExportColumn::Duration => row.duration.map(|duration| duration.to_string()),
+            ExportColumn::State => row.state.clone(),
+            ExportColumn::Error => row.error.clone(),
+            ExportColumn::Album => row.album.clone(),
+            ExportColumn::Disc => row.disc_number.map(|disc| disc.to_string()),
+            ExportColumn::DiscTotal => row.disc_total.map(|total| total.to_string()),
+            ExportColumn::Compilation => row.compilation.map(|flag| flag.to_string()),
+            ExportColumn::FilePath => row.file_path.as_ref().map(|path| path.display().to_string()),
+            ExportColumn::Sha256 => row.sha256.clone(),
+            ExportColumn::Tags => {
+                record.extend(
+                    tag_keys
+                        .iter()
+                        .map(|key| row.custom_tags.get(key).cloned().unwrap_or_default()),
+                );
+                continue;
+            }
+        };
+        record.push(value.unwrap_or_default());
+    }
+    record
+}
+
+fn collect_custom_tag_keys(rows: &[MusicRow]) -> Vec<String> {
+    rows.iter()
+        .flat_map(|row| row.custom_tags.keys().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
 fn map_xlsx_error(err: XlsxError) -> anyhow::Error {
     anyhow!(err.to_string())
 }
 
-#[derive(Clone, Copy)]
+const CUSTOM_TAG_PREFIX: &str = "tag:";
+
+#[derive(Clone)]
 struct HeaderMap {
     title: usize,
     artist: usize,
     url: usize,
+    disc: Option<usize>,
+    disc_total: Option<usize>,
+    compilation: Option<usize>,
+    custom_tags: Vec<(usize, String)>,
     has_header: bool,
 }
 
@@ -413,37 +719,43 @@ impl HeaderMap {
             title: 0,
             artist: 1,
             url: 2,
+            disc: None,
+            disc_total: None,
+            compilation: None,
+            custom_tags: Vec::new(),
             has_header: false,
         }
     }
 
-    fn from_header(record: &csv::StringRecord) -> Self {
-        let strings: Vec<String> = record.iter().map(|value| value.to_string()).collect();
-        Self::from_strings(&strings)
-    }
-
     fn from_strings(values: &[String]) -> Self {
         let mut map = Self::default();
         map.has_header = true;
         for (idx, value) in values.iter().enumerate() {
             let normalized = value.to_lowercase();
-            if normalized.contains("title") {
+            if let Some(key) = normalized.strip_prefix(CUSTOM_TAG_PREFIX) {
+                if let Some(key) = normalize_tag_key(key) {
+                    map.custom_tags.push((idx, key));
+                }
+            } else if normalized.contains("title") {
                 map.title = idx;
             } else if normalized.contains("artist") {
                 map.artist = idx;
             } else if normalized.contains("url") {
                 map.url = idx;
+            } else if normalized.contains("disc") && normalized.contains("total")
+                || normalized == "discs"
+            {
+                map.disc_total = Some(idx);
+            } else if normalized.contains("disc") {
+                map.disc = Some(idx);
+            } else if normalized.contains("compilation") {
+                map.compilation = Some(idx);
             }
         }
         map
     }
 }
 
-fn looks_like_header(record: &csv::StringRecord) -> bool {
-    let strings: Vec<String> = record.iter().map(|value| value.to_string()).collect();
-    looks_like_header_strings(&strings)
-}
-
 fn looks_like_header_strings(values: &[String]) -> bool {
     values.iter().any(|value| {
         let normalized = value.to_lowercase();
@@ -451,32 +763,77 @@ fn looks_like_header_strings(values: &[String]) -> bool {
     })
 }
 
-fn row_from_record(record: &csv::StringRecord, map: &HeaderMap) -> Option<MusicRow> {
-    let url = record.get(map.url)?.trim().to_string();
+fn row_from_strings(values: &[String], map: &HeaderMap) -> Option<MusicRow> {
+    let url = values.get(map.url)?.trim().to_string();
     if url.is_empty() {
         return None;
     }
-    let title = record.get(map.title).map(|value| value.trim().to_string());
-    let artist = record.get(map.artist).map(|value| value.trim().to_string());
+    let title = values.get(map.title).map(|value| value.trim().to_string());
+    let artist = values.get(map.artist).map(|value| value.trim().to_string());
+    let custom_tags = map
+        .custom_tags
+        .iter()
+        .filter_map(|(idx, key)| {
+            let value = values.get(*idx)?.trim();
+            (!value.is_empty()).then(|| (key.clone(), value.to_string()))
+        })
+        .collect();
+    let cell = |idx: Option<usize>| idx.and_then(|idx| values.get(idx)).map(|value| value.trim());
+    // "2/3" fills both disc fields, as in DISCNUMBER tags.
+    let (disc_number, disc_in_cell_total) = match cell(map.disc) {
+        Some(value) => match value.split_once('/') {
+            Some((disc, total)) => (disc.trim().parse().ok(), total.trim().parse().ok()),
+            None => (value.parse().ok(), None),
+        },
+        None => (None, None),
+    };
+    let compilation = cell(map.compilation).and_then(|value| {
+        match value.to_lowercase().as_str() {
+            "1" | "true" | "yes" | "y" | "x" => Some(true),
+            "0" | "false" | "no" | "n" => Some(false),
+            _ => None,
+        }
+    });
     Some(MusicRow {
         title: title.filter(|value| !value.is_empty()),
         artist: artist.filter(|value| !value.is_empty()),
         youtube_url: url,
+        custom_tags,
+        disc_number: disc_number.filter(|disc| *disc > 0),
+        disc_total: cell(map.disc_total)
+            .and_then(|value| value.parse().ok())
+            .or(disc_in_cell_total)
+            .filter(|total| *total > 0),
+        compilation,
+        ..MusicRow::default()
     })
 }
 
-fn row_from_cells(cells: &[Data], map: &HeaderMap) -> Option<MusicRow> {
-    let url = cells.get(map.url).map(cell_to_string)?.trim().to_string();
-    if url.is_empty() {
-        return None;
-    }
-    let title = cells.get(map.title).map(cell_to_string);
-    let artist = cells.get(map.artist).map(cell_to_string);
-    Some(MusicRow {
-        title: title.filter(|value| !value.trim().is_empty()),
-        artist: artist.filter(|value| !value.trim().is_empty()),
-        youtube_url: url,
-    })
+fn encode_uri_path(path: &Path) -> String {
+    path.components()
+        .map(|component| {
+            let mut encoded = String::new();
+            for byte in component.as_os_str().to_string_lossy().bytes() {
+                match byte {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                        encoded.push(byte as char)
+                    }
+                    _ => encoded.push_str(&format!("%{byte:02X}")),
+                }
+            }
+            encoded
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
 }
 
 fn cell_to_string(cell: &Data) -> String {
@@ -492,3 +849,44 @@ fn cell_to_string(cell: &Data) -> String {
         Data::Empty => String::new(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn row(header: &[&str], values: &[&str]) -> MusicRow {
+        let map = HeaderMap::from_strings(&strings(header));
+        row_from_strings(&strings(values), &map).unwrap()
+    }
+
+    #[test]
+    fn splits_disc_number_and_total() {
+        let header = ["Title", "Artist", "URL", "Disc"];
+        let parsed = row(&header, &["a", "b", "https://youtu.be/x", "2/3"]);
+        assert_eq!((parsed.disc_number, parsed.disc_total), (Some(2), Some(3)));
+        let parsed = row(&header, &["a", "b", "https://youtu.be/x", "0"]);
+        assert_eq!((parsed.disc_number, parsed.disc_total), (None, None));
+    }
+
+    #[test]
+    fn prefers_a_disc_total_column() {
+        let header = ["Title", "Artist", "URL", "Disc", "Disc Total"];
+        let parsed = row(&header, &["a", "b", "https://youtu.be/x", "1/4", "2"]);
+        assert_eq!((parsed.disc_number, parsed.disc_total), (Some(1), Some(2)));
+    }
+
+    #[test]
+    fn parses_compilation_flags() {
+        let header = ["Title", "Artist", "URL", "Compilation"];
+        let flag = |value| row(&header, &["a", "b", "https://youtu.be/x", value]).compilation;
+        assert_eq!(flag("Yes"), Some(true));
+        assert_eq!(flag("x"), Some(true));
+        assert_eq!(flag("0"), Some(false));
+        assert_eq!(flag("maybe"), None);
+        assert_eq!(flag(""), None);
+    }
+}
@@ -0,0 +1,403 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use lofty::mp4::{Mp4Codec, Mp4File};
+use lofty::{AudioFile, FileType, ParseOptions, TaggedFileExt};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::process::Command;
+
+use crate::settings::{AppSettings, ChannelLayout, ResampleSettings, SilenceTrimSettings};
+use crate::types::{AppState, QueueItem};
+
+const SAMPLE_RATES: [u32; 6] = [22_050, 32_000, 44_100, 48_000, 88_200, 96_000];
+const DURATION_TOLERANCE_SECS: f64 = 3.0;
+const DURATION_TOLERANCE_RATIO: f64 = 0.02;
+
+#[derive(Default)]
+pub struct PostProcessOutcome {
+    pub trimmed_seconds: Option<f32>,
+    // The part of the trim taken from the start, which is what shifts chapter offsets.
+    pub leading_trimmed_seconds: Option<f32>,
+}
+
+#[derive(Clone, Copy)]
+pub struct ProgressTarget<'a> {
+    pub state: &'a AppState,
+    pub queue_id: &'a str,
+    pub id: &'a str,
+}
+
+// `quality` is the level the download asked for; filtering re-encodes, so it is applied again.
+pub async fn post_process_audio(
+    path: &Path,
+    settings: &AppSettings,
+    item: &QueueItem,
+    quality: Option<u32>,
+    progress: ProgressTarget<'_>,
+) -> Result<PostProcessOutcome> {
+    let mut outcome = PostProcessOutcome::default();
+
+    // One chain, so the file is only re-encoded once. The trimmed length is not known up front,
+    // so fade-outs after a trim are placed by reversing instead of by timestamp.
+    let silence = &settings.silence_trim;
+    let before = read_duration(path);
+    let mut filters = Vec::new();
+    if silence.enabled {
+        filters.extend(silence_filters(silence));
+    }
+    filters.extend(fade_filters(item, before.filter(|_| !silence.enabled)));
+    filters.extend(resample_filters(&settings.resample));
+    if filters.is_empty() {
+        return Ok(outcome);
+    }
+
+    // With both ends trimmed the single pass cannot tell them apart, so when chapters need the
+    // leading part it is measured by running just that filter into a null output first.
+    let needs_leading = settings.cue_sheet.enabled && !item.chapters.is_empty();
+    let leading_remains = match silence_filters(silence).first() {
+        Some(remove) if silence.enabled && silence.trim_end && needs_leading => {
+            filtered_duration(path, remove).await
+        }
+        _ => None,
+    };
+    run_ffmpeg_filters(path, &filters, quality, progress).await?;
+    if silence.enabled {
+        if let (Some(before), Some(after)) = (before, read_duration(path)) {
+            let trimmed = before.saturating_sub(after);
+            outcome.trimmed_seconds = Some(trimmed.as_secs_f32());
+            outcome.leading_trimmed_seconds = match silence.trim_end {
+                false => Some(trimmed.as_secs_f32()),
+                true => leading_remains.map(|remains| before.saturating_sub(remains).as_secs_f32()),
+            };
+        }
+    }
+    Ok(outcome)
+}
+
+// Truncated downloads still decode, so compare against what the source reported instead.
+pub fn verify_duration(path: &Path, item: &QueueItem, trimmed: Option<f32>) -> Option<String> {
+    if item.is_live {
+        return None;
+    }
+    let source = item.duration? as f64;
+    let start = item.start.unwrap_or(0.0).clamp(0.0, source);
+    let end = item.end.unwrap_or(source).clamp(start, source);
+    let expected = end - start - f64::from(trimmed.unwrap_or(0.0));
+    if expected <= 0.0 {
+        return None;
+    }
+    let Some(actual) = read_duration(path) else {
+        return Some("could not read the duration of the downloaded file".to_string());
+    };
+    let actual = actual.as_secs_f64();
+    let tolerance = DURATION_TOLERANCE_SECS.max(expected * DURATION_TOLERANCE_RATIO);
+    ((actual - expected).abs() > tolerance).then(|| {
+        format!("file is {actual:.0}s long but {expected:.0}s was expected; it may be truncated")
+    })
+}
+
+pub fn validate_resample(settings: &ResampleSettings) -> Result<(), String> {
+    match settings.sample_rate {
+        Some(rate) if !SAMPLE_RATES.contains(&rate) => Err(format!(
+            "unsupported sample rate {rate}; expected one of {SAMPLE_RATES:?}"
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn resample_filters(settings: &ResampleSettings) -> Vec<String> {
+    let mut filters = Vec::new();
+    if let Some(rate) = settings.sample_rate {
+        filters.push(format!("aresample={rate}"));
+    }
+    if let Some(channels) = settings.channels {
+        let layout = match channels {
+            ChannelLayout::Mono => "mono",
+            ChannelLayout::Stereo => "stereo",
+        };
+        filters.push(format!("aformat=channel_layouts={layout}"));
+    }
+    filters
+}
+
+fn fade_filters(item: &QueueItem, duration: Option<Duration>) -> Vec<String> {
+    let mut filters = Vec::new();
+    if let Some(fade_in) = item.fade_in.filter(|value| *value > 0.0) {
+        filters.push(format!("afade=t=in:st=0:d={fade_in}"));
+    }
+    if let Some(fade_out) = item.fade_out.filter(|value| *value > 0.0) {
+        match duration {
+            Some(duration) => {
+                let start = (duration.as_secs_f32() - fade_out).max(0.0);
+                filters.push(format!("afade=t=out:st={start}:d={fade_out}"));
+            }
+            None => filters.extend([
+                "areverse".to_string(),
+                format!("afade=t=in:st=0:d={fade_out}"),
+                "areverse".to_string(),
+            ]),
+        }
+    }
+    filters
+}
+
+pub async fn convert_audio(
+    path: &Path,
+    extension: &str,
+    progress: ProgressTarget<'_>,
+) -> Result<PathBuf> {
+    let output_path = path.with_extension(extension);
+    transcode_audio(path, &output_path, None, progress).await?;
+    Ok(output_path)
+}
+
+pub async fn transcode_audio(
+    input: &Path,
+    output_path: &Path,
+    codec: Option<&str>,
+    progress: ProgressTarget<'_>,
+) -> Result<()> {
+    let mut cmd = ffmpeg_command();
+    cmd.arg("-i")
+        .arg(input)
+        .arg("-map_metadata")
+        .arg("0")
+        .arg("-vn");
+    if let Some(codec) = codec {
+        cmd.arg("-c:a").arg(codec);
+    }
+    cmd.arg(output_path);
+    let stderr = run_ffmpeg(cmd, read_duration(input), progress).await;
+
+    if let Err(err) = stderr {
+        let _ = tokio::fs::remove_file(output_path).await;
+        return Err(anyhow!("ffmpeg conversion failed: {err}"));
+    }
+    tokio::fs::remove_file(input)
+        .await
+        .with_context(|| format!("failed to remove {}", input.display()))?;
+    Ok(())
+}
+
+fn silence_filters(settings: &SilenceTrimSettings) -> Vec<String> {
+    let remove = format!(
+        "silenceremove=start_periods=1:start_threshold={}dB:start_silence={}",
+        settings.threshold_db, settings.keep_seconds
+    );
+    let mut filters = vec![remove.clone()];
+    if settings.trim_end {
+        filters.extend(["areverse".to_string(), remove, "areverse".to_string()]);
+    }
+    filters
+}
+
+// Decodes through `filter` without writing anything and returns the resulting length.
+async fn filtered_duration(path: &Path, filter: &str) -> Option<Duration> {
+    let output = ffmpeg_command()
+        .arg("-i")
+        .arg(path)
+        .arg("-vn")
+        .arg("-af")
+        .arg(filter)
+        .arg("-f")
+        .arg("null")
+        .arg("-")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .rev()
+        .filter_map(|line| line.strip_prefix("out_time_us="))
+        .find_map(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_micros)
+}
+
+fn read_duration(path: &Path) -> Option<Duration> {
+    let tagged = lofty::read_from_path(path).ok()?;
+    Some(tagged.properties().duration())
+}
+
+async fn run_ffmpeg_filters(
+    path: &Path,
+    filters: &[String],
+    quality: Option<u32>,
+    progress: ProgressTarget<'_>,
+) -> Result<()> {
+    let output_path = staging_path(path)?;
+    let mut cmd = ffmpeg_command();
+    cmd.arg("-i")
+        .arg(path)
+        .arg("-map_metadata")
+        .arg("0")
+        .arg("-vn")
+        .arg("-af")
+        .arg(filters.join(","))
+        .args(encoder_args(path, quality))
+        .arg(&output_path);
+
+    if let Err(err) = run_ffmpeg(cmd, read_duration(path), progress).await {
+        let _ = tokio::fs::remove_file(&output_path).await;
+        return Err(anyhow!("ffmpeg post-processing failed: {err}"));
+    }
+
+    tokio::fs::rename(&output_path, path)
+        .await
+        .with_context(|| format!("failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+// Without an explicit encoder ffmpeg picks the container default, which turns ALAC into AAC
+// and drops MP3 and Opus to their default bitrate.
+fn encoder_args(path: &Path, quality: Option<u32>) -> Vec<String> {
+    let Ok(tagged) = lofty::read_from_path(path) else {
+        return Vec::new();
+    };
+    let properties = tagged.properties();
+    let bitrate = quality.or(properties.audio_bitrate()).filter(|rate| *rate > 0);
+    let bit_depth = properties.bit_depth().unwrap_or(16);
+    let (codec, lossy) = match tagged.file_type() {
+        FileType::Flac => ("flac", false),
+        FileType::Mpeg => ("libmp3lame", true),
+        FileType::Opus => ("libopus", true),
+        FileType::Vorbis => ("libvorbis", true),
+        FileType::Mp4 => match read_mp4_codec(path) {
+            Some(Mp4Codec::ALAC) => ("alac", false),
+            _ => ("aac", true),
+        },
+        FileType::Wav if bit_depth > 16 => ("pcm_s24le", false),
+        FileType::Wav => ("pcm_s16le", false),
+        FileType::Aiff if bit_depth > 16 => ("pcm_s24be", false),
+        FileType::Aiff => ("pcm_s16be", false),
+        _ => return Vec::new(),
+    };
+    let mut args = vec!["-c:a".to_string(), codec.to_string()];
+    match (codec, quality, bitrate) {
+        ("flac", Some(level), _) => {
+            args.extend(["-compression_level".to_string(), level.to_string()]);
+        }
+        // Matches the `--audio-quality 0` VBR the download used.
+        ("libmp3lame", None, _) => args.extend(["-q:a".to_string(), "0".to_string()]),
+        (_, _, Some(bitrate)) if lossy => {
+            args.extend(["-b:a".to_string(), format!("{bitrate}k")]);
+        }
+        _ => {}
+    }
+    args
+}
+
+// Recordings are MPEG-TS, which lofty cannot read, so ask ffprobe for the audio codec.
+pub async fn probe_audio_codec(path: &Path) -> Option<String> {
+    let output = Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-select_streams")
+        .arg("a:0")
+        .arg("-show_entries")
+        .arg("stream=codec_name")
+        .arg("-of")
+        .arg("csv=p=0")
+        .arg(path)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .ok()?;
+    let codec = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !codec.is_empty()).then_some(codec)
+}
+
+fn read_mp4_codec(path: &Path) -> Option<Mp4Codec> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mp4 = Mp4File::read_from(&mut file, ParseOptions::new()).ok()?;
+    Some(*mp4.properties().codec())
+}
+
+fn ffmpeg_command() -> Command {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.arg("-hide_banner")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-nostats")
+        .arg("-progress")
+        .arg("pipe:1")
+        .arg("-y");
+    cmd
+}
+
+async fn run_ffmpeg(
+    mut cmd: Command,
+    duration: Option<Duration>,
+    progress: ProgressTarget<'_>,
+) -> Result<()> {
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("ffmpeg execution failed")?;
+
+    let mut stderr = child
+        .stderr
+        .take()
+        .ok_or_else(|| anyhow!("ffmpeg stderr unavailable"))?;
+    let stderr_task = tokio::spawn(async move {
+        let mut output = String::new();
+        let _ = stderr.read_to_string(&mut output).await;
+        output
+    });
+
+    if let Some(stdout) = child.stdout.take() {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let Some(value) = line.strip_prefix("out_time_us=") else {
+                continue;
+            };
+            let (Ok(micros), Some(duration)) = (value.trim().parse::<u64>(), duration) else {
+                continue;
+            };
+            if duration.is_zero() {
+                continue;
+            }
+            let percent = micros as f32 / duration.as_micros() as f32 * 100.0;
+            update_stage_progress(progress, percent).await;
+        }
+    }
+
+    let status = child.wait().await.context("ffmpeg execution failed")?;
+    let stderr = stderr_task.await.unwrap_or_default();
+    if !status.success() {
+        return Err(anyhow!("{stderr}"));
+    }
+    update_stage_progress(progress, 100.0).await;
+    Ok(())
+}
+
+async fn update_stage_progress(progress: ProgressTarget<'_>, percent: f32) {
+    let mut queue = progress.state.queue.lock().await;
+    if let Some(item) = queue
+        .iter_mut()
+        .find(|item| item.matches_key(progress.queue_id, progress.id))
+    {
+        item.stage_progress = Some(percent.clamp(0.0, 100.0));
+    }
+}
+
+fn staging_path(path: &Path) -> Result<PathBuf> {
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| anyhow!("invalid audio file name"))?;
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .ok_or_else(|| anyhow!("audio file has no extension"))?;
+    Ok(path.with_file_name(format!("{stem}.postprocess.{extension}")))
+}
@@ -0,0 +1,71 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::{
+    AppSettings, OutputSettings, ResampleSettings, SilenceTrimSettings, TaggingSettings,
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub format: String,
+    #[serde(default)]
+    pub quality: Option<u32>,
+    #[serde(default)]
+    pub output_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub limit_rate: Option<String>,
+    #[serde(default)]
+    pub number_tracks: bool,
+    // Each section set here replaces the saved one for downloads using the preset.
+    #[serde(default)]
+    pub output: Option<OutputSettings>,
+    #[serde(default)]
+    pub silence_trim: Option<SilenceTrimSettings>,
+    #[serde(default)]
+    pub resample: Option<ResampleSettings>,
+    #[serde(default)]
+    pub tagging: Option<TaggingSettings>,
+}
+
+impl Preset {
+    pub fn apply(&self, settings: &mut AppSettings) {
+        if let Some(output) = &self.output {
+            settings.output = output.clone();
+        }
+        if let Some(silence_trim) = &self.silence_trim {
+            settings.silence_trim = silence_trim.clone();
+        }
+        if let Some(resample) = &self.resample {
+            settings.resample = resample.clone();
+        }
+        if let Some(tagging) = &self.tagging {
+            settings.tagging = tagging.clone();
+        }
+    }
+}
+
+pub fn validate_preset_name(name: &str) -> Result<(), String> {
+    let valid = |ch: char| ch.is_alphanumeric() || matches!(ch, '-' | '_' | ' ');
+    if name.trim().is_empty() || name.len() > 64 || !name.chars().all(valid) {
+        return Err("preset name must be 1-64 letters, digits, spaces, '-' or '_'".to_string());
+    }
+    Ok(())
+}
+
+pub fn load_presets(path: &Path) -> Result<Vec<Preset>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+pub fn save_presets(path: &Path, presets: &[Preset]) -> Result<()> {
+    let content = serde_json::to_string_pretty(presets)?;
+    fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+}
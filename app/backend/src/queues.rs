@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::UserSettings;
+
+pub const DEFAULT_QUEUE_ID: &str = "default";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NamedQueue {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub output_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub quality: Option<u32>,
+    // The user owning this queue; shared queues have none.
+    #[serde(default)]
+    pub workspace: Option<String>,
+}
+
+pub fn default_queue_id() -> String {
+    DEFAULT_QUEUE_ID.to_string()
+}
+
+pub fn default_queue() -> NamedQueue {
+    NamedQueue {
+        id: default_queue_id(),
+        name: "Default".to_string(),
+        output_dir: None,
+        format: None,
+        quality: None,
+        workspace: None,
+    }
+}
+
+pub fn workspace_queue_id(user: &str) -> String {
+    format!("user-{user}")
+}
+
+// Every configured user gets a queue of their own, whose output directory follows the settings.
+pub fn ensure_workspace_queues(queues: &mut Vec<NamedQueue>, users: &[UserSettings]) {
+    for user in users {
+        let id = workspace_queue_id(&user.name);
+        if !queues.iter().any(|queue| queue.id == id) {
+            queues.push(NamedQueue {
+                id,
+                name: user.name.clone(),
+                output_dir: None,
+                format: None,
+                quality: None,
+                workspace: Some(user.name.clone()),
+            });
+        }
+        // Users cannot pick a directory, so every queue they own downloads to their own one.
+        let owned = queues
+            .iter_mut()
+            .filter(|queue| queue.workspace.as_deref() == Some(user.name.as_str()));
+        for queue in owned {
+            queue.output_dir = user.output_dir.clone();
+        }
+    }
+}
+
+pub fn load_queues(path: &Path) -> Result<Vec<NamedQueue>> {
+    let mut queues: Vec<NamedQueue> = if path.exists() {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse {}", path.display()))?
+    } else {
+        Vec::new()
+    };
+    if !queues.iter().any(|queue| queue.id == DEFAULT_QUEUE_ID) {
+        queues.insert(0, default_queue());
+    }
+    Ok(queues)
+}
+
+pub fn save_queues(path: &Path, queues: &[NamedQueue]) -> Result<()> {
+    let content = serde_json::to_string_pretty(queues)?;
+    fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+}
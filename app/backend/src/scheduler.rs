@@ -0,0 +1,208 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Local, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::queues::default_queue_id;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: String,
+    pub name: Option<String>,
+    pub cron: String,
+    #[serde(default = "default_queue_id")]
+    pub queue_id: String,
+    // Unset follows the queue's own format and quality.
+    #[serde(default)]
+    pub format: Option<String>,
+    pub quality: Option<u32>,
+    pub enabled: bool,
+    pub last_run: Option<ScheduleRun>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScheduleRun {
+    pub started_at: i64,
+    pub batch_id: Option<String>,
+    pub started: usize,
+    pub error: Option<String>,
+}
+
+impl Schedule {
+    pub fn is_due(&self, now: &DateTime<Local>) -> bool {
+        let minute = now.timestamp() / 60;
+        self.enabled
+            && self
+                .last_run
+                .as_ref()
+                .is_none_or(|run| run.started_at / 60 != minute)
+            && CronExpr::parse(&self.cron).is_ok_and(|cron| cron.matches(now))
+    }
+}
+
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronExpr {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err("cron expression must have 5 fields".to_string());
+        };
+        // Both 0 and 7 mean Sunday.
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            // `*/2` is still unrestricted for the day-of-month/day-of-week rule, as in cron.
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    pub fn matches(&self, at: &DateTime<Local>) -> bool {
+        let bit = |mask: u64, value: u32| mask & (1 << value) != 0;
+        let day = bit(self.days, at.day());
+        let weekday = bit(self.weekdays, at.weekday().num_days_from_sunday());
+        // Like cron, a restricted day of month and day of week match if either does.
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        bit(self.minutes, at.minute())
+            && bit(self.hours, at.hour())
+            && bit(self.months, at.month())
+            && day_matches
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("invalid step in cron field `{field}`"))?;
+                if step == 0 {
+                    return Err(format!("invalid step in cron field `{field}`"));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start, field)?, parse_value(end, field)?),
+                None => {
+                    let value = parse_value(range, field)?;
+                    let end = if part.contains('/') { max } else { value };
+                    (value, end)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("cron field `{field}` must be within {min}-{max}"));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(value: &str, field: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value in cron field `{field}`"))
+}
+
+pub fn load_schedules(path: &Path) -> Result<Vec<Schedule>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+pub fn save_schedules(path: &Path, schedules: &[Schedule]) -> Result<()> {
+    let content = serde_json::to_string_pretty(schedules)?;
+    fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        // June 2024: the 2nd is a Sunday and the 3rd a Monday.
+        Local.with_ymd_and_hms(2024, 6, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_ranges_lists_and_steps() {
+        assert_eq!(parse_field("1-3", 0, 59), Ok(0b1110));
+        assert_eq!(parse_field("1,4", 0, 59), Ok(0b10010));
+        assert_eq!(parse_field("*/20", 0, 59), Ok(1 | 1 << 20 | 1 << 40));
+        assert_eq!(parse_field("10-20/5", 0, 59), Ok(1 << 10 | 1 << 15 | 1 << 20));
+        assert_eq!(parse_field("50/5", 0, 59), Ok(1 << 50 | 1 << 55));
+    }
+
+    #[test]
+    fn rejects_out_of_range_and_malformed_fields() {
+        assert!(parse_field("60", 0, 59).is_err());
+        assert!(parse_field("5-1", 0, 59).is_err());
+        assert!(parse_field("*/0", 0, 59).is_err());
+        assert!(parse_field("a", 0, 59).is_err());
+        assert!(CronExpr::parse("* * * *").is_err());
+    }
+
+    #[test]
+    fn treats_zero_and_seven_as_sunday() {
+        for expr in ["0 12 * * 0", "0 12 * * 7"] {
+            let cron = CronExpr::parse(expr).unwrap();
+            assert!(cron.matches(&at(2, 12, 0)), "{expr}");
+            assert!(!cron.matches(&at(3, 12, 0)), "{expr}");
+        }
+    }
+
+    #[test]
+    fn matches_either_restricted_day_field() {
+        // The 3rd is a Monday, the 5th a Wednesday.
+        let cron = CronExpr::parse("0 12 5 * 1").unwrap();
+        assert!(cron.matches(&at(3, 12, 0)));
+        assert!(cron.matches(&at(5, 12, 0)));
+        assert!(!cron.matches(&at(4, 12, 0)));
+    }
+
+    #[test]
+    fn requires_both_day_fields_when_one_starts_with_a_star() {
+        // Odd days that are also Mondays: the 3rd matches, the 10th and 5th do not.
+        let cron = CronExpr::parse("0 12 */2 * 1").unwrap();
+        assert!(cron.matches(&at(3, 12, 0)));
+        assert!(!cron.matches(&at(10, 12, 0)));
+        assert!(!cron.matches(&at(5, 12, 0)));
+        let cron = CronExpr::parse("0 12 5 * *").unwrap();
+        assert!(cron.matches(&at(5, 12, 0)));
+        assert!(!cron.matches(&at(3, 12, 0)));
+    }
+}
@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::queues::NamedQueue;
+use crate::settings::AppSettings;
+use crate::types::{AppState, DownloadBatch, DownloadState, QueueItem};
+
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub queue: Vec<QueueItem>,
+    pub settings: AppSettings,
+    #[serde(default)]
+    pub batches: Vec<DownloadBatch>,
+    #[serde(default)]
+    pub queues: Vec<NamedQueue>,
+}
+
+pub fn reset_interrupted(item: &mut QueueItem) {
+    if !matches!(item.state, DownloadState::Working | DownloadState::Recording) {
+        return;
+    }
+    item.state = DownloadState::Waiting;
+    item.stage = None;
+    item.stage_progress = None;
+    item.progress = None;
+    item.downloaded_bytes = None;
+    item.total_bytes = None;
+    item.speed = None;
+    item.eta = None;
+}
+
+pub fn load_queue(path: &Path) -> Result<Vec<QueueItem>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let mut queue: Vec<QueueItem> = serde_json::from_str(&content)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    queue.iter_mut().for_each(reset_interrupted);
+    Ok(queue)
+}
+
+pub async fn autosave_queue(state: AppState, path: PathBuf) {
+    let mut last_saved = None;
+    loop {
+        tokio::time::sleep(AUTOSAVE_INTERVAL).await;
+        let content = match serde_json::to_vec_pretty(&*state.queue.lock().await) {
+            Ok(content) => content,
+            Err(err) => {
+                error!("failed to serialize queue: {err}");
+                continue;
+            }
+        };
+        if last_saved.as_ref() == Some(&content) {
+            continue;
+        }
+        match write_atomic(&path, &content).await {
+            Ok(()) => last_saved = Some(content),
+            Err(err) => error!("failed to save queue: {err}"),
+        }
+    }
+}
+
+async fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let temp_path = path.with_extension("json.tmp");
+    tokio::fs::write(&temp_path, content)
+        .await
+        .with_context(|| format!("failed to write {}", temp_path.display()))?;
+    tokio::fs::rename(&temp_path, path)
+        .await
+        .with_context(|| format!("failed to replace {}", path.display()))
+}
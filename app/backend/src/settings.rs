@@ -0,0 +1,768 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::cover::CoverArtProvider;
+use crate::library::MediaServerKind;
+use crate::lyrics::LyricsProvider;
+use crate::metadata::TitleRule;
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub title_cleanup: TitleCleanupSettings,
+    pub artist_title_split: ArtistTitleSplitSettings,
+    pub artists: ArtistJoinSettings,
+    pub genre: GenreSettings,
+    pub tagging: TaggingSettings,
+    pub lyrics: LyricsSettings,
+    pub cover_art: CoverArtSettings,
+    pub silence_trim: SilenceTrimSettings,
+    pub cue_sheet: CueSheetSettings,
+    pub resample: ResampleSettings,
+    pub livestream: LivestreamSettings,
+    pub archive: ArchiveSettings,
+    pub output: OutputSettings,
+    pub network: NetworkSettings,
+    pub cookies: CookieSettings,
+    pub metadata_cache: MetadataCacheSettings,
+    pub playlist: PlaylistSettings,
+    pub webhooks: WebhookSettings,
+    pub notifications: NotificationSettings,
+    pub hooks: HookSettings,
+    pub media_server: MediaServerSettings,
+    pub upload: UploadSettings,
+    pub metadata_sidecar: MetadataSidecarSettings,
+    pub file_browser: FileBrowserSettings,
+    pub storage: StorageSettings,
+    pub preview_cache: PreviewCacheSettings,
+    // Read at startup, like `--headless`; disables the folder picker, reveal and desktop toasts.
+    pub headless: bool,
+    pub clipboard: ClipboardSettings,
+    pub server: ServerSettings,
+}
+
+impl AppSettings {
+    // Secrets are write-only over the API: reads blank them, and writing a blank value back
+    // keeps the saved one. Removing a secret means editing settings.json.
+    pub fn redacted(&self) -> Self {
+        let mut settings = self.clone();
+        let hide = |secret: &mut String| {
+            if !secret.is_empty() {
+                secret.clear();
+            }
+        };
+        if let Some(token) = settings.server.auth_token.as_mut() {
+            hide(token);
+        }
+        settings.server.users.iter_mut().for_each(|user| hide(&mut user.token));
+        if let Some(s3) = settings.upload.s3.as_mut() {
+            hide(&mut s3.secret_access_key);
+        }
+        if let Some(password) = settings.upload.webdav.as_mut().and_then(|w| w.password.as_mut()) {
+            hide(password);
+        }
+        for endpoint in &mut settings.webhooks.endpoints {
+            if let Some(secret) = endpoint.secret.as_mut() {
+                hide(secret);
+            }
+        }
+        hide(&mut settings.media_server.token);
+        settings
+    }
+
+    pub fn keep_secrets(&mut self, current: &AppSettings) {
+        let keep = |secret: &mut String, saved: Option<&String>| {
+            if let Some(saved) = saved.filter(|_| secret.is_empty()) {
+                *secret = saved.clone();
+            }
+        };
+        if let Some(token) = self.server.auth_token.as_mut() {
+            keep(token, current.server.auth_token.as_ref());
+        }
+        for user in &mut self.server.users {
+            let saved = current.server.users.iter().find(|saved| saved.name == user.name);
+            keep(&mut user.token, saved.map(|saved| &saved.token));
+        }
+        if let (Some(s3), Some(saved)) = (self.upload.s3.as_mut(), current.upload.s3.as_ref()) {
+            keep(&mut s3.secret_access_key, Some(&saved.secret_access_key));
+        }
+        let webdav = self.upload.webdav.as_mut().and_then(|webdav| webdav.password.as_mut());
+        let saved = current.upload.webdav.as_ref().and_then(|webdav| webdav.password.as_ref());
+        if let Some(password) = webdav {
+            keep(password, saved);
+        }
+        for endpoint in &mut self.webhooks.endpoints {
+            let saved = current
+                .webhooks
+                .endpoints
+                .iter()
+                .find(|saved| saved.url == endpoint.url)
+                .and_then(|saved| saved.secret.as_ref());
+            if let Some(secret) = endpoint.secret.as_mut() {
+                keep(secret, saved);
+            }
+        }
+        keep(&mut self.media_server.token, Some(&current.media_server.token));
+    }
+}
+
+// Read at startup only.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerSettings {
+    pub address: String,
+    // Advertise `_audiodl._tcp` over mDNS whenever `address` is reachable from the LAN.
+    pub mdns: bool,
+    // Required for any non-loopback `address`; then every API request needs `auth_token`.
+    pub remote: bool,
+    pub auth_token: Option<String>,
+    pub tls: Option<TlsSettings>,
+    // Base URL podcast feeds link episodes to, e.g. behind a reverse proxy; otherwise the
+    // request's `Host` header is used.
+    pub public_url: Option<String>,
+    // IPs or CIDR ranges allowed to connect in remote mode, loopback included; empty allows any.
+    pub allowed_ips: Vec<String>,
+    // Remote mode tokens scoped to one user's queues and history; `auth_token` sees everything.
+    pub users: Vec<UserSettings>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UserSettings {
+    pub name: String,
+    pub token: String,
+    #[serde(default)]
+    pub output_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TlsSettings {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:47815".to_string(),
+            mdns: true,
+            remote: false,
+            auth_token: None,
+            tls: None,
+            public_url: None,
+            allowed_ips: Vec::new(),
+            users: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipboardSettings {
+    pub enabled: bool,
+    // Queue detected links right away instead of listing them at /api/clipboard.
+    pub auto_add: bool,
+    // Hosts (and their subdomains) whose links count as media URLs.
+    pub hosts: Vec<String>,
+}
+
+impl Default for ClipboardSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            auto_add: false,
+            hosts: ["youtube.com", "youtu.be", "soundcloud.com", "bandcamp.com", "vimeo.com"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreviewCacheSettings {
+    pub max_bytes: Option<u64>,
+    pub max_age_secs: Option<u64>,
+    // Download only this many seconds (from the item's start) instead of the full track.
+    pub clip_seconds: Option<u32>,
+}
+
+impl Default for PreviewCacheSettings {
+    fn default() -> Self {
+        Self {
+            max_bytes: Some(2 * 1024 * 1024 * 1024),
+            max_age_secs: Some(30 * 24 * 60 * 60),
+            clip_seconds: None,
+        }
+    }
+}
+
+// Directory changes take effect on the next start, since the preview cache is served statically.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageSettings {
+    pub preview_dir: Option<PathBuf>,
+    pub temp_dir: Option<PathBuf>,
+}
+
+impl StorageSettings {
+    pub fn resolve_preview_dir(&self) -> PathBuf {
+        self.preview_dir
+            .clone()
+            .unwrap_or_else(|| data_dir(dirs::cache_dir()).join("preview_cache"))
+    }
+
+    pub fn resolve_temp_dir(&self) -> PathBuf {
+        self.temp_dir
+            .clone()
+            .unwrap_or_else(|| data_dir(Some(std::env::temp_dir())))
+    }
+}
+
+fn data_dir(base: Option<PathBuf>) -> PathBuf {
+    base.unwrap_or_else(|| PathBuf::from(".")).join("AudioDownloader")
+}
+
+// Saved state lives under the platform directory `base` so packaged installs don't write next to
+// the binary; a file an older version left under `app/` keeps being used.
+pub fn state_path(project_root: &Path, base: Option<PathBuf>, name: &str) -> PathBuf {
+    let legacy = project_root.join("app").join(name);
+    match legacy.exists() {
+        true => legacy,
+        false => data_dir(base).join(name),
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileBrowserSettings {
+    // Directories the browser may list; the home directory when empty.
+    pub roots: Vec<PathBuf>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataSidecarSettings {
+    pub write_json: bool,
+    pub write_nfo: bool,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UploadSettings {
+    pub s3: Option<S3Settings>,
+    pub webdav: Option<WebDavSettings>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebDavSettings {
+    // Folder to upload into, e.g. `https://cloud.example.com/remote.php/dav/files/me/Music`.
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub conflict_policy: ConflictPolicy,
+    pub retries: u32,
+}
+
+impl Default for WebDavSettings {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            username: None,
+            password: None,
+            conflict_policy: ConflictPolicy::Rename,
+            retries: 3,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct S3Settings {
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub prefix: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    // MinIO and most self-hosted services expect path-style `endpoint/bucket/key` urls.
+    pub path_style: bool,
+    pub public_url: Option<String>,
+}
+
+impl Default for S3Settings {
+    fn default() -> Self {
+        Self {
+            endpoint: "https://s3.amazonaws.com".to_string(),
+            region: "us-east-1".to_string(),
+            bucket: String::new(),
+            prefix: String::new(),
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+            path_style: true,
+            public_url: None,
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MediaServerSettings {
+    pub kind: Option<MediaServerKind>,
+    pub base_url: String,
+    // Plex/Jellyfin API token, or the user's password for Navidrome.
+    pub token: String,
+    pub username: Option<String>,
+    // Plex library section to scan; every section is refreshed when unset.
+    pub library_id: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HookSettings {
+    pub post_download: Option<String>,
+    pub timeout_secs: u64,
+}
+
+impl Default for HookSettings {
+    fn default() -> Self {
+        Self {
+            post_download: None,
+            timeout_secs: 300,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub desktop: bool,
+    pub on_batch_complete: bool,
+    pub on_item_failed: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            desktop: false,
+            on_batch_complete: true,
+            on_item_failed: true,
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookSettings {
+    pub endpoints: Vec<WebhookEndpoint>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WebhookEndpoint {
+    pub url: String,
+    #[serde(default)]
+    pub secret: Option<String>,
+    // An empty list subscribes the endpoint to every event.
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    #[serde(default)]
+    pub format: WebhookFormat,
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    #[default]
+    Json,
+    Discord,
+    Slack,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    ItemComplete,
+    ItemFailed,
+    BatchComplete,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaylistSettings {
+    pub write_m3u8: bool,
+    pub paths: PlaylistPaths,
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistPaths {
+    #[default]
+    Relative,
+    Absolute,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataCacheSettings {
+    pub enabled: bool,
+    pub ttl_secs: u64,
+}
+
+impl Default for MetadataCacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CookieSettings {
+    pub browser: Option<String>,
+    pub profile: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    pub limit_rate: Option<String>,
+    pub concurrent_fragments: u32,
+    pub max_concurrent_downloads: usize,
+    pub max_concurrent_probes: usize,
+    pub probe_jitter_ms: u64,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            limit_rate: None,
+            concurrent_fragments: 1,
+            max_concurrent_downloads: 6,
+            max_concurrent_probes: 2,
+            probe_jitter_ms: 500,
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputSettings {
+    pub conflict_policy: ConflictPolicy,
+    // Unset follows the platform; enable it elsewhere when saving to a Windows share.
+    pub windows_safe_names: Option<bool>,
+    // Lifts the 260-character MAX_PATH budget for systems with long paths enabled.
+    pub long_paths: bool,
+    // Only the file name is transliterated; tags keep the original title.
+    pub ascii_filenames: bool,
+    // Runs of whitespace become this string, e.g. "_"; unset keeps spaces.
+    pub space_replacement: Option<String>,
+    pub lowercase_filenames: bool,
+    // In bytes, without the extension.
+    pub max_filename_length: Option<usize>,
+    // Files go to `<artist>/<album>/` below the output folder.
+    pub organize_folders: bool,
+}
+
+impl OutputSettings {
+    pub fn windows_safe(&self) -> bool {
+        self.windows_safe_names.unwrap_or(cfg!(windows))
+    }
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    Overwrite,
+    Skip,
+    #[default]
+    Rename,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveSettings {
+    pub enabled: bool,
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LivestreamSettings {
+    pub live_from_start: bool,
+    pub max_duration_secs: Option<u64>,
+    pub report_interval_secs: u64,
+}
+
+impl Default for LivestreamSettings {
+    fn default() -> Self {
+        Self {
+            live_from_start: true,
+            max_duration_secs: None,
+            report_interval_secs: 5,
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResampleSettings {
+    pub sample_rate: Option<u32>,
+    pub channels: Option<ChannelLayout>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelLayout {
+    Mono,
+    Stereo,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CueSheetSettings {
+    pub enabled: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SilenceTrimSettings {
+    pub enabled: bool,
+    pub threshold_db: f32,
+    pub keep_seconds: f32,
+    pub trim_end: bool,
+}
+
+impl Default for SilenceTrimSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_db: -50.0,
+            keep_seconds: 0.2,
+            trim_end: true,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoverArtSettings {
+    pub providers: Vec<CoverArtProvider>,
+    pub itunes_size: u32,
+    pub square_crop: bool,
+    pub max_dimension: Option<u32>,
+    pub max_bytes: Option<u64>,
+}
+
+impl Default for CoverArtSettings {
+    fn default() -> Self {
+        Self {
+            providers: vec![
+                CoverArtProvider::CoverArtArchive,
+                CoverArtProvider::Itunes,
+                CoverArtProvider::Thumbnail,
+            ],
+            itunes_size: 1200,
+            square_crop: true,
+            max_dimension: Some(1000),
+            max_bytes: Some(500_000),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LyricsSettings {
+    pub write_lrc: bool,
+    pub providers: Vec<LyricsProvider>,
+    pub languages: Vec<String>,
+    pub include_auto_captions: bool,
+}
+
+impl Default for LyricsSettings {
+    fn default() -> Self {
+        Self {
+            write_lrc: false,
+            providers: vec![LyricsProvider::Lrclib, LyricsProvider::Captions],
+            languages: vec!["en.*".to_string()],
+            include_auto_captions: true,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaggingSettings {
+    pub write_provenance: bool,
+}
+
+impl Default for TaggingSettings {
+    fn default() -> Self {
+        Self {
+            write_provenance: true,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenreSettings {
+    pub enabled: bool,
+    pub use_category_fallback: bool,
+    pub keywords: BTreeMap<String, String>,
+}
+
+impl Default for GenreSettings {
+    fn default() -> Self {
+        let keywords = [
+            ("hip hop", "Hip-Hop"),
+            ("hiphop", "Hip-Hop"),
+            ("rap", "Hip-Hop"),
+            ("r&b", "R&B"),
+            ("rnb", "R&B"),
+            ("rock", "Rock"),
+            ("metal", "Metal"),
+            ("punk", "Punk"),
+            ("pop", "Pop"),
+            ("k-pop", "K-Pop"),
+            ("kpop", "K-Pop"),
+            ("j-pop", "J-Pop"),
+            ("jpop", "J-Pop"),
+            ("jazz", "Jazz"),
+            ("blues", "Blues"),
+            ("classical", "Classical"),
+            ("country", "Country"),
+            ("folk", "Folk"),
+            ("reggae", "Reggae"),
+            ("electronic", "Electronic"),
+            ("edm", "Electronic"),
+            ("house", "House"),
+            ("techno", "Techno"),
+            ("ambient", "Ambient"),
+            ("lofi", "Lo-Fi"),
+            ("lo-fi", "Lo-Fi"),
+            ("soundtrack", "Soundtrack"),
+            ("ost", "Soundtrack"),
+        ];
+        Self {
+            enabled: true,
+            use_category_fallback: false,
+            keywords: keywords
+                .iter()
+                .map(|(keyword, genre)| (keyword.to_string(), genre.to_string()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArtistJoinSettings {
+    pub separator: String,
+    pub featuring_separator: String,
+    pub multi_value_tags: bool,
+}
+
+impl Default for ArtistJoinSettings {
+    fn default() -> Self {
+        Self {
+            separator: ", ".to_string(),
+            featuring_separator: " feat. ".to_string(),
+            multi_value_tags: true,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArtistTitleSplitSettings {
+    pub enabled: bool,
+    pub min_confidence: f32,
+}
+
+impl Default for ArtistTitleSplitSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_confidence: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TitleCleanupSettings {
+    pub enabled: bool,
+    pub rules: Vec<TitleRule>,
+}
+
+impl Default for TitleCleanupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rules: vec![
+                TitleRule::StripBracketed {
+                    keywords: [
+                        "official", "video", "audio", "lyric", "visualizer", "mv", "hd", "hq",
+                        "4k", "remastered",
+                    ]
+                    .iter()
+                    .map(|keyword| keyword.to_string())
+                    .collect(),
+                },
+                TitleRule::StripEmoji,
+                TitleRule::Trim,
+            ],
+        }
+    }
+}
+
+pub fn load_settings(path: &Path) -> Result<AppSettings> {
+    if !path.exists() {
+        return Ok(AppSettings::default());
+    }
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+// Settings hold the access tokens and upload credentials.
+pub fn save_settings(path: &Path, settings: &AppSettings) -> Result<()> {
+    let content = serde_json::to_string_pretty(settings)?;
+    write_private(path, content.as_bytes())
+}
+
+// Created owner-only from the start and renamed into place, so secrets are never readable by
+// others, not even briefly, and a crash never leaves a half-written file.
+pub fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+    let _ = fs::remove_file(&temp_path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options
+        .open(&temp_path)
+        .and_then(|mut file| file.write_all(content).and_then(|()| file.sync_all()));
+    if let Err(err) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err).with_context(|| format!("failed to write {}", temp_path.display()));
+    }
+    fs::rename(&temp_path, path).with_context(|| format!("failed to replace {}", path.display()))
+}
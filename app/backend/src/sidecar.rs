@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+
+use crate::media::clip_duration;
+use crate::metadata::format_yt_dlp_date;
+use crate::port::escape_xml;
+use crate::settings::MetadataSidecarSettings;
+use crate::types::{Chapter, QueueItem};
+
+#[derive(Serialize)]
+struct MetadataSidecar<'a> {
+    id: &'a str,
+    source_url: &'a str,
+    title: &'a str,
+    artist: &'a str,
+    artists: &'a [String],
+    album: Option<&'a str>,
+    genre: Option<&'a str>,
+    year: Option<u32>,
+    date: Option<&'a str>,
+    uploader: Option<&'a str>,
+    channel_url: Option<&'a str>,
+    upload_date: Option<&'a str>,
+    description: Option<&'a str>,
+    duration: Option<u64>,
+    chapters: &'a [Chapter],
+    tags: BTreeMap<&'a str, &'a str>,
+    file: &'a str,
+    downloaded_at: String,
+}
+
+pub async fn write_metadata_sidecars(
+    audio_path: &Path,
+    item: &QueueItem,
+    settings: &MetadataSidecarSettings,
+) -> Result<Vec<PathBuf>> {
+    let file_name = audio_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("invalid audio file name"))?;
+    let mut written = Vec::new();
+    if settings.write_json {
+        let sidecar = MetadataSidecar {
+            id: &item.id,
+            source_url: &item.youtube_url,
+            title: &item.title,
+            artist: &item.artist,
+            artists: &item.artists,
+            album: item.album.as_deref(),
+            genre: item.genre.as_deref(),
+            year: item.year,
+            date: item.date.as_deref(),
+            uploader: item.uploader.as_deref(),
+            channel_url: item.channel_url.as_deref(),
+            upload_date: item.upload_date.as_deref(),
+            description: item.description.as_deref(),
+            duration: item.duration,
+            chapters: &item.chapters,
+            tags: item
+                .custom_tags
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect(),
+            file: file_name,
+            downloaded_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        };
+        let content = serde_json::to_vec_pretty(&sidecar)?;
+        // `song.info.json` rather than `song.json`, matching yt-dlp's own metadata dumps.
+        let path = audio_path.with_extension("info.json");
+        tokio::fs::write(&path, content)
+            .await
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        written.push(path);
+    }
+    if settings.write_nfo {
+        let path = audio_path.with_extension("nfo");
+        tokio::fs::write(&path, build_nfo(item))
+            .await
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+// Kodi's music video NFO is the closest widely-read schema for a single downloaded track.
+fn build_nfo(item: &QueueItem) -> String {
+    let mut nfo = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+    nfo.push_str("<musicvideo>\n");
+    let mut element = |name: &str, value: &str| {
+        if !value.trim().is_empty() {
+            let _ = writeln!(nfo, "  <{name}>{}</{name}>", escape_xml(value.trim()));
+        }
+    };
+    element("title", &item.title);
+    for artist in &item.artists {
+        element("artist", artist);
+    }
+    if item.artists.is_empty() {
+        element("artist", &item.artist);
+    }
+    element("album", item.album.as_deref().unwrap_or_default());
+    element("genre", item.genre.as_deref().unwrap_or_default());
+    if let Some(year) = item.year {
+        element("year", &year.to_string());
+    }
+    let premiered = item.upload_date.as_deref().and_then(format_yt_dlp_date);
+    element("premiered", premiered.as_deref().unwrap_or_default());
+    element("studio", item.uploader.as_deref().unwrap_or_default());
+    element("plot", item.description.as_deref().unwrap_or_default());
+    if let Some(duration) = item.duration {
+        element("runtime", &(duration / 60).to_string());
+    }
+    element("source", &item.youtube_url);
+    let _ = writeln!(nfo, "  <uniqueid type=\"youtube\">{}</uniqueid>", escape_xml(&item.id));
+    nfo.push_str("</musicvideo>\n");
+    nfo
+}
+
+// `leading_trim` is the silence cut from the start after download, which moves every chapter.
+pub async fn write_cue_sheet(
+    audio_path: &Path,
+    item: &QueueItem,
+    leading_trim: Option<f32>,
+) -> Result<PathBuf> {
+    let file_name = audio_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("invalid audio file name"))?;
+    let content = build_cue_sheet(item, file_name, leading_trim);
+    let path = audio_path.with_extension("cue");
+    tokio::fs::write(&path, content)
+        .await
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+fn build_cue_sheet(item: &QueueItem, file_name: &str, leading_trim: Option<f32>) -> String {
+    let file_type = match Path::new(file_name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+    {
+        "mp3" => "MP3",
+        "aiff" | "aif" => "AIFF",
+        _ => "WAVE",
+    };
+    let offset = item.start.unwrap_or(0.0);
+    let trimmed = leading_trim.map(f64::from).unwrap_or(0.0);
+    let length = clip_duration(item);
+
+    let mut cue = String::new();
+    let _ = writeln!(cue, "PERFORMER \"{}\"", escape_cue(&item.artist));
+    let _ = writeln!(cue, "TITLE \"{}\"", escape_cue(&item.title));
+    let _ = writeln!(cue, "FILE \"{}\" {file_type}", escape_cue(file_name));
+
+    let chapters = item.chapters.iter().filter_map(|chapter| {
+        let start = (chapter.start_time - offset).max(0.0);
+        let ends_before_clip = chapter.end_time.is_some_and(|end| end <= offset + trimmed);
+        let starts_after_clip = length.is_some_and(|length| start >= length);
+        (!ends_before_clip && !starts_after_clip).then_some(((start - trimmed).max(0.0), chapter))
+    });
+    for (index, (start, chapter)) in chapters.enumerate() {
+        let title = if chapter.title.trim().is_empty() {
+            format!("Track {}", index + 1)
+        } else {
+            chapter.title.trim().to_string()
+        };
+        let _ = writeln!(cue, "  TRACK {:02} AUDIO", index + 1);
+        let _ = writeln!(cue, "    TITLE \"{}\"", escape_cue(&title));
+        let _ = writeln!(cue, "    PERFORMER \"{}\"", escape_cue(&item.artist));
+        let _ = writeln!(cue, "    INDEX 01 {}", format_cue_time(start));
+    }
+    cue
+}
+
+fn format_cue_time(seconds: f64) -> String {
+    let frames = (seconds * 75.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        frames / (75 * 60),
+        (frames / 75) % 60,
+        frames % 75
+    )
+}
+
+fn escape_cue(value: &str) -> String {
+    value.replace('"', "'")
+}
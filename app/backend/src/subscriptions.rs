@@ -0,0 +1,60 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::queues::default_queue_id;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: String,
+    #[serde(default)]
+    pub kind: SubscriptionKind,
+    pub url: String,
+    pub name: Option<String>,
+    #[serde(default = "default_queue_id")]
+    pub queue_id: String,
+    pub interval_secs: u64,
+    pub enabled: bool,
+    pub backfill: bool,
+    pub auto_download: bool,
+    pub format: String,
+    pub quality: Option<u32>,
+    pub last_checked: Option<i64>,
+    pub last_error: Option<String>,
+    pub last_added: usize,
+    #[serde(default)]
+    pub seen: Vec<String>,
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionKind {
+    #[default]
+    Playlist,
+    Feed,
+}
+
+impl Subscription {
+    pub fn is_due(&self, now: i64) -> bool {
+        self.enabled
+            && self
+                .last_checked
+                .is_none_or(|checked| now - checked >= self.interval_secs as i64)
+    }
+}
+
+pub fn load_subscriptions(path: &Path) -> Result<Vec<Subscription>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+pub fn save_subscriptions(path: &Path, subscriptions: &[Subscription]) -> Result<()> {
+    let content = serde_json::to_string_pretty(subscriptions)?;
+    fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+}
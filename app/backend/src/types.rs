@@ -1,8 +1,23 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use tokio::sync::{Mutex, Semaphore};
+use tokio::sync::{Mutex, Notify, Semaphore};
+
+use crate::archive::DownloadArchive;
+use crate::clipboard::ClipboardCandidate;
+use crate::cookies::CookieSource;
+use crate::errors::ErrorCode;
+use crate::jobs::JobRegistry;
+use crate::metadata::TitleRule;
+use crate::port::ExportColumn;
+use crate::presets::Preset;
+use crate::queues::{default_queue_id, NamedQueue};
+use crate::scheduler::Schedule;
+use crate::settings::AppSettings;
+use crate::subscriptions::{Subscription, SubscriptionKind};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -10,21 +25,120 @@ pub struct AppState {
     pub preview_dir: PathBuf,
     pub temp_dir: PathBuf,
     pub download_semaphore: Arc<Semaphore>,
+    pub metadata_semaphore: Arc<Semaphore>,
     pub client: reqwest::Client,
     pub project_root: PathBuf,
+    pub settings: Arc<Mutex<AppSettings>>,
+    pub settings_path: PathBuf,
+    pub recordings: Arc<Mutex<HashMap<ItemKey, Arc<Notify>>>>,
+    pub archive: Arc<Mutex<DownloadArchive>>,
+    pub archive_path: PathBuf,
+    pub cookies_path: PathBuf,
+    pub cookies: Arc<Mutex<CookieSource>>,
+    pub metadata_cache_dir: PathBuf,
+    pub jobs: JobRegistry,
+    pub batches: Arc<Mutex<HashMap<String, DownloadBatch>>>,
+    // Item id to the job downloading its preview.
+    pub pending_previews: Arc<Mutex<HashMap<String, String>>>,
+    // Output paths (without extension) claimed by downloads in flight, to the claiming item.
+    // Never held across an await, so a dropped download can release its claim synchronously.
+    pub output_names: Arc<std::sync::Mutex<HashMap<PathBuf, String>>>,
+    pub queues: Arc<Mutex<Vec<NamedQueue>>>,
+    pub queues_path: PathBuf,
+    pub subscriptions: Arc<Mutex<Vec<Subscription>>>,
+    pub subscriptions_path: PathBuf,
+    pub schedules: Arc<Mutex<Vec<Schedule>>>,
+    pub schedules_path: PathBuf,
+    pub presets: Arc<Mutex<Vec<Preset>>>,
+    pub presets_path: PathBuf,
+    // No display to open dialogs, file managers or toasts on.
+    pub headless: bool,
+    // Copied links waiting to be accepted or dismissed.
+    pub clipboard: Arc<Mutex<Vec<ClipboardCandidate>>>,
 }
 
-#[derive(Clone, Serialize)]
+// Queue id and item id; the same video may sit in several queues.
+pub type ItemKey = (String, String);
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct QueueItem {
     pub id: String,
+    #[serde(default = "default_queue_id")]
+    pub queue_id: String,
     pub youtube_url: String,
     pub title: String,
+    pub raw_title: String,
+    pub keep_raw_title: bool,
     pub artist: String,
+    pub artists: Vec<String>,
+    pub split_confidence: Option<f32>,
+    pub album: Option<String>,
+    pub year: Option<u32>,
+    // Set while the year is only the upload date's, so a looked-up release year can replace it.
+    #[serde(default)]
+    pub year_from_upload: bool,
+    pub date: Option<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    #[serde(default)]
+    pub compilation: bool,
+    pub genre: Option<String>,
+    pub custom_tags: HashMap<String, String>,
+    pub musicbrainz_recording_id: Option<String>,
+    pub musicbrainz_release_id: Option<String>,
     pub thumbnail_url: Option<String>,
+    pub thumbnail_candidates: Vec<String>,
     pub duration: Option<u64>,
+    pub estimated_size: Option<u64>,
     pub state: DownloadState,
+    pub stage: Option<DownloadStage>,
+    pub stage_progress: Option<f32>,
     pub progress: Option<f32>,
+    pub downloaded_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+    pub speed: Option<f64>,
+    pub eta: Option<u64>,
     pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+    #[serde(skip)]
+    pub attempts: Vec<DownloadAttempt>,
+    pub trimmed_silence: Option<f32>,
+    pub fade_in: Option<f32>,
+    pub fade_out: Option<f32>,
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    pub chapters: Vec<Chapter>,
+    pub format_id: Option<String>,
+    pub audio_languages: Vec<String>,
+    pub audio_language: Option<String>,
+    pub is_live: bool,
+    pub recorded_bytes: Option<u64>,
+    pub recorded_seconds: Option<u64>,
+    pub scheduled_at: Option<i64>,
+    pub remote_url: Option<String>,
+    pub uploader: Option<String>,
+    pub channel_url: Option<String>,
+    pub upload_date: Option<String>,
+    pub description: Option<String>,
+    pub sha256: Option<String>,
+    pub file_path: Option<PathBuf>,
+    pub file_size: Option<u64>,
+    pub warning: Option<String>,
+    #[serde(default)]
+    pub force_download: bool,
+}
+
+impl QueueItem {
+    // API routes address items by queue and id, so one queue never reaches into another.
+    pub fn matches_key(&self, queue_id: &str, id: &str) -> bool {
+        self.queue_id == queue_id && self.id == id
+    }
+
+    // Names the item's temporary files and output claims, which must not collide across queues.
+    pub fn scratch_name(&self) -> String {
+        format!("{}-{}", self.queue_id, self.id)
+    }
 }
 
 #[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -32,20 +146,232 @@ pub struct QueueItem {
 pub enum DownloadState {
     Waiting,
     Working,
+    Recording,
+    Scheduled,
+    Paused,
+    Cancelled,
+    Complete,
+    Failed,
+}
+
+impl DownloadState {
+    // Paused and cancelled items only leave through Waiting, so a batch that was dispatched
+    // before the user paused them cannot pick them up again.
+    pub fn can_transition_to(self, next: DownloadState) -> bool {
+        use DownloadState::*;
+        if self == next {
+            return true;
+        }
+        match self {
+            Waiting => true,
+            Scheduled => matches!(next, Waiting | Paused | Cancelled),
+            Working => matches!(next, Waiting | Paused | Cancelled | Complete | Failed),
+            // A finished recording is converted, tagged and published as Working.
+            Recording => matches!(next, Waiting | Working | Cancelled | Complete | Failed),
+            Paused | Cancelled => matches!(next, Waiting | Cancelled),
+            Complete => matches!(next, Waiting | Working | Recording),
+            Failed => matches!(next, Waiting | Working | Recording | Cancelled | Complete),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadStage {
+    Downloading,
+    Recording,
+    Converting,
+    PostProcessing,
+    Tagging,
+    Publishing,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DownloadAttempt {
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub outcome: Option<AttemptOutcome>,
+    pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+    pub exit_status: Option<i32>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AttemptOutcome {
     Complete,
+    Skipped,
     Failed,
 }
 
 #[derive(Deserialize)]
 pub struct AddRequest {
     pub url: String,
+    #[serde(default)]
+    pub refresh: bool,
+    #[serde(default)]
+    pub allow_duplicate: bool,
+    #[serde(default)]
+    pub force: bool,
 }
 
 #[derive(Deserialize)]
 pub struct UpdateRequest {
     pub id: String,
+    #[serde(flatten)]
+    pub changes: ItemChanges,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ItemChanges {
     pub title: Option<String>,
     pub artist: Option<String>,
+    pub artists: Option<Vec<String>>,
+    pub album: Option<String>,
+    pub year: Option<u32>,
+    pub date: Option<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub compilation: Option<bool>,
+    pub genre: Option<String>,
+    pub custom_tags: Option<HashMap<String, String>>,
+    pub keep_raw_title: Option<bool>,
+    pub fade_in: Option<f32>,
+    pub fade_out: Option<f32>,
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    pub format_id: Option<String>,
+    pub audio_language: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BatchUpdateRequest {
+    pub ids: Vec<String>,
+    #[serde(flatten)]
+    pub changes: ItemChanges,
+    // Applied to each title after `changes`, e.g. a `replace` rule for find/replace.
+    #[serde(default)]
+    pub title_rules: Vec<TitleRule>,
+}
+
+#[derive(Deserialize)]
+pub struct EnrichRequest {
+    pub ids: Option<Vec<String>>,
+    pub overwrite: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct WaveformQuery {
+    pub points: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct WaveformResponse {
+    pub duration: f64,
+    pub peaks: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+pub struct FeedQuery {
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ThumbnailQuery {
+    pub size: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct BrowserSelection {
+    pub browser: String,
+    pub profile: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct FormatsQuery {
+    pub url: String,
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub refresh: bool,
+    #[serde(default)]
+    pub mode: ImportMode,
+    pub queue: Option<String>,
+    #[serde(default)]
+    pub allow_duplicates: bool,
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Deserialize)]
+pub struct QueueQuery {
+    pub queue: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateSubscriptionRequest {
+    #[serde(default)]
+    pub kind: SubscriptionKind,
+    pub url: String,
+    pub name: Option<String>,
+    pub queue: Option<String>,
+    pub interval_secs: Option<u64>,
+    #[serde(default)]
+    pub backfill: bool,
+    #[serde(default)]
+    pub auto_download: bool,
+    pub format: Option<String>,
+    pub quality: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateScheduleRequest {
+    pub name: Option<String>,
+    pub cron: String,
+    pub queue: Option<String>,
+    pub format: Option<String>,
+    pub quality: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateQueueRequest {
+    pub name: String,
+    pub output_dir: Option<PathBuf>,
+    pub format: Option<String>,
+    pub quality: Option<u32>,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    #[default]
+    Append,
+    Replace,
+    Merge,
+}
+
+#[derive(Deserialize)]
+pub struct SheetImportRequest {
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+pub struct PlaylistQuery {
+    #[serde(default)]
+    pub format: PlaylistFormat,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistFormat {
+    #[default]
+    M3u8,
+    Xspf,
 }
 
 #[derive(Deserialize)]
@@ -55,17 +381,147 @@ pub struct ClearRequest {
 
 #[derive(Deserialize)]
 pub struct DownloadRequest {
+    // May be omitted when `preset` names one.
+    #[serde(default)]
     pub format: String,
+    pub quality: Option<u32>,
+    pub limit_rate: Option<String>,
+    // Prefixes file names with the item's position in the batch and writes it as the track.
+    #[serde(default)]
+    pub number_tracks: bool,
+    // Name of a saved preset whose values replace the ones above.
+    #[serde(default)]
+    pub preset: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RetryFailedRequest {
+    pub format: String,
+    pub quality: Option<u32>,
+    pub limit_rate: Option<String>,
+    #[serde(default)]
+    pub clear_attempts: bool,
 }
 
 #[derive(Deserialize)]
 pub struct ExportRequest {
     pub format: String,
+    #[serde(default)]
+    pub columns: Vec<ExportColumn>,
 }
 
 #[derive(Serialize)]
 pub struct DownloadResponse {
     pub started: usize,
+    pub batch_id: String,
+}
+
+#[derive(Serialize)]
+pub struct CancelAllResponse {
+    pub cancelled: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BatchItem {
+    pub id: String,
+    pub title: String,
+    pub job_id: String,
+    pub outcome: Option<AttemptOutcome>,
+    pub error: Option<String>,
+    pub bytes: Option<u64>,
+    pub path: Option<PathBuf>,
+    pub artist: String,
+    pub duration: Option<u64>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DownloadBatch {
+    pub id: String,
+    pub dir: PathBuf,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub items: Vec<BatchItem>,
+    pub playlist: Option<PathBuf>,
+    #[serde(default)]
+    pub workspace: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub pending: usize,
+    pub total_bytes: u64,
+    pub elapsed_secs: u64,
+}
+
+#[derive(Serialize)]
+pub struct BatchReport {
+    pub id: String,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub summary: BatchSummary,
+    pub items: Vec<BatchItem>,
+    pub playlist: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+pub struct FsListQuery {
+    pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+#[derive(Serialize)]
+pub struct FsListResponse {
+    pub path: Option<PathBuf>,
+    pub parent: Option<PathBuf>,
+    pub writable: bool,
+    pub entries: Vec<FsEntry>,
+}
+
+#[derive(Serialize)]
+pub struct FsEntry {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct SyncRequest {
+    pub prune: bool,
+    pub dry_run: bool,
+}
+
+#[derive(Serialize)]
+pub struct SyncReport {
+    pub subscription_id: String,
+    pub total: usize,
+    pub present: usize,
+    pub missing: Vec<String>,
+    pub queued: usize,
+    pub removed: Vec<SyncRemoval>,
+    pub pruned: bool,
+    pub batch_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SyncRemoval {
+    pub id: String,
+    pub title: String,
+    pub path: PathBuf,
+}
+
+#[derive(Serialize)]
+pub struct PruneResponse {
+    pub removed: usize,
+}
+
+#[derive(Serialize)]
+pub struct JobResponse {
+    pub job_id: String,
 }
 
 #[derive(Serialize)]
@@ -75,7 +531,8 @@ pub struct DefaultDirResponse {
 
 #[derive(Serialize)]
 pub struct PreviewResponse {
-    pub url: String,
+    pub url: Option<String>,
+    pub job_id: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -93,21 +550,137 @@ pub struct YtDlpInfo {
     pub title: Option<String>,
     pub uploader: Option<String>,
     pub channel: Option<String>,
+    pub channel_url: Option<String>,
+    pub description: Option<String>,
     pub thumbnail: Option<String>,
     pub thumbnails: Option<Vec<YtDlpThumb>>,
     pub duration: Option<f64>,
+    pub categories: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
+    pub upload_date: Option<String>,
+    pub release_date: Option<String>,
+    pub release_year: Option<u32>,
+    pub chapters: Option<Vec<Chapter>>,
+    pub formats: Option<Vec<YtDlpFormat>>,
+    pub is_live: Option<bool>,
+    pub live_status: Option<String>,
+    pub release_timestamp: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct YtDlpFormat {
+    pub format_id: String,
+    pub ext: Option<String>,
+    pub acodec: Option<String>,
+    pub vcodec: Option<String>,
+    pub abr: Option<f64>,
+    pub asr: Option<u32>,
+    pub audio_channels: Option<u32>,
+    pub filesize: Option<u64>,
+    pub filesize_approx: Option<u64>,
+    pub language: Option<String>,
+    pub format_note: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AudioFormat {
+    pub format_id: String,
+    pub ext: Option<String>,
+    pub codec: String,
+    pub bitrate: Option<f64>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+    pub filesize: Option<u64>,
+    pub language: Option<String>,
+    pub note: Option<String>,
+    pub audio_only: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Chapter {
+    pub start_time: f64,
+    pub end_time: Option<f64>,
+    #[serde(default)]
+    pub title: String,
 }
 
 #[derive(Deserialize)]
 pub struct YtDlpThumb {
     pub url: Option<String>,
+    pub id: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub preference: Option<i32>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct VideoInfo {
     pub id: String,
     pub title: String,
     pub artist: String,
+    pub uploader: Option<String>,
+    pub channel_url: Option<String>,
+    pub description: Option<String>,
     pub thumbnail_url: Option<String>,
+    pub thumbnail_candidates: Vec<String>,
     pub duration: Option<u64>,
+    pub categories: Vec<String>,
+    pub tags: Vec<String>,
+    pub upload_date: Option<String>,
+    pub release_date: Option<String>,
+    pub release_year: Option<u32>,
+    pub chapters: Vec<Chapter>,
+    pub audio_formats: Vec<AudioFormat>,
+    pub live_status: Option<String>,
+    pub release_timestamp: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DownloadState::{self, *};
+
+    const ALL: [DownloadState; 8] =
+        [Waiting, Working, Recording, Scheduled, Paused, Cancelled, Complete, Failed];
+
+    #[test]
+    fn waiting_can_move_anywhere_and_every_state_can_stay() {
+        for state in ALL {
+            assert!(Waiting.can_transition_to(state));
+            assert!(state.can_transition_to(state));
+        }
+    }
+
+    #[test]
+    fn paused_and_cancelled_only_leave_through_waiting() {
+        for state in [Paused, Cancelled] {
+            let allowed: Vec<_> = ALL
+                .into_iter()
+                .filter(|next| *next != state && state.can_transition_to(*next))
+                .collect();
+            let expected = if state == Paused { vec![Waiting, Cancelled] } else { vec![Waiting] };
+            assert!(allowed == expected);
+        }
+    }
+
+    #[test]
+    fn finished_items_can_be_restarted() {
+        assert!(Complete.can_transition_to(Working));
+        assert!(Failed.can_transition_to(Recording));
+        assert!(!Complete.can_transition_to(Failed));
+        assert!(!Complete.can_transition_to(Paused));
+    }
+
+    #[test]
+    fn finished_recordings_move_on_to_working() {
+        assert!(Recording.can_transition_to(Working));
+        assert!(!Working.can_transition_to(Recording));
+    }
+
+    #[test]
+    fn running_items_cannot_be_rescheduled() {
+        assert!(Working.can_transition_to(Complete));
+        assert!(!Working.can_transition_to(Scheduled));
+        assert!(!Recording.can_transition_to(Paused));
+        assert!(!Scheduled.can_transition_to(Working));
+    }
 }
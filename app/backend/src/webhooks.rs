@@ -0,0 +1,280 @@
+use std::time::Duration;
+
+use chrono::{SecondsFormat, Utc};
+use reqwest::header::CONTENT_TYPE;
+use ring::hmac;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::{error, warn};
+
+use crate::settings::{WebhookEndpoint, WebhookEvent, WebhookFormat, WebhookSettings};
+use crate::types::{BatchReport, QueueItem};
+
+const APP_NAME: &str = "AudioDownloader";
+const SIGNATURE_HEADER: &str = "X-AudioDownloader-Signature";
+const EVENT_HEADER: &str = "X-AudioDownloader-Event";
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+// Discord rejects embed field values longer than this.
+const MAX_FIELD_LEN: usize = 1024;
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(untagged)]
+pub enum WebhookData<'a> {
+    Item(&'a QueueItem),
+    Batch(&'a BatchReport),
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    event: WebhookEvent,
+    sent_at: String,
+    data: WebhookData<'a>,
+}
+
+pub fn validate_webhooks(settings: &WebhookSettings) -> Result<(), String> {
+    for endpoint in &settings.endpoints {
+        let url = reqwest::Url::parse(&endpoint.url)
+            .map_err(|err| format!("invalid webhook url {}: {err}", endpoint.url))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!(
+                "webhook url must use http or https: {}",
+                endpoint.url
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn dispatch_webhooks(
+    client: &reqwest::Client,
+    settings: &WebhookSettings,
+    event: WebhookEvent,
+    data: WebhookData<'_>,
+) {
+    let endpoints = settings
+        .endpoints
+        .iter()
+        .filter(|endpoint| endpoint.events.is_empty() || endpoint.events.contains(&event));
+    for endpoint in endpoints {
+        let body = match webhook_body(endpoint.format, event, data) {
+            Ok(body) => body,
+            Err(err) => {
+                error!("failed to serialize webhook payload: {err}");
+                continue;
+            }
+        };
+        let client = client.clone();
+        let endpoint: WebhookEndpoint = endpoint.clone();
+        // Delivery runs detached so a slow receiver never holds up the download pipeline.
+        tokio::spawn(async move {
+            let mut request = client
+                .post(&endpoint.url)
+                .timeout(WEBHOOK_TIMEOUT)
+                .header(CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, event_name(event));
+            if let Some(secret) = endpoint
+                .secret
+                .as_deref()
+                .filter(|secret| !secret.is_empty())
+            {
+                request = request.header(SIGNATURE_HEADER, sign(secret, &body));
+            }
+            match request.body(body).send().await {
+                Ok(response) if !response.status().is_success() => {
+                    warn!(
+                        "webhook {} responded with {}",
+                        endpoint.url,
+                        response.status()
+                    );
+                }
+                Ok(_) => {}
+                Err(err) => warn!("webhook {} failed: {err}", endpoint.url),
+            }
+        });
+    }
+}
+
+fn webhook_body(
+    format: WebhookFormat,
+    event: WebhookEvent,
+    data: WebhookData<'_>,
+) -> serde_json::Result<Vec<u8>> {
+    match format {
+        WebhookFormat::Json => serde_json::to_vec(&WebhookPayload {
+            event,
+            sent_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            data,
+        }),
+        WebhookFormat::Discord => serde_json::to_vec(&discord_message(event, data)),
+        WebhookFormat::Slack => serde_json::to_vec(&slack_message(event, data)),
+    }
+}
+
+struct Summary {
+    heading: &'static str,
+    title: String,
+    url: Option<String>,
+    thumbnail: Option<String>,
+    fields: Vec<(&'static str, String)>,
+}
+
+fn summarize(event: WebhookEvent, data: WebhookData<'_>) -> Summary {
+    let heading = match event {
+        WebhookEvent::ItemComplete => "Download complete",
+        WebhookEvent::ItemFailed => "Download failed",
+        WebhookEvent::BatchComplete => "Batch finished",
+    };
+    match data {
+        WebhookData::Item(item) => {
+            let mut fields = vec![("Artist", item.artist.clone())];
+            if let Some(duration) = item.duration {
+                fields.push(("Duration", format_duration(duration)));
+            }
+            fields.push(("Outcome", outcome_label(event).to_string()));
+            if let Some(error) = item
+                .error
+                .as_deref()
+                .filter(|_| event == WebhookEvent::ItemFailed)
+            {
+                fields.push(("Error", error.to_string()));
+            }
+            Summary {
+                heading,
+                title: item.title.clone(),
+                url: Some(item.youtube_url.clone()),
+                thumbnail: item.thumbnail_url.clone(),
+                fields,
+            }
+        }
+        WebhookData::Batch(report) => {
+            let summary = &report.summary;
+            Summary {
+                heading,
+                title: format!("{} of {} downloaded", summary.succeeded, summary.total),
+                url: None,
+                thumbnail: None,
+                fields: vec![
+                    ("Failed", summary.failed.to_string()),
+                    ("Skipped", summary.skipped.to_string()),
+                    ("Elapsed", format_duration(summary.elapsed_secs)),
+                ],
+            }
+        }
+    }
+}
+
+fn discord_message(event: WebhookEvent, data: WebhookData<'_>) -> Value {
+    let summary = summarize(event, data);
+    let color = match event {
+        WebhookEvent::ItemComplete => 0x2ecc71,
+        WebhookEvent::ItemFailed => 0xe74c3c,
+        WebhookEvent::BatchComplete => 0x3498db,
+    };
+    let fields: Vec<Value> = summary
+        .fields
+        .iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(name, value)| {
+            json!({
+                "name": name,
+                "value": truncate(value, MAX_FIELD_LEN),
+                "inline": *name != "Error",
+            })
+        })
+        .collect();
+    let mut embed = json!({
+        "author": { "name": summary.heading },
+        "title": truncate(&summary.title, 256),
+        "color": color,
+        "fields": fields,
+        "timestamp": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    });
+    if let Some(url) = summary.url {
+        embed["url"] = json!(url);
+    }
+    if let Some(thumbnail) = summary.thumbnail {
+        embed["thumbnail"] = json!({ "url": thumbnail });
+    }
+    json!({ "username": APP_NAME, "embeds": [embed] })
+}
+
+fn slack_message(event: WebhookEvent, data: WebhookData<'_>) -> Value {
+    let summary = summarize(event, data);
+    let title = match &summary.url {
+        Some(url) => format!("<{}|{}>", url, slack_escape(&summary.title)),
+        None => slack_escape(&summary.title),
+    };
+    let mut lines = vec![format!("*{}*", summary.heading), title];
+    lines.extend(
+        summary
+            .fields
+            .iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(name, value)| format!("*{name}:* {}", slack_escape(value))),
+    );
+    let mut section = json!({
+        "type": "section",
+        "text": { "type": "mrkdwn", "text": lines.join("\n") },
+    });
+    if let Some(thumbnail) = summary.thumbnail {
+        section["accessory"] = json!({
+            "type": "image",
+            "image_url": thumbnail,
+            "alt_text": summary.title,
+        });
+    }
+    json!({
+        "text": format!("{}: {}", summary.heading, summary.title),
+        "blocks": [section],
+    })
+}
+
+fn outcome_label(event: WebhookEvent) -> &'static str {
+    match event {
+        WebhookEvent::ItemComplete => "Complete",
+        WebhookEvent::ItemFailed => "Failed",
+        WebhookEvent::BatchComplete => "Finished",
+    }
+}
+
+fn format_duration(secs: u64) -> String {
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, minutes, seconds) => format!("{minutes}:{seconds:02}"),
+        (hours, minutes, seconds) => format!("{hours}:{minutes:02}:{seconds:02}"),
+    }
+}
+
+fn truncate(value: &str, max: usize) -> String {
+    if value.chars().count() <= max {
+        return value.to_string();
+    }
+    let mut truncated: String = value.chars().take(max - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn slack_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, body);
+    let hex: String = tag
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("sha256={hex}")
+}
+
+fn event_name(event: WebhookEvent) -> &'static str {
+    match event {
+        WebhookEvent::ItemComplete => "item_complete",
+        WebhookEvent::ItemFailed => "item_failed",
+        WebhookEvent::BatchComplete => "batch_complete",
+    }
+}
@@ -1,4 +1,4 @@
-import { API_BASE, PreviewResponse, QueueItem, VersionInfo } from "./state";
+import { API_BASE, Job, PreviewResponse, QueueItem, VersionInfo } from "./state";
 
 export async function fetchQueue(): Promise<QueueItem[] | null> {
   const response = await fetch(`${API_BASE}/api/queue`);
@@ -25,18 +25,27 @@ export async function fetchDefaultDir(): Promise<string> {
   return data.path;
 }
 
-export async function postAddQueue(url: string): Promise<boolean> {
+export async function postAddQueue(
+  url: string,
+  allowDuplicate = false,
+): Promise<{ ok: boolean; duplicate?: string }> {
   const response = await fetch(`${API_BASE}/api/queue/add`, {
     method: "POST",
     headers: { "Content-Type": "application/json" },
-    body: JSON.stringify({ url }),
+    body: JSON.stringify({ url, allow_duplicate: allowDuplicate }),
   });
-  return response.ok;
+  if (response.status === 409) {
+    const data = (await response.json()) as { error?: string };
+    if (data.error?.startsWith("possible duplicate")) {
+      return { ok: false, duplicate: data.error };
+    }
+  }
+  return { ok: response.ok };
 }
 
 export async function postUpdateQueue(
   id: string,
-  payload: { title?: string; artist?: string },
+  payload: { title?: string; artist?: string; audio_language?: string },
 ): Promise<void> {
   await fetch(`${API_BASE}/api/queue/update`, {
     method: "POST",
@@ -45,6 +54,14 @@ export async function postUpdateQueue(
   });
 }
 
+export async function postStopRecording(id: string): Promise<void> {
+  await fetch(`${API_BASE}/api/queue/${id}/stop`, { method: "POST" });
+}
+
+export async function postRevealFile(id: string): Promise<void> {
+  await fetch(`${API_BASE}/api/files/${id}/reveal`, { method: "POST" });
+}
+
 export async function deleteQueueItem(id: string): Promise<void> {
   await fetch(`${API_BASE}/api/queue/${id}`, { method: "DELETE" });
 }
@@ -57,19 +74,44 @@ export async function postClearQueue(mode: string): Promise<void> {
   });
 }
 
-export async function postDownloadAll(format: string): Promise<void> {
+export async function postDownloadAll(format: string, quality: number | null): Promise<void> {
   await fetch(`${API_BASE}/api/download`, {
     method: "POST",
     headers: { "Content-Type": "application/json" },
-    body: JSON.stringify({ format }),
+    body: JSON.stringify({ format, quality }),
+  });
+}
+
+export async function postRetryFailed(format: string, quality: number | null): Promise<void> {
+  await fetch(`${API_BASE}/api/queue/retry-failed`, {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ format, quality }),
   });
 }
 
-export async function postImportQueue(file: File): Promise<boolean> {
+export async function postImportQueue(file: File, mode: string): Promise<string | null> {
   const form = new FormData();
   form.append("file", file);
-  const response = await fetch(`${API_BASE}/api/import`, { method: "POST", body: form });
-  return response.ok;
+  const url = `${API_BASE}/api/import?mode=${encodeURIComponent(mode)}`;
+  const response = await fetch(url, { method: "POST", body: form });
+  if (!response.ok) {
+    return null;
+  }
+  const data = (await response.json()) as { job_id: string };
+  return data.job_id;
+}
+
+export async function fetchJob(id: string): Promise<Job | null> {
+  const response = await fetch(`${API_BASE}/api/jobs/${id}`);
+  if (!response.ok) {
+    return null;
+  }
+  return (await response.json()) as Job;
+}
+
+export async function postCancelJob(id: string): Promise<void> {
+  await fetch(`${API_BASE}/api/jobs/${id}/cancel`, { method: "POST" });
 }
 
 export async function postExportQueue(format: string): Promise<Blob | null> {
@@ -3,6 +3,7 @@ import "./style.css";
 import {
   deleteQueueItem,
   fetchDefaultDir,
+  fetchJob,
   fetchPreview,
   fetchQueue,
   fetchSample,
@@ -12,6 +13,9 @@ import {
   postDownloadAll,
   postExportQueue,
   postImportQueue,
+  postRetryFailed,
+  postRevealFile,
+  postStopRecording,
   postUpdateQueue,
 } from "./api";
 import { state } from "./state";
@@ -24,6 +28,7 @@ if (!app) {
 }
 
 async function bootstrap(): Promise<void> {
+  storeAccessToken();
   if (!app.dataset.initialized) {
     renderShell(app);
     bindEvents();
@@ -65,7 +70,9 @@ function bindEvents(): void {
   const addBtn = document.querySelector<HTMLButtonElement>("#addBtn");
   const urlInput = document.querySelector<HTMLInputElement>("#urlInput");
   const formatSelect = document.querySelector<HTMLSelectElement>("#formatSelect");
+  const qualityInput = document.querySelector<HTMLInputElement>("#qualityInput");
   const exportFormatSelect = document.querySelector<HTMLSelectElement>("#exportFormatSelect");
+  const importModeSelect = document.querySelector<HTMLSelectElement>("#importModeSelect");
   const downloadBtn = document.querySelector<HTMLButtonElement>("#downloadBtn");
   const exportBtn = document.querySelector<HTMLButtonElement>("#exportBtn");
   const sampleBtn = document.querySelector<HTMLButtonElement>("#sampleBtn");
@@ -73,6 +80,7 @@ function bindEvents(): void {
   const importBrowseBtn = document.querySelector<HTMLButtonElement>("#importBrowseBtn");
   const clearCompleteBtn = document.querySelector<HTMLButtonElement>("#clearCompleteBtn");
   const clearFailedBtn = document.querySelector<HTMLButtonElement>("#clearFailedBtn");
+  const retryFailedBtn = document.querySelector<HTMLButtonElement>("#retryFailedBtn");
   const clearAllBtn = document.querySelector<HTMLButtonElement>("#clearAllBtn");
 
   addBtn?.addEventListener("click", async () => {
@@ -115,10 +123,19 @@ function bindEvents(): void {
     state.format = formatSelect.value;
   });
 
+  qualityInput?.addEventListener("change", () => {
+    const value = Number.parseInt(qualityInput.value, 10);
+    state.quality = Number.isNaN(value) ? null : value;
+  });
+
   exportFormatSelect?.addEventListener("change", () => {
     state.exportFormat = exportFormatSelect.value;
   });
 
+  importModeSelect?.addEventListener("change", () => {
+    state.importMode = importModeSelect.value;
+  });
+
   downloadBtn?.addEventListener("click", async () => {
     await downloadAll();
   });
@@ -145,6 +162,7 @@ function bindEvents(): void {
 
   clearCompleteBtn?.addEventListener("click", () => clearQueue("complete"));
   clearFailedBtn?.addEventListener("click", () => clearQueue("failed"));
+  retryFailedBtn?.addEventListener("click", () => retryFailed());
   clearAllBtn?.addEventListener("click", () => clearQueue("all"));
   window.addEventListener("resize", syncActionsCollapse);
 
@@ -162,6 +180,23 @@ function bindEvents(): void {
     if (target.closest("button.delete")) {
       deleteItem(id);
     }
+    if (target.closest("button.stop")) {
+      postStopRecording(id);
+    }
+    if (target.closest("button.reveal")) {
+      postRevealFile(id);
+    }
+  });
+
+  queueSection?.addEventListener("change", (event) => {
+    const target = event.target as HTMLElement;
+    if (!(target instanceof HTMLSelectElement) || !target.classList.contains("audio-language")) {
+      return;
+    }
+    const id = target.closest<HTMLDivElement>(".queue-card")?.dataset.id;
+    if (id) {
+      updateQueue(id, { audio_language: target.value });
+    }
   });
 
   queueSection?.addEventListener("focusout", (event) => {
@@ -188,8 +223,11 @@ async function addQueue(url: string): Promise<void> {
   render();
   setBusy(true, "Fetching video info...");
   try {
-    const ok = await postAddQueue(url);
-    if (!ok) {
+    let result = await postAddQueue(url);
+    if (result.duplicate && window.confirm(`This looks like a ${result.duplicate}. Add it anyway?`)) {
+      result = await postAddQueue(url, true);
+    }
+    if (!result.ok) {
       return;
     }
     await loadQueue();
@@ -201,7 +239,10 @@ async function addQueue(url: string): Promise<void> {
   }
 }
 
-async function updateQueue(id: string, payload: { title?: string; artist?: string }): Promise<void> {
+async function updateQueue(
+  id: string,
+  payload: { title?: string; artist?: string; audio_language?: string },
+): Promise<void> {
   await postUpdateQueue(id, payload);
 }
 
@@ -221,18 +262,33 @@ async function downloadAll(): Promise<void> {
   if (!state.dir) {
     return;
   }
-  await postDownloadAll(state.format);
+  await postDownloadAll(state.format, state.quality);
+}
+
+async function retryFailed(): Promise<void> {
+  await postRetryFailed(state.format, state.quality);
+  await loadQueue();
+  renderQueue();
 }
 
 async function importQueue(file: File): Promise<void> {
   setBusy(true, "Loading items from file (yt-dlp can take a while)...");
   try {
-    const ok = await postImportQueue(file);
-    if (!ok) {
+    const jobId = await postImportQueue(file, state.importMode);
+    if (!jobId) {
       return;
     }
-    await loadQueue();
-    renderQueue();
+    for (;;) {
+      await new Promise((resolve) => setTimeout(resolve, 1000));
+      const job = await fetchJob(jobId);
+      await loadQueue();
+      renderQueue();
+      if (!job || job.state !== "running") {
+        break;
+      }
+      state.busyMessage = `Loading items from file (${job.processed}/${job.total})...`;
+      render();
+    }
   } finally {
     setBusy(false);
   }
@@ -255,8 +311,19 @@ async function downloadSample(): Promise<void> {
 }
 
 async function previewItem(id: string): Promise<void> {
-  const data = await fetchPreview(id);
-  if (!data) {
+  let data = await fetchPreview(id);
+  while (data && !data.url && data.job_id) {
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+    const job = await fetchJob(data.job_id);
+    if (!job || job.state === "running") {
+      continue;
+    }
+    if (job.state !== "complete") {
+      return;
+    }
+    data = await fetchPreview(id);
+  }
+  if (!data?.url) {
     return;
   }
   state.preview = { id, url: data.url };
@@ -280,6 +347,19 @@ function setBusy(active: boolean, message?: string): void {
   render();
 }
 
+// Remote mode links carry `?token=`; keep it as a cookie so every request (including audio
+// elements) is authorized, and drop it from the address bar.
+function storeAccessToken(): void {
+  const url = new URL(window.location.href);
+  const token = url.searchParams.get("token");
+  if (!token) {
+    return;
+  }
+  document.cookie = `audiodl_token=${encodeURIComponent(token)}; path=/; max-age=31536000; SameSite=Strict`;
+  url.searchParams.delete("token");
+  window.history.replaceState(null, "", url);
+}
+
 function triggerDownload(blob: Blob, name: string): void {
   const url = URL.createObjectURL(blob);
   const link = document.createElement("a");
@@ -1,13 +1,72 @@
+export type DownloadStage =
+  | "downloading"
+  | "recording"
+  | "converting"
+  | "post_processing"
+  | "tagging"
+  | "publishing";
+
+export type ErrorCode =
+  | "age_restricted"
+  | "private"
+  | "members_only"
+  | "removed"
+  | "geo_blocked"
+  | "rate_limited"
+  | "drm"
+  | "network"
+  | "unknown";
+
 export type QueueItem = {
   id: string;
   youtube_url: string;
   title: string;
+  raw_title: string;
+  keep_raw_title: boolean;
   artist: string;
+  artists: string[];
+  split_confidence?: number | null;
+  album?: string | null;
+  year?: number | null;
+  date?: string | null;
+  track_number?: number | null;
+  disc_number?: number | null;
+  disc_total?: number | null;
+  compilation?: boolean;
+  genre?: string | null;
+  custom_tags?: Record<string, string>;
+  musicbrainz_recording_id?: string | null;
+  musicbrainz_release_id?: string | null;
   thumbnail_url?: string;
+  thumbnail_candidates?: string[];
   duration?: number;
-  state: "WAITING" | "WORKING" | "COMPLETE" | "FAILED";
+  estimated_size?: number | null;
+  state: "WAITING" | "WORKING" | "RECORDING" | "SCHEDULED" | "PAUSED" | "CANCELLED" | "COMPLETE" | "FAILED";
+  stage?: DownloadStage | null;
+  stage_progress?: number | null;
   progress?: number | null;
+  downloaded_bytes?: number | null;
+  total_bytes?: number | null;
+  speed?: number | null;
+  eta?: number | null;
   error?: string | null;
+  error_code?: ErrorCode | null;
+  trimmed_silence?: number | null;
+  fade_in?: number | null;
+  fade_out?: number | null;
+  start?: number | null;
+  end?: number | null;
+  chapters?: { start_time: number; end_time?: number | null; title: string }[];
+  format_id?: string | null;
+  audio_languages?: string[];
+  audio_language?: string | null;
+  is_live?: boolean;
+  recorded_bytes?: number | null;
+  recorded_seconds?: number | null;
+  scheduled_at?: number | null;
+  file_path?: string | null;
+  file_size?: number | null;
+  warning?: string | null;
 };
 
 export type VersionInfo = {
@@ -19,16 +78,36 @@ export type VersionInfo = {
 };
 
 export type PreviewResponse = {
-  url: string;
+  url: string | null;
+  job_id: string | null;
+};
+
+export type Job = {
+  id: string;
+  kind: "import" | "download" | "preview" | "enrich";
+  state: "running" | "complete" | "failed" | "cancelled";
+  target?: string | null;
+  total: number;
+  processed: number;
+  succeeded: number;
+  skipped: number;
+  failed: number;
+  error?: string | null;
+  warnings?: string[];
+  created_at: string;
+  finished_at?: string | null;
 };
 
-export const API_BASE = "http://127.0.0.1:47815";
+// The production bundle is served by the backend itself, so it can use relative URLs.
+export const API_BASE = import.meta.env.DEV ? "http://127.0.0.1:47815" : "";
 
 export const state = {
   queue: [] as QueueItem[],
   version: null as VersionInfo | null,
   format: "flac",
+  quality: null as number | null,
   exportFormat: "xlsx",
+  importMode: "append",
   dir: "",
   preview: { id: "", url: "" },
   isBusy: false,
@@ -1,5 +1,14 @@
 import { API_BASE, state } from "./state";
-import { badgeContentFor, escapeHtml, stateLabel } from "./utils";
+import {
+  badgeContentFor,
+  errorHint,
+  escapeHtml,
+  recordingLabel,
+  savedLabel,
+  stageLabel,
+  stateLabel,
+  transferLabel,
+} from "./utils";
 
 export function renderShell(app: HTMLDivElement): void {
   app.innerHTML = `
@@ -34,8 +43,17 @@ export function renderShell(app: HTMLDivElement): void {
               <option value="mp3">mp3</option>
               <option value="m4a">m4a</option>
               <option value="wav">wav</option>
+              <option value="opus">opus</option>
+              <option value="vorbis">ogg vorbis</option>
+              <option value="alac">alac</option>
+              <option value="aiff">aiff</option>
+              <option value="source">original (no re-encode)</option>
             </select>
           </label>
+          <label>
+            Quality (kbps / flac level)
+            <input id="qualityInput" type="number" min="0" placeholder="best" />
+          </label>
           <label>
             Export format
             <select id="exportFormatSelect">
@@ -43,6 +61,14 @@ export function renderShell(app: HTMLDivElement): void {
               <option value="csv">csv</option>
             </select>
           </label>
+          <label>
+            Import mode
+            <select id="importModeSelect">
+              <option value="append">append</option>
+              <option value="replace">replace</option>
+              <option value="merge">merge</option>
+            </select>
+          </label>
         </div>
         <div class="actions">
           <button id="downloadBtn">Download All</button>
@@ -52,13 +78,14 @@ export function renderShell(app: HTMLDivElement): void {
             <summary class="ghost">More Actions</summary>
             <div class="actions-group">
               <button id="sampleBtn" class="ghost">Sample XLSX</button>
+              <button id="retryFailedBtn" class="ghost">Retry Failed</button>
               <button id="clearCompleteBtn" class="ghost">Delete Complete</button>
               <button id="clearFailedBtn" class="ghost">Delete Failed</button>
               <button id="clearAllBtn" class="ghost">Delete All (Except Working)</button>
             </div>
           </details>
         </div>
-        <input id="importInput" type="file" accept=".xlsx,.csv" />
+        <input id="importInput" type="file" accept=".xlsx,.ods,.csv" />
         <div id="busyStatus" class="busy-status" aria-live="polite"></div>
       </div>
 
@@ -108,6 +135,10 @@ export function render(): void {
   if (exportFormatSelect && document.activeElement !== exportFormatSelect) {
     exportFormatSelect.value = state.exportFormat;
   }
+  const importModeSelect = document.querySelector<HTMLSelectElement>("#importModeSelect");
+  if (importModeSelect && document.activeElement !== importModeSelect) {
+    importModeSelect.value = state.importMode;
+  }
   const busyStatus = document.querySelector<HTMLDivElement>("#busyStatus");
   if (busyStatus) {
     if (state.isBusy) {
@@ -159,24 +190,56 @@ export function renderQueue(): void {
       const badgeClass = item.state.toLowerCase();
       const isActive = item.id === state.preview.id;
       const activeClass = isActive ? " active" : "";
+      const postDownload = item.stage !== "downloading" && typeof item.stage_progress === "number";
+      const rawProgress = postDownload ? item.stage_progress : item.progress;
       const progressValue =
-        typeof item.progress === "number" ? Math.min(100, Math.max(0, item.progress)) : null;
+        typeof rawProgress === "number" ? Math.min(100, Math.max(0, rawProgress)) : null;
       const thumbnail = item.thumbnail_url
-        ? `<img src="${item.thumbnail_url}" alt="${escapeHtml(item.title)}" />`
+        ? `<img src="${API_BASE}/api/thumbnail/${encodeURIComponent(item.id)}?size=192" alt="${escapeHtml(item.title)}" />`
         : `<div class="thumb-placeholder"></div>`;
-      const error = item.error ? `title="${escapeHtml(item.error)}"` : "";
-      const statusLabel = stateLabel(item.state, progressValue);
+      const hint = errorHint(item.error_code);
+      const tooltip =
+        (item.error ? [item.error, hint].filter(Boolean).join("\n") : null) ??
+        (item.scheduled_at ? `Starts ${new Date(item.scheduled_at * 1000).toLocaleString()}` : null) ??
+        (item.state === "WORKING" ? transferLabel(item) : null) ??
+        (item.state === "COMPLETE"
+          ? [item.warning, savedLabel(item)].filter(Boolean).join("\n")
+          : "");
+      const error = tooltip ? `title="${escapeHtml(tooltip)}"` : "";
+      const statusLabel =
+        item.state === "RECORDING"
+          ? recordingLabel(item.recorded_seconds, item.recorded_bytes)
+          : ((item.state === "WORKING" && item.stage
+            ? stageLabel(item.stage, item.stage_progress)
+            : null) ??
+            stateLabel(item.state, progressValue));
       const badgeContent = badgeContentFor(item.state, progressValue, statusLabel);
+      const languages = item.audio_languages ?? [];
+      const languageSelect =
+        languages.length > 1
+          ? `<select class="audio-language">
+              <option value="">default audio</option>
+              ${languages
+                .map(
+                  (language) =>
+                    `<option value="${escapeHtml(language)}"${language === item.audio_language ? " selected" : ""}>${escapeHtml(language)}</option>`,
+                )
+                .join("")}
+            </select>`
+          : "";
       return `
         <div class="queue-card${activeClass}" data-id="${item.id}">
           ${thumbnail}
           <div class="queue-info">
             <input class="title" value="${escapeHtml(item.title)}" />
             <input class="artist" value="${escapeHtml(item.artist)}" />
+            ${languageSelect}
             <div class="badge ${badgeClass}" ${error}>${badgeContent}</div>
           </div>
           <div class="queue-actions">
             <button class="preview">Preview</button>
+            ${item.state === "RECORDING" ? `<button class="stop">Stop</button>` : ""}
+            ${item.state === "COMPLETE" && item.file_path ? `<button class="reveal">Show file</button>` : ""}
             <button class="delete">Remove</button>
           </div>
         </div>
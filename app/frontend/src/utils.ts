@@ -1,4 +1,4 @@
-import { QueueItem } from "./state";
+import { DownloadStage, ErrorCode, QueueItem } from "./state";
 
 export function escapeHtml(value: string): string {
   return value
@@ -21,6 +21,12 @@ export function stateLabel(state: QueueItem["state"], progress: number | null):
         return `${Math.round(progress)}%`;
       }
       return "0%";
+    case "SCHEDULED":
+      return "Scheduled";
+    case "PAUSED":
+      return "Paused";
+    case "CANCELLED":
+      return "Cancelled";
     case "COMPLETE":
       return "Finished";
     case "FAILED":
@@ -30,6 +36,91 @@ export function stateLabel(state: QueueItem["state"], progress: number | null):
   }
 }
 
+export function stageLabel(
+  stage: DownloadStage,
+  progress: number | null | undefined,
+): string | null {
+  let label: string;
+  switch (stage) {
+    case "converting":
+      label = "Converting";
+      break;
+    case "post_processing":
+      label = "Processing";
+      break;
+    case "tagging":
+      label = "Tagging";
+      break;
+    case "publishing":
+      label = "Saving";
+      break;
+    default:
+      return null;
+  }
+  return typeof progress === "number" ? `${label} ${Math.round(progress)}%` : label;
+}
+
+export function recordingLabel(
+  seconds: number | null | undefined,
+  bytes: number | null | undefined,
+): string {
+  const elapsed = seconds ?? 0;
+  const minutes = Math.floor(elapsed / 60);
+  const clock = `${minutes}:${String(elapsed % 60).padStart(2, "0")}`;
+  const size = ((bytes ?? 0) / (1024 * 1024)).toFixed(1);
+  return `REC ${clock} · ${size} MB`;
+}
+
+export function savedLabel(item: QueueItem): string {
+  if (!item.file_path) {
+    return "";
+  }
+  if (typeof item.file_size !== "number") {
+    return item.file_path;
+  }
+  return `${item.file_path} (${(item.file_size / (1024 * 1024)).toFixed(1)} MB)`;
+}
+
+export function transferLabel(item: QueueItem): string {
+  if (typeof item.downloaded_bytes !== "number") {
+    return "";
+  }
+  const toMb = (bytes: number) => (bytes / (1024 * 1024)).toFixed(1);
+  const parts = [
+    typeof item.total_bytes === "number"
+      ? `${toMb(item.downloaded_bytes)} / ${toMb(item.total_bytes)} MB`
+      : `${toMb(item.downloaded_bytes)} MB`,
+  ];
+  if (typeof item.speed === "number") {
+    parts.push(`${toMb(item.speed)} MB/s`);
+  }
+  if (typeof item.eta === "number") {
+    parts.push(`ETA ${Math.floor(item.eta / 60)}:${String(item.eta % 60).padStart(2, "0")}`);
+  }
+  return parts.join(" · ");
+}
+
+export function errorHint(code: ErrorCode | null | undefined): string {
+  switch (code) {
+    case "age_restricted":
+    case "members_only":
+    case "private":
+      return "Upload browser cookies from an account that can view this video.";
+    case "geo_blocked":
+      return "This video is blocked in your region.";
+    case "rate_limited":
+      return "YouTube is rate limiting requests; wait a while or add cookies.";
+    case "removed":
+      return "The video is no longer available.";
+    case "drm":
+      return "The stream is DRM protected and cannot be downloaded.";
+    case "network":
+      return "Check your network connection and retry.";
+    default:
+      return "";
+  }
+}
+
 export function badgeContentFor(
   state: QueueItem["state"],
   progress: number | null,